@@ -0,0 +1,59 @@
+// Converts a KJV USFM edition into data/kjv_verses.json, keeping the words
+// the translators supplied (\add) and the words of Christ (\wj) as markup.
+// Reads a folder of .usfm files, one per book, such as eBible.org's eng-kjv:
+//
+//     cargo run --example kjv_usfm_to_json -- path/to/eng-kjv_usfm > data/kjv_verses.json
+
+use std::path::Path;
+use std::process::ExitCode;
+use bible_verse_hunter_lib::import::{self, BibleFormat};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct BibleVerse<'a> {
+    b: i64,
+    c: i64,
+    v: i64,
+    t: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    m: Option<&'a str>,
+}
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("Usage: cargo run --example kjv_usfm_to_json -- <USFM file or folder> > data/kjv_verses.json");
+        return ExitCode::from(2);
+    };
+
+    let bible = match import::read_bible(Path::new(&path), Some(BibleFormat::Usfm)) {
+        Ok(bible) => bible,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // The bundled KJV has the 66 books and no Psalm titles
+    let verses: Vec<BibleVerse> = bible.verses.iter()
+        .filter(|v| v.verse > 0 && !v.text.is_empty())
+        .filter_map(|v| Some(BibleVerse {
+            b: v.book_id?,
+            c: v.chapter,
+            v: v.verse,
+            t: &v.text,
+            m: v.markup.as_deref(),
+        }))
+        .collect();
+
+    match serde_json::to_string(&verses) {
+        Ok(json) => {
+            println!("{}", json);
+            eprintln!("Converted {} verses", verses.len());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Cannot write JSON: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,30 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 2: full-text search over verse text
+
+-- External-content FTS5 index over verses.text
+CREATE VIRTUAL TABLE IF NOT EXISTS verses_fts USING fts5(
+    text,
+    content = 'verses',
+    content_rowid = 'id',
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+-- Keep the index in sync with the verses table
+CREATE TRIGGER IF NOT EXISTS verses_fts_insert AFTER INSERT ON verses BEGIN
+    INSERT INTO verses_fts (rowid, text) VALUES (new.id, new.text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_delete AFTER DELETE ON verses BEGIN
+    INSERT INTO verses_fts (verses_fts, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_update AFTER UPDATE OF text ON verses BEGIN
+    INSERT INTO verses_fts (verses_fts, rowid, text) VALUES ('delete', old.id, old.text);
+    INSERT INTO verses_fts (rowid, text) VALUES (new.id, new.text);
+END;
+
+-- Index any verses seeded before this migration
+INSERT INTO verses_fts (verses_fts) VALUES ('rebuild');
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (2, '002_verse_search');
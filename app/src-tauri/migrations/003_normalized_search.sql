@@ -0,0 +1,55 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 3: KJV-aware normalized search index
+
+-- verses_fts becomes the exact-match index: no stemming, case-insensitive words only
+DROP TRIGGER IF EXISTS verses_fts_insert;
+DROP TRIGGER IF EXISTS verses_fts_delete;
+DROP TRIGGER IF EXISTS verses_fts_update;
+DROP TABLE IF EXISTS verses_fts;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS verses_fts USING fts5(
+    text,
+    content = 'verses',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_insert AFTER INSERT ON verses BEGIN
+    INSERT INTO verses_fts (rowid, text) VALUES (new.id, new.text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_delete AFTER DELETE ON verses BEGIN
+    INSERT INTO verses_fts (verses_fts, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_update AFTER UPDATE OF text ON verses BEGIN
+    INSERT INTO verses_fts (verses_fts, rowid, text) VALUES ('delete', old.id, old.text);
+    INSERT INTO verses_fts (rowid, text) VALUES (new.id, new.text);
+END;
+
+INSERT INTO verses_fts (verses_fts) VALUES ('rebuild');
+
+-- Normalized index: archaic forms mapped to modern words (kjv_normalize is
+-- registered by the application), then Porter-stemmed
+CREATE VIRTUAL TABLE IF NOT EXISTS verses_fts_normalized USING fts5(
+    text,
+    tokenize = 'porter unicode61 remove_diacritics 2'
+);
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_normalized_insert AFTER INSERT ON verses BEGIN
+    INSERT INTO verses_fts_normalized (rowid, text) VALUES (new.id, kjv_normalize(new.text));
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_normalized_delete AFTER DELETE ON verses BEGIN
+    DELETE FROM verses_fts_normalized WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_normalized_update AFTER UPDATE OF text ON verses BEGIN
+    DELETE FROM verses_fts_normalized WHERE rowid = old.id;
+    INSERT INTO verses_fts_normalized (rowid, text) VALUES (new.id, kjv_normalize(new.text));
+END;
+
+INSERT INTO verses_fts_normalized (rowid, text) SELECT id, kjv_normalize(text) FROM verses;
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (3, '003_normalized_search');
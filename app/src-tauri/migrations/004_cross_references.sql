@@ -0,0 +1,25 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 4: cross references
+
+-- Links from a verse to a related verse or verse range. Built-in links come
+-- from the Treasury of Scripture Knowledge; users can add their own.
+CREATE TABLE IF NOT EXISTS cross_references (
+    id INTEGER PRIMARY KEY,
+    from_verse_id INTEGER NOT NULL,
+    to_verse_id INTEGER NOT NULL,           -- First verse of the target
+    to_verse_end_id INTEGER,                -- Last verse when the target is a range
+    votes INTEGER NOT NULL DEFAULT 0,       -- Relevance weight, higher is stronger
+    source TEXT NOT NULL DEFAULT 'tsk' CHECK (source IN ('tsk', 'user')),
+    note TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (from_verse_id) REFERENCES verses(id) ON DELETE CASCADE,
+    FOREIGN KEY (to_verse_id) REFERENCES verses(id) ON DELETE CASCADE,
+    FOREIGN KEY (to_verse_end_id) REFERENCES verses(id) ON DELETE CASCADE,
+    UNIQUE(from_verse_id, to_verse_id, source)
+);
+
+CREATE INDEX IF NOT EXISTS idx_cross_refs_from ON cross_references(from_verse_id, votes DESC);
+CREATE INDEX IF NOT EXISTS idx_cross_refs_to ON cross_references(to_verse_id);
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (4, '004_cross_references');
@@ -0,0 +1,74 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 5: multiple translations
+
+CREATE TABLE IF NOT EXISTS translations (
+    id INTEGER PRIMARY KEY,
+    code TEXT NOT NULL UNIQUE COLLATE NOCASE,  -- Short code used by the API, e.g. 'KJV'
+    name TEXT NOT NULL,
+    language TEXT NOT NULL DEFAULT 'en',
+    year INTEGER,
+    sort_order INTEGER NOT NULL DEFAULT 0
+);
+
+-- KJV keeps id 1 and stays the default for every verse query. The others
+-- have no verses until they are imported with import_bible.
+INSERT OR IGNORE INTO translations (id, code, name, language, year, sort_order) VALUES
+    (1, 'KJV', 'King James Version', 'en', 1769, 1),
+    (2, 'ASV', 'American Standard Version', 'en', 1901, 2),
+    (3, 'WEB', 'World English Bible', 'en', 2000, 3),
+    (4, 'YLT', 'Young''s Literal Translation', 'en', 1898, 4),
+    (5, 'DARBY', 'Darby Translation', 'en', 1890, 5);
+
+-- Rebuild verses with a translation id. Existing rows are KJV and keep their
+-- ids, so notes, highlights, topics and cross references stay attached.
+CREATE TABLE verses_new (
+    id INTEGER PRIMARY KEY,
+    translation_id INTEGER NOT NULL DEFAULT 1,
+    book_id INTEGER NOT NULL,
+    chapter INTEGER NOT NULL,
+    verse INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    FOREIGN KEY (translation_id) REFERENCES translations(id),
+    FOREIGN KEY (book_id) REFERENCES books(id),
+    UNIQUE(translation_id, book_id, chapter, verse)
+);
+
+INSERT INTO verses_new (id, translation_id, book_id, chapter, verse, text)
+SELECT id, 1, book_id, chapter, verse, text FROM verses;
+
+DROP TABLE verses;
+ALTER TABLE verses_new RENAME TO verses;
+
+CREATE INDEX IF NOT EXISTS idx_verses_book_chapter ON verses(book_id, chapter);
+CREATE INDEX IF NOT EXISTS idx_verses_lookup ON verses(book_id, chapter, verse);
+
+-- Dropping the table removed the search index triggers; both indexes keep
+-- their rows since verse ids are unchanged
+CREATE TRIGGER IF NOT EXISTS verses_fts_insert AFTER INSERT ON verses BEGIN
+    INSERT INTO verses_fts (rowid, text) VALUES (new.id, new.text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_delete AFTER DELETE ON verses BEGIN
+    INSERT INTO verses_fts (verses_fts, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_update AFTER UPDATE OF text ON verses BEGIN
+    INSERT INTO verses_fts (verses_fts, rowid, text) VALUES ('delete', old.id, old.text);
+    INSERT INTO verses_fts (rowid, text) VALUES (new.id, new.text);
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_normalized_insert AFTER INSERT ON verses BEGIN
+    INSERT INTO verses_fts_normalized (rowid, text) VALUES (new.id, kjv_normalize(new.text));
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_normalized_delete AFTER DELETE ON verses BEGIN
+    DELETE FROM verses_fts_normalized WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS verses_fts_normalized_update AFTER UPDATE OF text ON verses BEGIN
+    DELETE FROM verses_fts_normalized WHERE rowid = old.id;
+    INSERT INTO verses_fts_normalized (rowid, text) VALUES (new.id, kjv_normalize(new.text));
+END;
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (5, '005_translations');
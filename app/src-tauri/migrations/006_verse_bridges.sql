@@ -0,0 +1,9 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 6: merged verses
+
+-- Some translations join verses ("3-4"); the text is stored on the first
+-- verse and end_verse records the last verse it covers
+ALTER TABLE verses ADD COLUMN end_verse INTEGER;
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (6, '006_verse_bridges');
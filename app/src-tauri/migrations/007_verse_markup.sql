@@ -0,0 +1,9 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 7: verse markup
+
+-- Text with <i> around supplied (italicized) words and <j> around words of
+-- Christ. NULL when a verse has neither; text stays plain for search.
+ALTER TABLE verses ADD COLUMN markup TEXT;
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (7, '007_verse_markup');
@@ -0,0 +1,30 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 8: model prices for token usage cost estimates
+
+-- USD per million tokens. A row prices every model whose name starts with
+-- `model`, so 'claude-3-5-sonnet' covers its dated snapshots; the longest
+-- match wins. Models without a price (local ones) cost nothing.
+CREATE TABLE IF NOT EXISTS model_prices (
+    provider TEXT NOT NULL,
+    model TEXT NOT NULL,
+    input_per_million REAL NOT NULL,
+    output_per_million REAL NOT NULL,
+    PRIMARY KEY (provider, model)
+);
+
+INSERT OR IGNORE INTO model_prices (provider, model, input_per_million, output_per_million) VALUES
+    ('openai', 'gpt-4o-mini', 0.15, 0.60),
+    ('openai', 'gpt-4o', 2.50, 10.00),
+    ('openai', 'gpt-4.1-nano', 0.10, 0.40),
+    ('openai', 'gpt-4.1-mini', 0.40, 1.60),
+    ('openai', 'gpt-4.1', 2.00, 8.00),
+    ('claude', 'claude-3-haiku', 0.25, 1.25),
+    ('claude', 'claude-3-5-haiku', 0.80, 4.00),
+    ('claude', 'claude-3-5-sonnet', 3.00, 15.00),
+    ('claude', 'claude-3-7-sonnet', 3.00, 15.00),
+    ('claude', 'claude-sonnet-4', 3.00, 15.00),
+    ('claude', 'claude-3-opus', 15.00, 75.00),
+    ('claude', 'claude-opus-4', 15.00, 75.00);
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (8, '008_model_prices');
@@ -0,0 +1,16 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 9: where action steps and reflection questions come from
+
+-- Curated items are seeded with the app; 'ai' ones were generated and saved,
+-- with the model that wrote them; 'user' ones were written by the user.
+-- Saved steps are numbered after the ones a verse already has.
+ALTER TABLE action_steps ADD COLUMN source TEXT NOT NULL DEFAULT 'curated'
+    CHECK (source IN ('curated', 'ai', 'user'));
+ALTER TABLE action_steps ADD COLUMN model TEXT;
+
+ALTER TABLE reflection_questions ADD COLUMN source TEXT NOT NULL DEFAULT 'curated'
+    CHECK (source IN ('curated', 'ai', 'user'));
+ALTER TABLE reflection_questions ADD COLUMN model TEXT;
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (9, '009_application_sources');
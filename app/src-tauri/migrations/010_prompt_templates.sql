@@ -0,0 +1,13 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 10: edited prompt templates
+
+-- Only templates the user has changed have a row; the rest use the built-in
+-- body, and resetting a template deletes its row.
+CREATE TABLE IF NOT EXISTS prompt_templates (
+    name TEXT PRIMARY KEY,
+    body TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (10, '010_prompt_templates');
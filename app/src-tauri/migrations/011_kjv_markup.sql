@@ -0,0 +1,9 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 11: KJV markup backfill
+
+-- No schema change. A database whose KJV verses were seeded before version 7
+-- has no markup; when this migration is first applied, the startup seed
+-- fills it in from the bundled KJV text (seed::seed_kjv_markup).
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (11, '011_kjv_markup');
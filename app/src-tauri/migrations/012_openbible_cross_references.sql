@@ -0,0 +1,35 @@
+-- KJV Bible Verse Hunter Database Schema
+-- Version 12: cross references labelled by their real source
+
+-- The built-in cross references are OpenBible.info's (CC BY), not the
+-- Treasury of Scripture Knowledge itself, so they are stored as 'openbible'.
+-- SQLite can't alter a CHECK constraint, so the table is rebuilt.
+CREATE TABLE cross_references_new (
+    id INTEGER PRIMARY KEY,
+    from_verse_id INTEGER NOT NULL,
+    to_verse_id INTEGER NOT NULL,           -- First verse of the target
+    to_verse_end_id INTEGER,                -- Last verse when the target is a range
+    votes INTEGER NOT NULL DEFAULT 0,       -- Relevance weight, higher is stronger
+    source TEXT NOT NULL DEFAULT 'openbible' CHECK (source IN ('openbible', 'user')),
+    note TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY (from_verse_id) REFERENCES verses(id) ON DELETE CASCADE,
+    FOREIGN KEY (to_verse_id) REFERENCES verses(id) ON DELETE CASCADE,
+    FOREIGN KEY (to_verse_end_id) REFERENCES verses(id) ON DELETE CASCADE,
+    UNIQUE(from_verse_id, to_verse_id, source)
+);
+
+INSERT INTO cross_references_new
+    (id, from_verse_id, to_verse_id, to_verse_end_id, votes, source, note, created_at)
+SELECT id, from_verse_id, to_verse_id, to_verse_end_id, votes,
+       CASE source WHEN 'tsk' THEN 'openbible' ELSE source END, note, created_at
+FROM cross_references;
+
+DROP TABLE cross_references;
+ALTER TABLE cross_references_new RENAME TO cross_references;
+
+CREATE INDEX IF NOT EXISTS idx_cross_refs_from ON cross_references(from_verse_id, votes DESC);
+CREATE INDEX IF NOT EXISTS idx_cross_refs_to ON cross_references(to_verse_id);
+
+-- Record this migration
+INSERT OR IGNORE INTO schema_migrations (version, name) VALUES (12, '012_openbible_cross_references');
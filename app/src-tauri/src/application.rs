@@ -0,0 +1,271 @@
+// Action steps and reflection questions beyond the curated ones: the JSON
+// we ask the LLM for, its validation into the models the curated ones use,
+// and the checks on steps and questions before they are saved
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use crate::llm::JsonOutput;
+use crate::models::{ActionStep, ReflectionQuestion};
+
+/// The values the CHECK constraints on action_steps.difficulty and
+/// reflection_questions.category allow
+pub const DIFFICULTIES: [&str; 3] = ["easy", "medium", "challenging"];
+pub const CATEGORIES: [&str; 4] = ["personal", "relational", "spiritual", "practical"];
+
+/// Sources of the steps and questions that can be saved; curated ones only
+/// come with the app
+pub const SAVED_SOURCES: [&str; 2] = ["ai", "user"];
+
+#[derive(Debug, Deserialize)]
+struct GeneratedSteps {
+    steps: Vec<GeneratedStep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeneratedStep {
+    content: String,
+    difficulty: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeneratedQuestions {
+    questions: Vec<GeneratedQuestion>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GeneratedQuestion {
+    question: String,
+    category: String,
+}
+
+pub fn action_steps_output() -> JsonOutput {
+    JsonOutput {
+        name: "action_steps",
+        description: "Record the action steps for applying the verse",
+        schema: list_schema("steps", "content", "difficulty", &DIFFICULTIES),
+    }
+}
+
+pub fn reflection_questions_output() -> JsonOutput {
+    JsonOutput {
+        name: "reflection_questions",
+        description: "Record the reflection questions for the verse",
+        schema: list_schema("questions", "question", "category", &CATEGORIES),
+    }
+}
+
+/// `{ "<list>": [{ "<text>": "...", "<label>": one of `labels` }] }`, in the
+/// subset of JSON Schema that strict structured output accepts
+fn list_schema(list: &str, text: &str, label: &str, labels: &[&str]) -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            list: {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        text: { "type": "string" },
+                        label: { "type": "string", "enum": labels },
+                    },
+                    "required": [text, label],
+                    "additionalProperties": false,
+                },
+            },
+        },
+        "required": [list],
+        "additionalProperties": false,
+    })
+}
+
+/// Validate a reply to the action steps prompt. Generated steps aren't
+/// saved, so they get negative ids that can't clash with saved ones; the
+/// caller fills in the model.
+pub fn parse_action_steps(reply: &str, verse_id: i64) -> Result<Vec<ActionStep>, String> {
+    let generated: GeneratedSteps = parse_reply(reply)?;
+    if generated.steps.is_empty() {
+        return Err("\"steps\" is empty".to_string());
+    }
+
+    generated.steps.into_iter().zip(1..).map(|(step, number)| {
+        Ok(ActionStep {
+            id: -number,
+            verse_id,
+            step_number: number,
+            content: non_empty(&step.content, "content", number)?,
+            difficulty: one_of(&step.difficulty, &DIFFICULTIES, "difficulty")?,
+            source: "ai".to_string(),
+            model: None,
+        })
+    }).collect()
+}
+
+/// Validate a reply to the reflection questions prompt, numbering the
+/// questions like `parse_action_steps`
+pub fn parse_reflection_questions(reply: &str, verse_id: i64) -> Result<Vec<ReflectionQuestion>, String> {
+    let generated: GeneratedQuestions = parse_reply(reply)?;
+    if generated.questions.is_empty() {
+        return Err("\"questions\" is empty".to_string());
+    }
+
+    generated.questions.into_iter().zip(1..).map(|(question, number)| {
+        Ok(ReflectionQuestion {
+            id: -number,
+            verse_id,
+            question: non_empty(&question.question, "question", number)?,
+            category: one_of(&question.category, &CATEGORIES, "category")?,
+            source: "ai".to_string(),
+            model: None,
+        })
+    }).collect()
+}
+
+/// Check steps before they are saved, normalizing their labels the way
+/// generated ones are
+pub fn validate_action_steps(steps: Vec<ActionStep>) -> Result<Vec<ActionStep>, String> {
+    steps.into_iter().zip(1..).map(|(step, number)| {
+        Ok(ActionStep {
+            content: non_empty(&step.content, "content", number)?,
+            difficulty: one_of(&step.difficulty, &DIFFICULTIES, "difficulty")?,
+            source: one_of(&step.source, &SAVED_SOURCES, "source")?,
+            ..step
+        })
+    }).collect()
+}
+
+pub fn validate_reflection_questions(questions: Vec<ReflectionQuestion>) -> Result<Vec<ReflectionQuestion>, String> {
+    questions.into_iter().zip(1..).map(|(question, number)| {
+        Ok(ReflectionQuestion {
+            question: non_empty(&question.question, "question", number)?,
+            category: one_of(&question.category, &CATEGORIES, "category")?,
+            source: one_of(&question.source, &SAVED_SOURCES, "source")?,
+            ..question
+        })
+    }).collect()
+}
+
+/// The JSON object in a reply. Providers without a JSON mode may wrap it in
+/// a code fence or a sentence.
+fn parse_reply<T: DeserializeOwned>(reply: &str) -> Result<T, String> {
+    let json = match (reply.find('{'), reply.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Err("the reply contains no JSON object".to_string()),
+    };
+
+    serde_json::from_str(json).map_err(|e| format!("the JSON does not match the schema: {}", e))
+}
+
+fn non_empty(text: &str, field: &str, number: i64) -> Result<String, String> {
+    match text.trim() {
+        "" => Err(format!("item {} has an empty \"{}\"", number, field)),
+        text => Ok(text.to_string()),
+    }
+}
+
+fn one_of(value: &str, allowed: &[&str], field: &str) -> Result<String, String> {
+    let value = value.trim().to_lowercase();
+    if allowed.contains(&value.as_str()) {
+        Ok(value)
+    } else {
+        Err(format!("\"{}\" must be one of {}, not \"{}\"", field, allowed.join(", "), value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEPS: &str = r#"{"steps": [
+        {"content": " Write the verse on a card. ", "difficulty": "Easy"},
+        {"content": "Forgive someone you have held a grudge against.", "difficulty": " CHALLENGING "}
+    ]}"#;
+
+    #[test]
+    fn parses_action_steps() {
+        let steps = parse_action_steps(STEPS, 42).unwrap();
+        let summary: Vec<(i64, i64, &str, &str)> = steps.iter()
+            .map(|s| (s.id, s.step_number, s.content.as_str(), s.difficulty.as_str()))
+            .collect();
+        assert_eq!(summary, vec![
+            (-1, 1, "Write the verse on a card.", "easy"),
+            (-2, 2, "Forgive someone you have held a grudge against.", "challenging"),
+        ]);
+        assert!(steps.iter().all(|s| s.verse_id == 42 && s.source == "ai" && s.model.is_none()));
+    }
+
+    #[test]
+    fn finds_json_wrapped_in_a_reply() {
+        let fenced = format!("```json\n{}\n```", STEPS);
+        assert_eq!(parse_action_steps(&fenced, 1).unwrap().len(), 2);
+
+        let sentence = format!("Here are your steps: {} Let me know if you need more.", STEPS);
+        assert_eq!(parse_action_steps(&sentence, 1).unwrap().len(), 2);
+
+        assert_eq!(parse_action_steps("I can't help with that.", 1).unwrap_err(), "the reply contains no JSON object");
+        assert_eq!(parse_action_steps("} {", 1).unwrap_err(), "the reply contains no JSON object");
+        assert!(parse_action_steps(r#"{"items": []}"#, 1).unwrap_err().starts_with("the JSON does not match the schema"));
+    }
+
+    #[test]
+    fn rejects_empty_lists_and_blank_text() {
+        assert_eq!(parse_action_steps(r#"{"steps": []}"#, 1).unwrap_err(), "\"steps\" is empty");
+        assert_eq!(parse_reflection_questions(r#"{"questions": []}"#, 1).unwrap_err(), "\"questions\" is empty");
+
+        let blank = r#"{"steps": [{"content": "Pray", "difficulty": "easy"}, {"content": "  ", "difficulty": "easy"}]}"#;
+        assert_eq!(parse_action_steps(blank, 1).unwrap_err(), "item 2 has an empty \"content\"");
+
+        let blank = r#"{"questions": [{"question": "", "category": "personal"}]}"#;
+        assert_eq!(parse_reflection_questions(blank, 1).unwrap_err(), "item 1 has an empty \"question\"");
+    }
+
+    #[test]
+    fn checks_labels_against_the_allowed_values() {
+        let hard = r#"{"steps": [{"content": "Fast for a day", "difficulty": "hard"}]}"#;
+        assert_eq!(
+            parse_action_steps(hard, 1).unwrap_err(),
+            "\"difficulty\" must be one of easy, medium, challenging, not \"hard\"",
+        );
+
+        let questions = r#"{"questions": [
+            {"question": "Where do you need peace?", "category": "Personal"},
+            {"question": "Who needs your help?", "category": "communal"}
+        ]}"#;
+        assert_eq!(
+            parse_reflection_questions(questions, 1).unwrap_err(),
+            "\"category\" must be one of personal, relational, spiritual, practical, not \"communal\"",
+        );
+
+        let questions = r#"{"questions": [{"question": "Where do you need peace?", "category": " SPIRITUAL"}]}"#;
+        let parsed = parse_reflection_questions(questions, 7).unwrap();
+        assert_eq!((parsed[0].id, parsed[0].verse_id, parsed[0].category.as_str()), (-1, 7, "spiritual"));
+    }
+
+    #[test]
+    fn validates_steps_and_questions_before_saving() {
+        let step = ActionStep {
+            id: 0,
+            verse_id: 1,
+            step_number: 1,
+            content: " Call a friend ".to_string(),
+            difficulty: "Medium".to_string(),
+            source: "User".to_string(),
+            model: None,
+        };
+        let saved = validate_action_steps(vec![step.clone()]).unwrap();
+        assert_eq!((saved[0].content.as_str(), saved[0].difficulty.as_str(), saved[0].source.as_str()), ("Call a friend", "medium", "user"));
+
+        // Curated steps only come with the app
+        let curated = ActionStep { source: "curated".to_string(), ..step };
+        assert!(validate_action_steps(vec![curated]).unwrap_err().contains("\"source\" must be one of ai, user"));
+
+        let question = ReflectionQuestion {
+            id: 0,
+            verse_id: 1,
+            question: "What will you change?".to_string(),
+            category: "other".to_string(),
+            source: "ai".to_string(),
+            model: Some("mistral".to_string()),
+        };
+        assert!(validate_reflection_questions(vec![question]).unwrap_err().contains("\"category\""));
+    }
+}
@@ -1,115 +1,353 @@
-use tauri::State;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+use tauri::{AppHandle, Emitter, State};
 use serde::{Deserialize, Serialize};
 use crate::AppState;
+use crate::application;
+use crate::db::Database;
+use crate::error::AppError;
+use crate::import::{self, BibleFormat};
+use crate::parallel;
 use crate::models::*;
-use crate::llm::{LlmClient, LlmConfig, prompts};
+use crate::reference;
+use crate::search::MatchQuery;
+use crate::semantic;
+use crate::templates;
+use crate::usage::{self, UsageGrouping};
+use crate::versification::{self, Scheme};
+use crate::llm::{self, BackendStatus, Completion, LlmClient, LlmConfig, LlmError, LlmModel, LlmResponse, OllamaProvider, PullProgress, RetryNotice, prompts};
+
+/// Report a query that found no row as NotFound with `message`
+fn not_found(message: String) -> impl FnOnce(rusqlite::Error) -> AppError {
+    move |e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(message),
+        e => e.into(),
+    }
+}
+
+/// Resolve an optional translation code from the frontend, defaulting to KJV
+fn resolve_translation(db: &Database, translation: Option<&str>) -> Result<i64, AppError> {
+    db.get_translation_id(translation)
+        .map_err(not_found(format!("Unknown translation: {}", translation.unwrap_or_default())))
+}
+
+/// Resolve an optional versification scheme name, defaulting to KJV numbering
+fn resolve_versification(versification: Option<&str>) -> Result<Scheme, AppError> {
+    let scheme = versification.map(Scheme::from_name).transpose().map_err(AppError::InvalidInput)?;
+    Ok(scheme.unwrap_or(Scheme::Kjv))
+}
 
 // Bible Commands
 #[tauri::command]
-pub fn get_books(state: State<'_, AppState>) -> Result<Vec<Book>, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_books().map_err(|e| e.to_string())
+pub fn get_books(state: State<'_, AppState>) -> Result<Vec<Book>, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_books()?)
+}
+
+#[tauri::command]
+pub fn get_translations(state: State<'_, AppState>) -> Result<Vec<Translation>, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_translations()?)
+}
+
+#[tauri::command]
+pub fn get_verses(state: State<'_, AppState>, book_id: i64, chapter: i64, translation: Option<String>) -> Result<Vec<Verse>, AppError> {
+    let db = state.db.lock()?;
+    let translation_id = resolve_translation(&db, translation.as_deref())?;
+    Ok(db.get_verses(book_id, chapter, translation_id)?)
+}
+
+#[tauri::command]
+pub fn get_verse(state: State<'_, AppState>, verse_id: i64, translation: Option<String>) -> Result<VerseWithBook, AppError> {
+    let db = state.db.lock()?;
+    let translation_id = resolve_translation(&db, translation.as_deref())?;
+    db.get_verse(verse_id, translation_id).map_err(not_found(format!("Verse not found: {}", verse_id)))
+}
+
+#[tauri::command]
+pub fn get_verse_by_reference(
+    state: State<'_, AppState>,
+    reference: String,
+    translation: Option<String>,
+    versification: Option<String>,
+) -> Result<VerseWithBook, AppError> {
+    let scheme = resolve_versification(versification.as_deref())?;
+    let db = state.db.lock()?;
+    let translation_id = resolve_translation(&db, translation.as_deref())?;
+    reference::lookup_verse(&db, &reference, translation_id, scheme)
+}
+
+#[tauri::command]
+pub fn get_passages(
+    state: State<'_, AppState>,
+    reference: String,
+    translation: Option<String>,
+    versification: Option<String>,
+) -> Result<Vec<Passage>, AppError> {
+    let scheme = resolve_versification(versification.as_deref())?;
+    let db = state.db.lock()?;
+    let translation_id = resolve_translation(&db, translation.as_deref())?;
+    reference::lookup_passages(&db, &reference, translation_id, scheme)
+}
+
+#[tauri::command]
+pub fn get_random_verse(state: State<'_, AppState>, topic_id: Option<i64>, translation: Option<String>) -> Result<VerseWithBook, AppError> {
+    let db = state.db.lock()?;
+    let translation_id = resolve_translation(&db, translation.as_deref())?;
+    Ok(db.get_random_verse(topic_id, translation_id)?)
+}
+
+#[tauri::command]
+pub fn compare_passages(
+    state: State<'_, AppState>,
+    reference: String,
+    translations: Vec<String>,
+) -> Result<Vec<ParallelPassage>, AppError> {
+    if translations.is_empty() {
+        return Err(AppError::InvalidInput("Choose at least one translation to compare".to_string()));
+    }
+
+    let db = state.db.lock()?;
+    let mut resolved = Vec::with_capacity(translations.len());
+    for code in &translations {
+        let translation_id = resolve_translation(&db, Some(code))?;
+        resolved.push((translation_id, code.trim().to_uppercase()));
+    }
+
+    parallel::compare_passages(&db, &reference, &resolved)
+}
+
+#[tauri::command]
+pub fn convert_versification(
+    book_id: i64,
+    chapter: i64,
+    verse: i64,
+    from: String,
+    to: String,
+) -> Result<VerseLocation, AppError> {
+    let from = Scheme::from_name(&from).map_err(AppError::InvalidInput)?;
+    let to = Scheme::from_name(&to).map_err(AppError::InvalidInput)?;
+    let (chapter, verse) = versification::convert(book_id, chapter, verse, from, to);
+    Ok(VerseLocation { book_id, chapter, verse })
 }
 
+// Import Commands
 #[tauri::command]
-pub fn get_verses(state: State<'_, AppState>, book_id: i64, chapter: i64) -> Result<Vec<Verse>, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_verses(book_id, chapter).map_err(|e| e.to_string())
+pub fn import_bible(
+    state: State<'_, AppState>,
+    path: String,
+    code: String,
+    name: Option<String>,
+    format: Option<String>,
+    versification: Option<String>,
+) -> Result<ImportReport, AppError> {
+    let code = code.trim().to_string();
+    if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(AppError::InvalidInput(format!("Invalid translation code '{}': use letters and digits only", code)));
+    }
+
+    let format = format.as_deref().map(BibleFormat::from_name).transpose().map_err(AppError::InvalidInput)?;
+    let scheme = resolve_versification(versification.as_deref())?;
+    let bible = import::read_bible(Path::new(&path), format).map_err(AppError::InvalidInput)?;
+
+    let db = state.db.lock()?;
+    let books = db.get_books()?;
+    let (verses, unmapped) = import::map_verses(bible.verses, &books, scheme);
+    if verses.is_empty() {
+        return Err(AppError::InvalidInput(format!("No verses could be imported from {}", path)));
+    }
+
+    let name = name.filter(|n| !n.trim().is_empty())
+        .or(bible.title)
+        .unwrap_or_else(|| code.clone());
+    let language = bible.language.unwrap_or_else(|| "en".to_string());
+
+    let translation_id = db.import_translation(&code, &name, &language, &verses)
+        ?;
+
+    Ok(ImportReport {
+        translation_id,
+        code,
+        name,
+        format: bible.format.name().to_string(),
+        imported: verses.len() as i64,
+        unmapped,
+    })
 }
 
+// Cross Reference Commands
 #[tauri::command]
-pub fn get_verse(state: State<'_, AppState>, verse_id: i64) -> Result<VerseWithBook, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_verse(verse_id).map_err(|e| e.to_string())
+pub fn get_cross_references(state: State<'_, AppState>, verse_id: i64, limit: Option<i64>) -> Result<Vec<CrossReference>, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_cross_references(verse_id, limit)?)
 }
 
 #[tauri::command]
-pub fn get_verse_by_reference(_state: State<'_, AppState>, reference: String) -> Result<VerseWithBook, String> {
-    // TODO: Parse reference and look up verse
-    // For now, return a placeholder error
-    Err(format!("Reference parsing not yet implemented: {}", reference))
+pub fn get_cross_references_to(state: State<'_, AppState>, verse_id: i64, limit: Option<i64>) -> Result<Vec<CrossReference>, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_cross_references_to(verse_id, limit)?)
 }
 
 #[tauri::command]
-pub fn get_random_verse(state: State<'_, AppState>, topic_id: Option<i64>) -> Result<VerseWithBook, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_random_verse(topic_id).map_err(|e| e.to_string())
+pub fn add_cross_reference(
+    state: State<'_, AppState>,
+    verse_id: i64,
+    reference: String,
+    note: Option<String>,
+) -> Result<CrossReference, AppError> {
+    let db = state.db.lock()?;
+    let passage = reference::lookup_single_passage(&db, &reference)?;
+
+    let first = passage.verses.first()
+        .ok_or_else(|| AppError::NotFound(format!("No verses found for '{}'", reference)))?;
+    let last = passage.verses.last().filter(|v| v.id != first.id);
+
+    Ok(db.add_cross_reference(verse_id, first.id, last.map(|v| v.id), note.as_deref())?)
+}
+
+/// Import OpenBible.info's cross_references.txt, replacing earlier imported references
+#[tauri::command]
+pub fn import_cross_references(state: State<'_, AppState>, path: String) -> Result<CrossReferenceImportReport, AppError> {
+    let references = import::read_cross_references(Path::new(&path)).map_err(AppError::InvalidInput)?;
+
+    let db = state.db.lock()?;
+    let (imported, skipped) = db.import_cross_references(&references)?;
+    Ok(CrossReferenceImportReport { imported, skipped })
+}
+
+#[tauri::command]
+pub fn delete_cross_reference(state: State<'_, AppState>, id: i64) -> Result<bool, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.delete_cross_reference(id)?)
 }
 
 // Topic Commands
 #[tauri::command]
-pub fn get_topics(state: State<'_, AppState>) -> Result<Vec<Topic>, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_topics().map_err(|e| e.to_string())
+pub fn get_topics(state: State<'_, AppState>) -> Result<Vec<Topic>, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_topics()?)
 }
 
 #[tauri::command]
-pub fn get_verses_by_topic(state: State<'_, AppState>, topic_id: i64, limit: Option<i64>) -> Result<Vec<VerseWithTopic>, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_verses_by_topic(topic_id, limit).map_err(|e| e.to_string())
+pub fn get_verses_by_topic(
+    state: State<'_, AppState>,
+    topic_id: i64,
+    limit: Option<i64>,
+    translation: Option<String>,
+) -> Result<Vec<VerseWithTopic>, AppError> {
+    let db = state.db.lock()?;
+    let translation_id = resolve_translation(&db, translation.as_deref())?;
+    Ok(db.get_verses_by_topic(topic_id, limit, translation_id)?)
 }
 
 #[tauri::command]
-pub fn get_topic_by_slug(state: State<'_, AppState>, slug: String) -> Result<Topic, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_topic_by_slug(&slug).map_err(|e| e.to_string())
+pub fn get_topic_by_slug(state: State<'_, AppState>, slug: String) -> Result<Topic, AppError> {
+    let db = state.db.lock()?;
+    db.get_topic_by_slug(&slug).map_err(not_found(format!("Topic not found: {}", slug)))
 }
 
 // Application Commands
 #[tauri::command]
-pub fn get_verse_application(state: State<'_, AppState>, verse_id: i64) -> Result<VerseApplication, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_verse_application(verse_id).map_err(|e| e.to_string())
+pub fn get_verse_application(state: State<'_, AppState>, verse_id: i64) -> Result<VerseApplication, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_verse_application(verse_id)?)
+}
+
+/// Save generated ("ai") or user-written ("user") steps for a verse after
+/// its existing ones. Ids and step numbers are assigned; steps the verse
+/// already has are skipped. Returns all of the verse's steps.
+#[tauri::command]
+pub fn save_action_steps(state: State<'_, AppState>, verse_id: i64, steps: Vec<ActionStep>) -> Result<Vec<ActionStep>, AppError> {
+    let steps = application::validate_action_steps(steps).map_err(AppError::InvalidInput)?;
+    let db = state.db.lock()?;
+    Ok(db.save_action_steps(verse_id, &steps)?)
+}
+
+/// Save questions for a verse like `save_action_steps`
+#[tauri::command]
+pub fn save_reflection_questions(
+    state: State<'_, AppState>,
+    verse_id: i64,
+    questions: Vec<ReflectionQuestion>,
+) -> Result<Vec<ReflectionQuestion>, AppError> {
+    let questions = application::validate_reflection_questions(questions).map_err(AppError::InvalidInput)?;
+    let db = state.db.lock()?;
+    Ok(db.save_reflection_questions(verse_id, &questions)?)
+}
+
+#[tauri::command]
+pub fn delete_action_step(state: State<'_, AppState>, id: i64) -> Result<bool, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.delete_action_step(id)?)
+}
+
+#[tauri::command]
+pub fn delete_reflection_question(state: State<'_, AppState>, id: i64) -> Result<bool, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.delete_reflection_question(id)?)
 }
 
 // Search Commands
 #[tauri::command]
-pub fn search_verses(state: State<'_, AppState>, query: String, limit: Option<i64>) -> Result<Vec<VerseWithBook>, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.search_verses(&query, limit).map_err(|e| e.to_string())
+pub fn search_verses(
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<i64>,
+    exact: Option<bool>,
+    translation: Option<String>,
+) -> Result<Vec<SearchResult>, AppError> {
+    let Some(query) = MatchQuery::parse(&query, exact.unwrap_or(false)).map_err(AppError::InvalidInput)? else {
+        return Ok(Vec::new());
+    };
+
+    let db = state.db.lock()?;
+    let translation_id = resolve_translation(&db, translation.as_deref())?;
+    Ok(db.search_verses(&query, limit, translation_id)?)
 }
 
 // User Data Commands
 #[tauri::command]
-pub fn create_note(state: State<'_, AppState>, note: NewNote) -> Result<UserNote, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.create_note(&note).map_err(|e| e.to_string())
+pub fn create_note(state: State<'_, AppState>, note: NewNote) -> Result<UserNote, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.create_note(&note)?)
 }
 
 #[tauri::command]
-pub fn get_notes(state: State<'_, AppState>, verse_id: Option<i64>) -> Result<Vec<UserNote>, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_notes(verse_id).map_err(|e| e.to_string())
+pub fn get_notes(state: State<'_, AppState>, verse_id: Option<i64>) -> Result<Vec<UserNote>, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_notes(verse_id)?)
 }
 
 #[tauri::command]
-pub fn add_highlight(state: State<'_, AppState>, verse_id: i64, color: String) -> Result<UserHighlight, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.add_highlight(verse_id, &color).map_err(|e| e.to_string())
+pub fn add_highlight(state: State<'_, AppState>, verse_id: i64, color: String) -> Result<UserHighlight, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.add_highlight(verse_id, &color)?)
 }
 
 #[tauri::command]
-pub fn get_highlights(state: State<'_, AppState>) -> Result<Vec<UserHighlight>, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_highlights().map_err(|e| e.to_string())
+pub fn get_highlights(state: State<'_, AppState>) -> Result<Vec<UserHighlight>, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_highlights()?)
 }
 
 // Settings Commands
 #[tauri::command]
-pub fn get_settings(state: State<'_, AppState>) -> Result<Settings, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.get_settings().map_err(|e| e.to_string())
+pub fn get_settings(state: State<'_, AppState>) -> Result<Settings, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_settings()?)
 }
 
 #[tauri::command]
-pub fn update_settings(state: State<'_, AppState>, settings: Settings) -> Result<(), String> {
+pub fn update_settings(state: State<'_, AppState>, settings: Settings) -> Result<(), AppError> {
     println!("[Settings Update] Saving settings:");
     println!("  provider: {}", settings.llm_provider);
     println!("  base_url: {:?}", settings.llm_base_url);
     println!("  model: {:?}", settings.llm_model);
 
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let result = db.update_settings(&settings).map_err(|e| e.to_string());
+    let db = state.db.lock()?;
+    let result = db.update_settings(&settings).map_err(AppError::from);
 
     if result.is_ok() {
         println!("[Settings Update] Settings saved successfully");
@@ -126,6 +364,73 @@ pub fn update_settings(state: State<'_, AppState>, settings: Settings) -> Result
 pub struct AiInsight {
     pub content: String,
     pub tokens_used: u32,
+    pub cached: bool,  // Served from ai_cache; no tokens were spent
+    pub provider: String,  // The provider that answered, which may be a fallback
+    pub model: String,
+    pub stop_reason: Option<String>,  // As the provider reports it; None when cached
+}
+
+impl AiInsight {
+    fn new(completion: Completion, cached: bool) -> Self {
+        let response = completion.response;
+        Self {
+            content: response.text,
+            tokens_used: response.input_tokens + response.output_tokens,
+            cached,
+            provider: completion.provider,
+            model: completion.model,
+            stop_reason: response.stop_reason,
+        }
+    }
+}
+
+// Action steps or reflection questions validated from a JSON reply, which
+// is the insight's content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiItems<T> {
+    pub items: Vec<T>,
+    #[serde(flatten)]
+    pub insight: AiInsight,
+}
+
+// Verses found by AI search for the themes the query was expanded into,
+// which are the insight's content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticSearch {
+    pub query: String,
+    pub themes: Vec<String>,
+    pub results: Vec<SemanticMatch>,
+    #[serde(flatten)]
+    pub insight: AiInsight,
+}
+
+// Streaming events, sent when a generation is started with a request id
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiToken {
+    pub request_id: String,
+    pub delta: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiDone {
+    pub request_id: String,
+    pub content: String,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub stop_reason: Option<String>,
+}
+
+// Sent when a streamed generation is about to be retried, e.g. after a rate limit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AiRetry {
+    pub request_id: String,
+    #[serde(flatten)]
+    pub notice: RetryNotice,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -134,15 +439,274 @@ pub struct LlmStatus {
     pub connected: bool,
     pub provider: String,
     pub model: Option<String>,
+    pub models: Vec<LlmModel>,  // What the provider offers; empty when not connected
+    pub fallbacks: Vec<BackendStatus>,  // In the order they are tried
+}
+
+// Progress events of `pull_ollama_model`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OllamaPull {
+    pub model: String,
+    #[serde(flatten)]
+    pub progress: PullProgress,
+}
+
+/// Removes a generation from `AppState::generations` when it ends, however it ends
+struct Registration<'a> {
+    generations: &'a Mutex<HashMap<String, oneshot::Sender<()>>>,
+    id: String,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut generations) = self.generations.lock() {
+            generations.remove(&self.id);
+        }
+    }
+}
+
+/// Run a generation, registered under its request id (or a generated one)
+/// so `cancel_generation` can stop it. With a request id the completion is
+/// streamed to the webview as "ai-token" events, followed by an "ai-done"
+/// event with usage; an "ai-retry" event comes before each retry of a
+/// rate-limited or failing request.
+///
+/// Responses are reused from ai_cache for `Settings::ai_cache_hours` unless
+/// `regenerate` is set; a cached response comes back flagged as such, and
+/// is sent to the webview as a single "ai-token" event.
+///
+/// Every call, cached or not, is logged in token_usage under `request_type`
+/// (see usage.rs for the types).
+/// Calls that would go over a usage quota are refused before they are sent.
+///
+/// When the provider is unavailable the fallbacks in the settings are tried
+/// in order; the completion names whichever one answered.
+async fn run_generation(
+    app: &AppHandle,
+    state: &AppState,
+    client: &LlmClient,
+    prompt: &str,
+    request_type: &str,
+    request_id: Option<String>,
+    regenerate: bool,
+) -> Result<(Completion, bool), AppError> {
+    let (settings, system, cache_key, cached) = {
+        let db = state.db.lock()?;
+        let settings = db.get_settings()?;
+        let system = templates::render_template(&db, "system", &[])?;
+        let cache_key = response_cache_key(client, client.provider(), &system, prompt);
+        let cached = if settings.ai_cache_hours > 0 && !regenerate {
+            db.get_cached_response(&cache_key)?
+        } else {
+            None
+        };
+        if let Some((_, model)) = &cached {
+            if let Err(e) = db.log_token_usage(client.provider(), model, 0, 0, request_type, true) {
+                println!("[AI] Failed to log token usage: {}", e);
+            }
+        }
+        (settings, system, cache_key, cached)
+    };
+
+    if let Some((text, model)) = cached {
+        println!("[AI] Cache hit for {}", cache_key);
+        if let Some(request_id) = request_id {
+            let token = AiToken { request_id: request_id.clone(), delta: text.clone() };
+            let done = AiDone { request_id, content: text.clone(), input_tokens: 0, output_tokens: 0, stop_reason: None };
+            if let Err(e) = app.emit("ai-token", token).and_then(|_| app.emit("ai-done", done)) {
+                println!("[AI Stream] Failed to emit cached response: {}", e);
+            }
+        }
+        let response = LlmResponse { text, input_tokens: 0, output_tokens: 0, stop_reason: None };
+        return Ok((Completion { response, provider: client.provider().to_string(), model }, true));
+    }
+
+    // Each provider in the fallback chain is checked against the quotas
+    // before it is used
+    let prompt_tokens = usage::estimate_tokens(&system) + usage::estimate_tokens(prompt);
+    let check_quota = |provider: &str, model: &str| {
+        let db = state.db.lock().map_err(|e| e.to_string())?;
+        match usage::check_quota(&db, &settings, provider, model, prompt_tokens).map_err(|e| e.to_string())? {
+            Some(exceeded) => {
+                println!("[AI] {}", exceeded);
+                Err(LlmError::QuotaExceeded(exceeded))
+            }
+            None => Ok(()),
+        }
+    };
+
+    let id = request_id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    {
+        let mut generations = state.generations.lock()?;
+        if generations.contains_key(&id) {
+            return Err(AppError::InvalidInput(format!("A generation with request id '{}' is already running", id)));
+        }
+        generations.insert(id.clone(), cancel_tx);
+    }
+    let _registration = Registration { generations: &state.generations, id };
+
+    // Cancelling drops the request future, which closes the connection so a
+    // local server stops generating
+    let generation = async {
+        let Some(request_id) = request_id else {
+            return client.generate(prompt, Some(&system), check_quota, |_| {}).await;
+        };
+
+        let on_retry = |notice: &RetryNotice| {
+            let retry = AiRetry { request_id: request_id.clone(), notice: notice.clone() };
+            if let Err(e) = app.emit("ai-retry", retry) {
+                println!("[AI Stream] Failed to emit retry: {}", e);
+            }
+        };
+
+        let completion = client.generate_stream(prompt, Some(&system), check_quota, on_retry, |delta| {
+            let token = AiToken { request_id: request_id.clone(), delta: delta.to_string() };
+            if let Err(e) = app.emit("ai-token", token) {
+                println!("[AI Stream] Failed to emit token: {}", e);
+            }
+        }).await?;
+
+        let done = AiDone {
+            request_id,
+            content: completion.response.text.clone(),
+            input_tokens: completion.response.input_tokens,
+            output_tokens: completion.response.output_tokens,
+            stop_reason: completion.response.stop_reason.clone(),
+        };
+        if let Err(e) = app.emit("ai-done", done) {
+            println!("[AI Stream] Failed to emit completion: {}", e);
+        }
+
+        Ok(completion)
+    };
+
+    let completion = tokio::select! {
+        result = generation => result.map_err(AppError::from),
+        _ = cancel_rx => Err(AppError::Cancelled),
+    }?;
+
+    // Log and cache under the provider that actually answered, so a
+    // fallback's response is not served later in place of the primary's
+    let Completion { response, provider, model } = &completion;
+    let db = state.db.lock()?;
+    if let Err(e) = db.log_token_usage(provider, model, response.input_tokens, response.output_tokens, request_type, false) {
+        println!("[AI] Failed to log token usage: {}", e);
+    }
+    if settings.ai_cache_hours > 0 && !response.text.trim().is_empty() {
+        let cache_key = response_cache_key(client, provider, &system, prompt);
+        let tokens_used = (response.input_tokens + response.output_tokens) as i64;
+        if let Err(e) = db.cache_response(&cache_key, prompt, &response.text, model, tokens_used, settings.ai_cache_hours) {
+            println!("[AI] Failed to cache response: {}", e);
+        }
+    }
+
+    Ok((completion, false))
+}
+
+/// Key of the cached response to `prompt` from `provider`. The model is the
+/// one in the settings, empty when the server picks it, so a lookup never
+/// has to ask a local server which model it has loaded.
+fn response_cache_key(client: &LlmClient, provider: &str, system: &str, prompt: &str) -> String {
+    llm::cache_key(provider, client.configured_model(provider).unwrap_or_default(), Some(system), prompt)
+}
+
+/// The request id a correction streams under, so that listeners of the
+/// rejected reply don't see a second stream under the same id
+fn repair_request_id(request_id: &str) -> String {
+    format!("{}:repair", request_id)
+}
+
+/// Run a generation whose reply `parse` must accept. A reply it rejects is
+/// dropped from the cache and the model is asked once to correct it, under
+/// `repair_request_id`; the correction is cached in its place. Tokens of
+/// both calls are counted.
+#[allow(clippy::too_many_arguments)]
+async fn run_validated_generation<T>(
+    app: &AppHandle,
+    state: &AppState,
+    client: &LlmClient,
+    prompt: &str,
+    request_type: &str,
+    request_id: Option<String>,
+    regenerate: bool,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<(T, AiInsight), AppError> {
+    let (completion, cached) = run_generation(app, state, client, prompt, request_type, request_id.clone(), regenerate).await?;
+    let problem = match parse(&completion.response.text) {
+        Ok(parsed) => return Ok((parsed, AiInsight::new(completion, cached))),
+        Err(problem) => problem,
+    };
+
+    println!("[AI] Invalid {} reply from {}, asking for a correction: {}", request_type, completion.provider, problem);
+    let system = {
+        let db = state.db.lock()?;
+        let system = templates::render_template(&db, "system", &[])?;
+        db.delete_cached_response(&response_cache_key(client, &completion.provider, &system, prompt))?;
+        system
+    };
+
+    let repair_prompt = prompts::json_repair_prompt(prompt, &completion.response.text, &problem);
+    let repair_id = request_id.as_deref().map(repair_request_id);
+    let (repaired, repair_cached) = run_generation(app, state, client, &repair_prompt, request_type, repair_id, false).await?;
+    let parsed = parse(&repaired.response.text)
+        .map_err(|problem| AppError::LlmFailed(format!("The model's reply could not be used: {}", problem)))?;
+
+    {
+        let db = state.db.lock()?;
+        let ai_cache_hours = db.get_settings()?.ai_cache_hours;
+        if ai_cache_hours > 0 {
+            let cache_key = response_cache_key(client, &repaired.provider, &system, prompt);
+            let tokens_used = (repaired.response.input_tokens + repaired.response.output_tokens) as i64;
+            if let Err(e) = db.cache_response(&cache_key, prompt, &repaired.response.text, &repaired.model, tokens_used, ai_cache_hours) {
+                println!("[AI] Failed to cache response: {}", e);
+            }
+        }
+    }
+
+    let mut insight = AiInsight::new(repaired, repair_cached);
+    insight.tokens_used += completion.response.input_tokens + completion.response.output_tokens;
+    Ok((parsed, insight))
+}
+
+/// The LLM client for the provider in the settings, falling back to
+/// `Settings::llm_fallbacks` in order
+fn llm_client(settings: &Settings) -> Result<LlmClient, AppError> {
+    let mut client = LlmClient::new(LlmConfig {
+        provider: settings.llm_provider.clone(),
+        base_url: settings.llm_base_url.clone(),
+        model: settings.llm_model.clone(),
+        api_key: settings.llm_api_key.clone(),
+        keep_alive: settings.ollama_keep_alive.clone(),
+        num_ctx: settings.ollama_num_ctx,
+        temperature: settings.llm_temperature,
+        stop_sequences: settings.llm_stop_sequences.clone(),
+    }).map_err(AppError::InvalidInput)?;
+
+    for fallback in &settings.llm_fallbacks {
+        client = client.with_fallback(LlmConfig {
+            provider: fallback.provider.clone(),
+            base_url: fallback.base_url.clone(),
+            model: fallback.model.clone(),
+            api_key: fallback.api_key.clone(),
+            keep_alive: settings.ollama_keep_alive.clone(),
+            num_ctx: settings.ollama_num_ctx,
+            temperature: settings.llm_temperature,
+            stop_sequences: settings.llm_stop_sequences.clone(),
+        }).map_err(AppError::InvalidInput)?;
+    }
+
+    Ok(client)
 }
 
 // AI/LLM Commands
 #[tauri::command]
-pub async fn check_llm_connection(state: State<'_, AppState>) -> Result<LlmStatus, String> {
+pub async fn check_llm_connection(state: State<'_, AppState>) -> Result<LlmStatus, AppError> {
     println!("[Check Connection] Reading settings from database...");
     let settings = {
-        let db = state.db.lock().map_err(|e| e.to_string())?;
-        db.get_settings().map_err(|e| e.to_string())?
+        let db = state.db.lock()?;
+        db.get_settings()?
     };
 
     println!("[Check Connection] Settings loaded:");
@@ -150,61 +714,88 @@ pub async fn check_llm_connection(state: State<'_, AppState>) -> Result<LlmStatu
     println!("  base_url: {:?}", settings.llm_base_url);
     println!("  model: {:?}", settings.llm_model);
 
-    let config = LlmConfig {
-        provider: settings.llm_provider.clone(),
-        base_url: settings.llm_base_url.clone(),
-        model: settings.llm_model.clone(),
-        api_key: settings.llm_api_key.clone(),
-    };
-
-    let client = LlmClient::new(config);
     println!("[Check Connection] Testing connection...");
-    let connected = client.check_connection().await.unwrap_or(false);
-    println!("[Check Connection] Result: connected={}", connected);
+    let (connected, models, fallbacks) = match llm_client(&settings) {
+        Ok(client) => {
+            let mut statuses = client.check_connections().await;
+            let connected = statuses.remove(0).connected;
+            let models = if connected { client.list_models().await.unwrap_or_default() } else { Vec::new() };
+            (connected, models, statuses)
+        }
+        Err(e) => {
+            println!("[Check Connection] {}", e);
+            (false, Vec::new(), Vec::new())
+        }
+    };
+    println!("[Check Connection] Result: connected={}, {} models, {} of {} fallbacks connected",
+        connected, models.len(), fallbacks.iter().filter(|f| f.connected).count(), fallbacks.len());
 
     Ok(LlmStatus {
         connected,
         provider: settings.llm_provider,
         model: settings.llm_model,
+        models,
+        fallbacks,
     })
 }
 
+/// Download a model into Ollama, sending "ollama-pull" progress events.
+/// Uses the configured server when the provider is Ollama, else the default.
+#[tauri::command]
+pub async fn pull_ollama_model(app: AppHandle, state: State<'_, AppState>, model: String) -> Result<(), AppError> {
+    let settings = {
+        let db = state.db.lock()?;
+        db.get_settings()?
+    };
+
+    let config = LlmConfig {
+        provider: "ollama".to_string(),
+        base_url: settings.llm_base_url.filter(|_| settings.llm_provider == "ollama"),
+        ..LlmConfig::default()
+    };
+    let ollama = OllamaProvider::new(reqwest::Client::new(), &config);
+
+    println!("[Ollama] Pulling {}", model);
+    ollama.pull_model(&model, |progress| {
+        let event = OllamaPull { model: model.clone(), progress: progress.clone() };
+        if let Err(e) = app.emit("ollama-pull", event) {
+            println!("[Ollama] Failed to emit pull progress: {}", e);
+        }
+    }).await?;
+
+    Ok(())
+}
+
 #[tauri::command]
 pub async fn get_ai_insight(
+    app: AppHandle,
     state: State<'_, AppState>,
     verse_text: String,
     reference: String,
-) -> Result<AiInsight, String> {
+    request_id: Option<String>,
+    regenerate: Option<bool>,
+) -> Result<AiInsight, AppError> {
     println!("[AI Insight] Called with reference: {}", reference);
 
-    let settings = {
-        let db = state.db.lock().map_err(|e| e.to_string())?;
-        db.get_settings().map_err(|e| e.to_string())?
+    let (settings, prompt) = {
+        let db = state.db.lock()?;
+        let prompt = templates::render_template(&db, "verse_insight", &[("reference", &reference), ("verse_text", &verse_text)])?;
+        (db.get_settings()?, prompt)
     };
 
     println!("[AI Insight] Provider: {}, Base URL: {:?}, Model: {:?}",
              settings.llm_provider, settings.llm_base_url, settings.llm_model);
 
-    let config = LlmConfig {
-        provider: settings.llm_provider,
-        base_url: settings.llm_base_url,
-        model: settings.llm_model,
-        api_key: settings.llm_api_key,
-    };
-
-    let client = LlmClient::new(config);
-    let prompt = prompts::verse_insight_prompt(&verse_text, &reference);
+    let client = llm_client(&settings)?;
 
     println!("[AI Insight] Calling LLM...");
-    let response = client.generate(&prompt, Some(prompts::SYSTEM_PROMPT)).await;
+    let response = run_generation(&app, &state, &client, &prompt, "insight", request_id, regenerate.unwrap_or(false)).await;
 
     match response {
-        Ok(resp) => {
-            println!("[AI Insight] Success! Tokens: {} in, {} out", resp.input_tokens, resp.output_tokens);
-            Ok(AiInsight {
-                content: resp.text,
-                tokens_used: resp.input_tokens + resp.output_tokens,
-            })
+        Ok((completion, cached)) => {
+            println!("[AI Insight] Success from {}! Tokens: {} in, {} out",
+                     completion.provider, completion.response.input_tokens, completion.response.output_tokens);
+            Ok(AiInsight::new(completion, cached))
         }
         Err(e) => {
             println!("[AI Insight] Error: {}", e);
@@ -213,61 +804,223 @@ pub async fn get_ai_insight(
     }
 }
 
+/// Action steps for applying a verse, as JSON the model is asked for and
+/// validated into `ActionStep`s
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn generate_action_steps(
+    app: AppHandle,
     state: State<'_, AppState>,
+    verse_id: i64,
     verse_text: String,
     reference: String,
     topic: String,
-) -> Result<AiInsight, String> {
-    let settings = {
-        let db = state.db.lock().map_err(|e| e.to_string())?;
-        db.get_settings().map_err(|e| e.to_string())?
-    };
-
-    let config = LlmConfig {
-        provider: settings.llm_provider,
-        base_url: settings.llm_base_url,
-        model: settings.llm_model,
-        api_key: settings.llm_api_key,
+    request_id: Option<String>,
+    regenerate: Option<bool>,
+) -> Result<AiItems<ActionStep>, AppError> {
+    let (settings, prompt) = {
+        let db = state.db.lock()?;
+        let prompt = templates::render_template(&db, "action_steps", &[("reference", &reference), ("verse_text", &verse_text), ("topic", &topic)])?;
+        (db.get_settings()?, prompt)
     };
 
-    let client = LlmClient::new(config);
-    let prompt = prompts::action_steps_prompt(&verse_text, &reference, &topic);
+    let client = llm_client(&settings)?.with_json_output(application::action_steps_output());
 
-    let response = client.generate(&prompt, Some(prompts::SYSTEM_PROMPT)).await?;
+    let (mut items, insight) = run_validated_generation(
+        &app, &state, &client, &prompt, "action_steps", request_id, regenerate.unwrap_or(false),
+        |reply| application::parse_action_steps(reply, verse_id),
+    ).await?;
+    for step in &mut items {
+        step.model = Some(insight.model.clone());
+    }
 
-    Ok(AiInsight {
-        content: response.text,
-        tokens_used: response.input_tokens + response.output_tokens,
-    })
+    Ok(AiItems { items, insight })
 }
 
+/// Reflection questions for a verse, validated like `generate_action_steps`
 #[tauri::command]
 pub async fn generate_reflection_questions(
+    app: AppHandle,
     state: State<'_, AppState>,
+    verse_id: i64,
     verse_text: String,
     reference: String,
-) -> Result<AiInsight, String> {
-    let settings = {
-        let db = state.db.lock().map_err(|e| e.to_string())?;
-        db.get_settings().map_err(|e| e.to_string())?
+    request_id: Option<String>,
+    regenerate: Option<bool>,
+) -> Result<AiItems<ReflectionQuestion>, AppError> {
+    let (settings, prompt) = {
+        let db = state.db.lock()?;
+        let prompt = templates::render_template(&db, "reflection_questions", &[("reference", &reference), ("verse_text", &verse_text)])?;
+        (db.get_settings()?, prompt)
     };
 
-    let config = LlmConfig {
-        provider: settings.llm_provider,
-        base_url: settings.llm_base_url,
-        model: settings.llm_model,
-        api_key: settings.llm_api_key,
+    let client = llm_client(&settings)?.with_json_output(application::reflection_questions_output());
+
+    let (mut items, insight) = run_validated_generation(
+        &app, &state, &client, &prompt, "reflection", request_id, regenerate.unwrap_or(false),
+        |reply| application::parse_reflection_questions(reply, verse_id),
+    ).await?;
+    for question in &mut items {
+        question.model = Some(insight.model.clone());
+    }
+
+    Ok(AiItems { items, insight })
+}
+
+/// Search by need rather than by words: the LLM expands the query into
+/// themes, which are matched against topics and the keyword index. The
+/// query is recorded in search_history.
+#[tauri::command]
+pub async fn ai_search(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<i64>,
+    translation: Option<String>,
+    request_id: Option<String>,
+    regenerate: Option<bool>,
+) -> Result<SemanticSearch, AppError> {
+    let query = query.trim().to_string();
+    if query.is_empty() {
+        return Err(AppError::InvalidInput("Enter something to search for".to_string()));
+    }
+
+    let (settings, prompt, translation_id) = {
+        let db = state.db.lock()?;
+        let prompt = templates::render_template(&db, "semantic_search", &[("query", &query)])?;
+        (db.get_settings()?, prompt, resolve_translation(&db, translation.as_deref())?)
     };
 
-    let client = LlmClient::new(config);
-    let prompt = prompts::reflection_questions_prompt(&verse_text, &reference);
+    let client = llm_client(&settings)?;
+    let (completion, cached) = run_generation(&app, &state, &client, &prompt, "search", request_id, regenerate.unwrap_or(false)).await?;
+
+    // A reply without themes still searches for the query itself
+    let mut themes = semantic::parse_themes(&completion.response.text);
+    if themes.is_empty() {
+        themes.push(query.to_lowercase());
+    }
+    println!("[AI Search] \"{}\" -> {}", query, themes.join(", "));
 
-    let response = client.generate(&prompt, Some(prompts::SYSTEM_PROMPT)).await?;
+    let db = state.db.lock()?;
+    let results = semantic::search(&db, &themes, limit.unwrap_or(25).max(0) as usize, translation_id)?;
+    if let Err(e) = db.record_search(&query, "ai", results.len()) {
+        println!("[AI Search] Failed to record search: {}", e);
+    }
 
-    Ok(AiInsight {
-        content: response.text,
-        tokens_used: response.input_tokens + response.output_tokens,
-    })
+    Ok(SemanticSearch { query, themes, results, insight: AiInsight::new(completion, cached) })
+}
+
+/// Stop an in-flight generation. Returns false when no generation with that
+/// request id is running (it may already have finished).
+#[tauri::command]
+pub fn cancel_generation(state: State<'_, AppState>, request_id: String) -> Result<bool, AppError> {
+    // A generation being corrected runs under its repair id
+    let cancel = {
+        let mut generations = state.generations.lock()?;
+        generations.remove(&request_id).or_else(|| generations.remove(&repair_request_id(&request_id)))
+    };
+    let Some(cancel) = cancel else {
+        return Ok(false);
+    };
+
+    println!("[AI] Cancelling generation {}", request_id);
+    Ok(cancel.send(()).is_ok())
+}
+
+/// Drop every cached AI response. Returns how many were removed.
+#[tauri::command]
+pub fn clear_ai_cache(state: State<'_, AppState>) -> Result<usize, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.clear_ai_cache()?)
+}
+
+// Token Usage Commands
+/// Token usage and estimated cost grouped by "day", "month", "provider" or
+/// "model", optionally only from `since` (YYYY-MM-DD) on
+#[tauri::command]
+pub fn get_token_usage(
+    state: State<'_, AppState>,
+    group_by: String,
+    since: Option<String>,
+) -> Result<Vec<TokenUsageTotal>, AppError> {
+    let grouping = UsageGrouping::from_name(&group_by).map_err(AppError::InvalidInput)?;
+    let db = state.db.lock()?;
+    Ok(db.get_token_usage(grouping, since.as_deref())?)
+}
+
+#[tauri::command]
+pub fn get_model_prices(state: State<'_, AppState>) -> Result<Vec<ModelPrice>, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.get_model_prices()?)
+}
+
+#[tauri::command]
+pub fn set_model_price(state: State<'_, AppState>, price: ModelPrice) -> Result<(), AppError> {
+    if price.input_per_million < 0.0 || price.output_per_million < 0.0 {
+        return Err(AppError::InvalidInput("Prices cannot be negative".to_string()));
+    }
+    let db = state.db.lock()?;
+    Ok(db.set_model_price(&price)?)
+}
+
+#[tauri::command]
+pub fn delete_model_price(state: State<'_, AppState>, provider: String, model: String) -> Result<bool, AppError> {
+    let db = state.db.lock()?;
+    Ok(db.delete_model_price(&provider, &model)?)
+}
+
+/// Every prompt template with the body in use
+#[tauri::command]
+pub fn get_prompt_templates(state: State<'_, AppState>) -> Result<Vec<PromptTemplate>, AppError> {
+    let db = state.db.lock()?;
+    Ok(templates::TEMPLATES.iter()
+        .map(|spec| templates::load(&db, spec))
+        .collect::<rusqlite::Result<_>>()?)
+}
+
+/// Save an edited template body. Placeholders must be variables the
+/// template takes, including every one it requires.
+#[tauri::command]
+pub fn update_prompt_template(state: State<'_, AppState>, name: String, body: String) -> Result<PromptTemplate, AppError> {
+    let spec = templates::spec(&name).map_err(AppError::NotFound)?;
+    templates::validate(spec, &body).map_err(AppError::InvalidInput)?;
+
+    let db = state.db.lock()?;
+    db.set_prompt_template(spec.name, &body)?;
+    Ok(templates::load(&db, spec)?)
+}
+
+/// A template rendered as it would be sent: `body` (or the one in use) with
+/// `values`, and sample values for any variable not given
+#[tauri::command]
+pub fn preview_prompt_template(
+    state: State<'_, AppState>,
+    name: String,
+    body: Option<String>,
+    values: Option<HashMap<String, String>>,
+) -> Result<String, AppError> {
+    let spec = templates::spec(&name).map_err(AppError::NotFound)?;
+    let body = match body {
+        Some(body) => body,
+        None => templates::load(&*state.db.lock()?, spec)?.body,
+    };
+    templates::validate(spec, &body).map_err(AppError::InvalidInput)?;
+
+    let values = values.unwrap_or_default();
+    let values = spec.variables.iter()
+        .map(|&variable| (variable, values.get(variable).map_or(templates::sample_value(variable), String::as_str)))
+        .collect();
+    Ok(templates::render(&body, &values))
+}
+
+/// Go back to the built-in body of one template, or of all of them when no
+/// name is given. Returns every template.
+#[tauri::command]
+pub fn reset_prompt_template(state: State<'_, AppState>, name: Option<String>) -> Result<Vec<PromptTemplate>, AppError> {
+    if let Some(name) = &name {
+        templates::spec(name).map_err(AppError::NotFound)?;
+    }
+
+    state.db.lock()?.delete_prompt_templates(name.as_deref())?;
+    get_prompt_templates(state)
 }
@@ -1,7 +1,15 @@
 use rusqlite::{Connection, Result, params};
+use rusqlite::functions::FunctionFlags;
+use std::collections::HashMap;
 use std::path::Path;
+use crate::import::{ImportedVerse, ParsedCrossReference};
 use crate::models::*;
+use crate::search::{self, MatchQuery};
 use crate::seed;
+use crate::usage::{self, QuotaPeriod, UsageGrouping};
+
+/// Translation used when a query doesn't name one
+pub const KJV_TRANSLATION_ID: i64 = 1;
 
 pub struct Database {
     conn: Connection,
@@ -10,6 +18,15 @@ pub struct Database {
 impl Database {
     pub fn new(path: &Path) -> Result<Self> {
         let conn = Connection::open(path)?;
+
+        // Used by the normalized search index triggers
+        conn.create_scalar_function(
+            "kjv_normalize",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| Ok(search::normalize_text(&ctx.get::<String>(0)?)),
+        )?;
+
         let db = Self { conn };
         db.initialize()?;
         Ok(db)
@@ -18,6 +35,19 @@ impl Database {
     fn initialize(&self) -> Result<()> {
         // Create tables
         self.conn.execute_batch(include_str!("../migrations/001_initial.sql"))?;
+        self.run_migration(2, include_str!("../migrations/002_verse_search.sql"))?;
+        self.run_migration(3, include_str!("../migrations/003_normalized_search.sql"))?;
+        self.run_migration(4, include_str!("../migrations/004_cross_references.sql"))?;
+        self.run_migration(5, include_str!("../migrations/005_translations.sql"))?;
+        self.run_migration(6, include_str!("../migrations/006_verse_bridges.sql"))?;
+        self.run_migration(7, include_str!("../migrations/007_verse_markup.sql"))?;
+        self.run_migration(8, include_str!("../migrations/008_model_prices.sql"))?;
+        self.run_migration(9, include_str!("../migrations/009_application_sources.sql"))?;
+        self.run_migration(10, include_str!("../migrations/010_prompt_templates.sql"))?;
+        // KJV verses seeded before markup existed are backfilled once, below
+        let backfill_markup = !self.migration_applied(11)?;
+        self.run_migration(11, include_str!("../migrations/011_kjv_markup.sql"))?;
+        self.run_migration(12, include_str!("../migrations/012_openbible_cross_references.sql"))?;
 
         // Seed topics if empty
         let topic_count: i64 = self.conn.query_row(
@@ -43,8 +73,8 @@ impl Database {
 
         // Seed all Bible verses if empty
         let verse_count: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM verses",
-            [],
+            "SELECT COUNT(*) FROM verses WHERE translation_id = ?1",
+            params![KJV_TRANSLATION_ID],
             |row| row.get(0)
         )?;
 
@@ -56,6 +86,30 @@ impl Database {
             // Seed action steps and reflection questions
             seed::seed_action_steps(&self.conn)?;
             seed::seed_reflection_questions(&self.conn)?;
+        } else if backfill_markup {
+            seed::seed_kjv_markup(&self.conn)?;
+        }
+
+        self.purge_expired_cache()?;
+
+        Ok(())
+    }
+
+    fn migration_applied(&self, version: i64) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM schema_migrations WHERE version = ?1)",
+            params![version],
+            |row| row.get(0)
+        )
+    }
+
+    /// Apply a versioned migration script unless schema_migrations already records it
+    fn run_migration(&self, version: i64, sql: &str) -> Result<()> {
+        // A migration that fails part way, as a table rebuild can, is rolled back whole
+        if !self.migration_applied(version)? {
+            let tx = self.conn.unchecked_transaction()?;
+            tx.execute_batch(sql)?;
+            tx.commit()?;
         }
 
         Ok(())
@@ -107,31 +161,125 @@ impl Database {
         Ok(books)
     }
 
-    pub fn get_verses(&self, book_id: i64, chapter: i64) -> Result<Vec<Verse>> {
+    pub fn get_translations(&self) -> Result<Vec<Translation>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.id, t.code, t.name, t.language, t.year,
+                    (SELECT COUNT(*) FROM verses WHERE translation_id = t.id) as verse_count
+             FROM translations t
+             ORDER BY t.sort_order"
+        )?;
+
+        let translations = stmt.query_map([], |row| {
+            Ok(Translation {
+                id: row.get(0)?,
+                code: row.get(1)?,
+                name: row.get(2)?,
+                language: row.get(3)?,
+                year: row.get(4)?,
+                verse_count: row.get(5)?,
+            })
+        })?.collect::<Result<Vec<_>>>()?;
+
+        Ok(translations)
+    }
+
+    /// Resolve a translation code ("KJV", "web") to its id; `None` means KJV
+    pub fn get_translation_id(&self, code: Option<&str>) -> Result<i64> {
+        match code {
+            Some(code) => self.conn.query_row(
+                "SELECT id FROM translations WHERE code = ?1",
+                params![code.trim()],
+                |row| row.get(0),
+            ),
+            None => Ok(KJV_TRANSLATION_ID),
+        }
+    }
+
+    /// Create or update a translation and write its verses in one transaction.
+    /// Re-importing a translation replaces the text of verses it already has.
+    pub fn import_translation(&self, code: &str, name: &str, language: &str, verses: &[ImportedVerse]) -> Result<i64> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        tx.execute(
+            "INSERT INTO translations (code, name, language, sort_order)
+             VALUES (?1, ?2, ?3, (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM translations))
+             ON CONFLICT (code) DO UPDATE SET name = excluded.name, language = excluded.language",
+            params![code, name, language],
+        )?;
+
+        let translation_id: i64 = tx.query_row(
+            "SELECT id FROM translations WHERE code = ?1",
+            params![code],
+            |row| row.get(0),
+        )?;
+
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO verses (translation_id, book_id, chapter, verse, end_verse, text, markup)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT (translation_id, book_id, chapter, verse)
+                 DO UPDATE SET end_verse = excluded.end_verse, text = excluded.text, markup = excluded.markup"
+            )?;
+            for v in verses {
+                stmt.execute(params![translation_id, v.book_id, v.chapter, v.verse, v.end_verse, v.text, v.markup])?;
+            }
+        }
+
+        tx.commit()?;
+        Ok(translation_id)
+    }
+
+    pub fn get_verses(&self, book_id: i64, chapter: i64, translation_id: i64) -> Result<Vec<Verse>> {
+        self.get_verse_range(book_id, chapter, 0, chapter, i64::MAX, translation_id)
+    }
+
+    /// Verses of one translation from start_chapter:start_verse through
+    /// end_chapter:end_verse. Verse 0 is a Psalm title where a translation has one.
+    pub fn get_verse_range(
+        &self,
+        book_id: i64,
+        start_chapter: i64,
+        start_verse: i64,
+        end_chapter: i64,
+        end_verse: i64,
+        translation_id: i64,
+    ) -> Result<Vec<Verse>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, book_id, chapter, verse, text FROM verses WHERE book_id = ?1 AND chapter = ?2 ORDER BY verse"
+            "SELECT id, book_id, chapter, verse, end_verse, text, markup FROM verses
+             WHERE book_id = ?1
+               AND chapter BETWEEN ?2 AND ?4
+               AND (chapter > ?2 OR verse >= ?3)
+               AND (chapter < ?4 OR verse <= ?5)
+               AND translation_id = ?6
+             ORDER BY chapter, verse"
         )?;
 
-        let verses = stmt.query_map(params![book_id, chapter], |row| {
+        let params = params![book_id, start_chapter, start_verse, end_chapter, end_verse, translation_id];
+        let verses = stmt.query_map(params, |row| {
             Ok(Verse {
                 id: row.get(0)?,
                 book_id: row.get(1)?,
                 chapter: row.get(2)?,
                 verse: row.get(3)?,
-                text: row.get(4)?,
+                end_verse: row.get(4)?,
+                text: row.get(5)?,
+                markup: row.get(6)?,
             })
         })?.collect::<Result<Vec<_>>>()?;
 
         Ok(verses)
     }
 
-    pub fn get_verse(&self, verse_id: i64) -> Result<VerseWithBook> {
+    /// Load a verse in the given translation. `verse_id` may belong to any
+    /// translation; the verse at the same reference is returned.
+    pub fn get_verse(&self, verse_id: i64, translation_id: i64) -> Result<VerseWithBook> {
         self.conn.query_row(
-            "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation
-             FROM verses v
+            "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation, v.markup
+             FROM verses r
+             JOIN verses v ON v.book_id = r.book_id AND v.chapter = r.chapter AND v.verse = r.verse
              JOIN books b ON v.book_id = b.id
-             WHERE v.id = ?1",
-            params![verse_id],
+             WHERE r.id = ?1 AND v.translation_id = ?2",
+            params![verse_id, translation_id],
             |row| {
                 Ok(VerseWithBook {
                     id: row.get(0)?,
@@ -141,21 +289,84 @@ impl Database {
                     text: row.get(4)?,
                     book_name: row.get(5)?,
                     book_abbreviation: row.get(6)?,
+                    markup: row.get(7)?,
                 })
             }
         )
     }
 
-    pub fn get_random_verse(&self, topic_id: Option<i64>) -> Result<VerseWithBook> {
+    pub fn find_verse_id(&self, book_id: i64, chapter: i64, verse: i64) -> Result<Option<i64>> {
+        let result = self.conn.query_row(
+            "SELECT id FROM verses WHERE book_id = ?1 AND chapter = ?2 AND verse = ?3 AND translation_id = ?4",
+            params![book_id, chapter, verse, KJV_TRANSLATION_ID],
+            |row| row.get(0),
+        );
+
+        match result {
+            Ok(id) => Ok(Some(id)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn get_verse_count(&self, book_id: i64, chapter: i64) -> Result<i64> {
+        self.conn.query_row(
+            "SELECT COALESCE(MAX(verse), 0) FROM verses WHERE book_id = ?1 AND chapter = ?2 AND translation_id = ?3",
+            params![book_id, chapter, KJV_TRANSLATION_ID],
+            |row| row.get(0),
+        )
+    }
+
+    pub fn get_passage_verses(
+        &self,
+        book_id: i64,
+        start_chapter: i64,
+        start_verse: i64,
+        end_chapter: i64,
+        end_verse: i64,
+        translation_id: i64,
+    ) -> Result<Vec<VerseWithBook>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation, v.markup
+             FROM verses v
+             JOIN books b ON v.book_id = b.id
+             WHERE v.book_id = ?1
+               AND v.chapter BETWEEN ?2 AND ?4
+               AND (v.chapter > ?2 OR v.verse >= ?3)
+               AND (v.chapter < ?4 OR v.verse <= ?5)
+               AND v.translation_id = ?6
+             ORDER BY v.chapter, v.verse"
+        )?;
+
+        let params = params![book_id, start_chapter, start_verse, end_chapter, end_verse, translation_id];
+        let verses = stmt.query_map(params, |row| {
+            Ok(VerseWithBook {
+                id: row.get(0)?,
+                book_id: row.get(1)?,
+                chapter: row.get(2)?,
+                verse: row.get(3)?,
+                text: row.get(4)?,
+                book_name: row.get(5)?,
+                book_abbreviation: row.get(6)?,
+                markup: row.get(7)?,
+            })
+        })?.collect::<Result<Vec<_>>>()?;
+
+        Ok(verses)
+    }
+
+    pub fn get_random_verse(&self, topic_id: Option<i64>, translation_id: i64) -> Result<VerseWithBook> {
         if let Some(tid) = topic_id {
+            // Topics are tagged on KJV verses; match other translations by reference
             self.conn.query_row(
-                "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation
-                 FROM verses v
+                "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation, v.markup
+                 FROM verse_topics vt
+                 JOIN verses k ON k.id = vt.verse_id
+                 JOIN verses v ON v.book_id = k.book_id AND v.chapter = k.chapter AND v.verse = k.verse
                  JOIN books b ON v.book_id = b.id
-                 JOIN verse_topics vt ON v.id = vt.verse_id
-                 WHERE vt.topic_id = ?1
+                 WHERE vt.topic_id = ?1 AND v.translation_id = ?2
                  ORDER BY RANDOM() LIMIT 1",
-                params![tid],
+                params![tid, translation_id],
                 |row| {
                     Ok(VerseWithBook {
                         id: row.get(0)?,
@@ -165,16 +376,18 @@ impl Database {
                         text: row.get(4)?,
                         book_name: row.get(5)?,
                         book_abbreviation: row.get(6)?,
+                        markup: row.get(7)?,
                     })
                 }
             )
         } else {
             self.conn.query_row(
-                "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation
+                "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation, v.markup
                  FROM verses v
                  JOIN books b ON v.book_id = b.id
+                 WHERE v.translation_id = ?1
                  ORDER BY RANDOM() LIMIT 1",
-                [],
+                params![translation_id],
                 |row| {
                     Ok(VerseWithBook {
                         id: row.get(0)?,
@@ -184,6 +397,7 @@ impl Database {
                         text: row.get(4)?,
                         book_name: row.get(5)?,
                         book_abbreviation: row.get(6)?,
+                        markup: row.get(7)?,
                     })
                 }
             )
@@ -236,21 +450,22 @@ impl Database {
         )
     }
 
-    pub fn get_verses_by_topic(&self, topic_id: i64, limit: Option<i64>) -> Result<Vec<VerseWithTopic>> {
+    pub fn get_verses_by_topic(&self, topic_id: i64, limit: Option<i64>, translation_id: i64) -> Result<Vec<VerseWithTopic>> {
         let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
 
         let mut stmt = self.conn.prepare(&format!(
             "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation,
                     vt.topic_id, vt.subtopic_id, vt.relevance_score
-             FROM verses v
+             FROM verse_topics vt
+             JOIN verses k ON k.id = vt.verse_id
+             JOIN verses v ON v.book_id = k.book_id AND v.chapter = k.chapter AND v.verse = k.verse
              JOIN books b ON v.book_id = b.id
-             JOIN verse_topics vt ON v.id = vt.verse_id
-             WHERE vt.topic_id = ?1
+             WHERE vt.topic_id = ?1 AND v.translation_id = ?2
              ORDER BY vt.relevance_score DESC, b.sort_order, v.chapter, v.verse{}",
             limit_clause
         ))?;
 
-        let verses = stmt.query_map(params![topic_id], |row| {
+        let verses = stmt.query_map(params![topic_id, translation_id], |row| {
             Ok(VerseWithTopic {
                 id: row.get(0)?,
                 book_id: row.get(1)?,
@@ -268,6 +483,156 @@ impl Database {
         Ok(verses)
     }
 
+    // Cross references
+    fn map_cross_reference(row: &rusqlite::Row) -> Result<CrossReference> {
+        let book: String = row.get(7)?;
+        let (chapter, verse): (i64, i64) = (row.get(8)?, row.get(9)?);
+        let end: (Option<i64>, Option<i64>) = (row.get(10)?, row.get(11)?);
+
+        let reference = match end {
+            (Some(c), Some(v)) if c == chapter => format!("{} {}:{}-{}", book, chapter, verse, v),
+            (Some(c), Some(v)) => format!("{} {}:{}-{}:{}", book, chapter, verse, c, v),
+            _ => format!("{} {}:{}", book, chapter, verse),
+        };
+
+        Ok(CrossReference {
+            id: row.get(0)?,
+            from_verse_id: row.get(1)?,
+            to_verse_id: row.get(2)?,
+            to_verse_end_id: row.get(3)?,
+            votes: row.get(4)?,
+            source: row.get(5)?,
+            note: row.get(6)?,
+            reference,
+            text: row.get(12)?,
+        })
+    }
+
+    /// References from a verse to related passages, user-added first, then by votes
+    pub fn get_cross_references(&self, verse_id: i64, limit: Option<i64>) -> Result<Vec<CrossReference>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT cr.id, cr.from_verse_id, cr.to_verse_id, cr.to_verse_end_id, cr.votes, cr.source, cr.note,
+                    b.name, t.chapter, t.verse, te.chapter, te.verse, t.text
+             FROM cross_references cr
+             JOIN verses t ON t.id = cr.to_verse_id
+             JOIN books b ON b.id = t.book_id
+             LEFT JOIN verses te ON te.id = cr.to_verse_end_id
+             WHERE cr.from_verse_id = ?1
+             ORDER BY cr.source = 'user' DESC, cr.votes DESC, cr.to_verse_id
+             LIMIT ?2"
+        )?;
+
+        let refs = stmt.query_map(params![verse_id, limit.unwrap_or(-1)], Self::map_cross_reference)?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(refs)
+    }
+
+    /// Reverse lookup: verses whose cross references point at this verse,
+    /// either directly or as part of a referenced range
+    pub fn get_cross_references_to(&self, verse_id: i64, limit: Option<i64>) -> Result<Vec<CrossReference>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT cr.id, cr.from_verse_id, cr.to_verse_id, cr.to_verse_end_id, cr.votes, cr.source, cr.note,
+                    b.name, f.chapter, f.verse, NULL, NULL, f.text
+             FROM cross_references cr
+             JOIN verses f ON f.id = cr.from_verse_id
+             JOIN books b ON b.id = f.book_id
+             WHERE cr.to_verse_id = ?1
+                OR (cr.to_verse_end_id IS NOT NULL AND cr.to_verse_id < ?1 AND cr.to_verse_end_id >= ?1)
+             ORDER BY cr.source = 'user' DESC, cr.votes DESC, cr.from_verse_id
+             LIMIT ?2"
+        )?;
+
+        let refs = stmt.query_map(params![verse_id, limit.unwrap_or(-1)], Self::map_cross_reference)?
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(refs)
+    }
+
+    pub fn get_cross_reference(&self, id: i64) -> Result<CrossReference> {
+        self.conn.query_row(
+            "SELECT cr.id, cr.from_verse_id, cr.to_verse_id, cr.to_verse_end_id, cr.votes, cr.source, cr.note,
+                    b.name, t.chapter, t.verse, te.chapter, te.verse, t.text
+             FROM cross_references cr
+             JOIN verses t ON t.id = cr.to_verse_id
+             JOIN books b ON b.id = t.book_id
+             LEFT JOIN verses te ON te.id = cr.to_verse_end_id
+             WHERE cr.id = ?1",
+            params![id],
+            Self::map_cross_reference,
+        )
+    }
+
+    pub fn add_cross_reference(
+        &self,
+        from_verse_id: i64,
+        to_verse_id: i64,
+        to_verse_end_id: Option<i64>,
+        note: Option<&str>,
+    ) -> Result<CrossReference> {
+        self.conn.execute(
+            "INSERT INTO cross_references (from_verse_id, to_verse_id, to_verse_end_id, note, source)
+             VALUES (?1, ?2, ?3, ?4, 'user')
+             ON CONFLICT (from_verse_id, to_verse_id, source)
+             DO UPDATE SET to_verse_end_id = excluded.to_verse_end_id, note = excluded.note",
+            params![from_verse_id, to_verse_id, to_verse_end_id, note],
+        )?;
+
+        let id: i64 = self.conn.query_row(
+            "SELECT id FROM cross_references WHERE from_verse_id = ?1 AND to_verse_id = ?2 AND source = 'user'",
+            params![from_verse_id, to_verse_id],
+            |row| row.get(0),
+        )?;
+        self.get_cross_reference(id)
+    }
+
+    /// Replace the OpenBible.info cross references with an imported set, keeping
+    /// user-added ones. Links to verses the KJV lacks are skipped and counted.
+    pub fn import_cross_references(&self, references: &[ParsedCrossReference]) -> Result<(i64, i64)> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let mut verse_ids = HashMap::new();
+        {
+            let mut stmt = tx.prepare("SELECT id, book_id, chapter, verse FROM verses WHERE translation_id = ?1")?;
+            let rows = stmt.query_map(params![KJV_TRANSLATION_ID], |row| {
+                Ok(([row.get::<_, i64>(1)?, row.get(2)?, row.get(3)?], row.get::<_, i64>(0)?))
+            })?;
+            for row in rows {
+                let (key, id) = row?;
+                verse_ids.insert(key, id);
+            }
+        }
+
+        tx.execute("DELETE FROM cross_references WHERE source = 'openbible'", [])?;
+
+        let (mut imported, mut skipped) = (0, 0);
+        {
+            let mut insert = tx.prepare(
+                "INSERT OR IGNORE INTO cross_references (from_verse_id, to_verse_id, to_verse_end_id, votes, source)
+                 VALUES (?1, ?2, ?3, ?4, 'openbible')"
+            )?;
+            for r in references {
+                let end = r.to_end.and_then(|e| verse_ids.get(&e));
+                match (verse_ids.get(&r.from), verse_ids.get(&r.to)) {
+                    (Some(from), Some(to)) => imported += insert.execute(params![from, to, end, r.votes])? as i64,
+                    _ => skipped += 1,
+                }
+            }
+        }
+
+        tx.commit()?;
+        Ok((imported, skipped))
+    }
+
+    /// Delete a user-added cross reference; imported OpenBible.info references are left untouched
+    pub fn delete_cross_reference(&self, id: i64) -> Result<bool> {
+        let deleted = self.conn.execute(
+            "DELETE FROM cross_references WHERE id = ?1 AND source = 'user'",
+            params![id],
+        )?;
+        Ok(deleted > 0)
+    }
+
     // User data queries
     pub fn create_note(&self, note: &NewNote) -> Result<UserNote> {
         self.conn.execute(
@@ -396,8 +761,21 @@ impl Database {
                 "llm_base_url" => settings.llm_base_url = Some(value),
                 "llm_model" => settings.llm_model = Some(value),
                 "llm_api_key" => settings.llm_api_key = Some(value),
+                "llm_fallbacks" => settings.llm_fallbacks = serde_json::from_str(&value).unwrap_or_default(),
+                "llm_temperature" => settings.llm_temperature = value.parse().ok(),
+                "llm_stop_sequences" => settings.llm_stop_sequences = serde_json::from_str(&value).unwrap_or_default(),
+                "ollama_keep_alive" => settings.ollama_keep_alive = Some(value).filter(|v| !v.is_empty()),
+                "ollama_num_ctx" => settings.ollama_num_ctx = value.parse().ok(),
                 "daily_verse_enabled" => settings.daily_verse_enabled = value == "true",
                 "daily_verse_time" => settings.daily_verse_time = value,
+                "ai_cache_hours" => {
+                    settings.ai_cache_hours = value.parse().unwrap_or(settings.ai_cache_hours)
+                }
+                "quota_daily_tokens" => settings.quota_daily_tokens = value.parse().ok(),
+                "quota_monthly_tokens" => settings.quota_monthly_tokens = value.parse().ok(),
+                "quota_daily_cost" => settings.quota_daily_cost = value.parse().ok(),
+                "quota_monthly_cost" => settings.quota_monthly_cost = value.parse().ok(),
+                "quota_local_providers" => settings.quota_local_providers = value == "true",
                 _ => {}
             }
         }
@@ -413,8 +791,19 @@ impl Database {
             ("llm_base_url", settings.llm_base_url.clone().unwrap_or_default()),
             ("llm_model", settings.llm_model.clone().unwrap_or_default()),
             ("llm_api_key", settings.llm_api_key.clone().unwrap_or_default()),
+            ("llm_fallbacks", serde_json::to_string(&settings.llm_fallbacks).unwrap_or_default()),
+            ("llm_temperature", settings.llm_temperature.map(|v| v.to_string()).unwrap_or_default()),
+            ("llm_stop_sequences", serde_json::to_string(&settings.llm_stop_sequences).unwrap_or_default()),
+            ("ollama_keep_alive", settings.ollama_keep_alive.clone().unwrap_or_default()),
+            ("ollama_num_ctx", settings.ollama_num_ctx.map(|v| v.to_string()).unwrap_or_default()),
             ("daily_verse_enabled", settings.daily_verse_enabled.to_string()),
             ("daily_verse_time", settings.daily_verse_time.clone()),
+            ("ai_cache_hours", settings.ai_cache_hours.to_string()),
+            ("quota_daily_tokens", settings.quota_daily_tokens.map(|v| v.to_string()).unwrap_or_default()),
+            ("quota_monthly_tokens", settings.quota_monthly_tokens.map(|v| v.to_string()).unwrap_or_default()),
+            ("quota_daily_cost", settings.quota_daily_cost.map(|v| v.to_string()).unwrap_or_default()),
+            ("quota_monthly_cost", settings.quota_monthly_cost.map(|v| v.to_string()).unwrap_or_default()),
+            ("quota_local_providers", settings.quota_local_providers.to_string()),
         ];
 
         for (key, value) in pairs {
@@ -427,10 +816,220 @@ impl Database {
         Ok(())
     }
 
+    // AI response cache
+    /// The cached response and the model that wrote it for a query hash,
+    /// unless it has expired. Counts the hit.
+    pub fn get_cached_response(&self, query_hash: &str) -> Result<Option<(String, String)>> {
+        let result = self.conn.query_row(
+            "UPDATE ai_cache SET hit_count = hit_count + 1
+             WHERE query_hash = ?1 AND expires_at > datetime('now')
+             RETURNING response, model",
+            params![query_hash],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        );
+
+        match result {
+            Ok(cached) => Ok(Some(cached)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Store a response for `ttl_hours`, replacing any earlier one for the
+    /// same query
+    pub fn cache_response(
+        &self,
+        query_hash: &str,
+        query_text: &str,
+        response: &str,
+        model: &str,
+        tokens_used: i64,
+        ttl_hours: i64,
+    ) -> Result<()> {
+        self.purge_expired_cache()?;
+        self.conn.execute(
+            "INSERT INTO ai_cache (query_hash, query_text, response, model, tokens_used, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, datetime('now', ?6))
+             ON CONFLICT(query_hash) DO UPDATE SET
+                query_text = excluded.query_text,
+                response = excluded.response,
+                model = excluded.model,
+                tokens_used = excluded.tokens_used,
+                created_at = datetime('now'),
+                expires_at = excluded.expires_at,
+                hit_count = 0",
+            params![query_hash, query_text, response, model, tokens_used, format!("+{} hours", ttl_hours)],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_cached_response(&self, query_hash: &str) -> Result<usize> {
+        self.conn.execute("DELETE FROM ai_cache WHERE query_hash = ?1", params![query_hash])
+    }
+
+    pub fn purge_expired_cache(&self) -> Result<usize> {
+        self.conn.execute("DELETE FROM ai_cache WHERE expires_at <= datetime('now')", [])
+    }
+
+    pub fn clear_ai_cache(&self) -> Result<usize> {
+        self.conn.execute("DELETE FROM ai_cache", [])
+    }
+
+    // Token usage
+    pub fn log_token_usage(
+        &self,
+        provider: &str,
+        model: &str,
+        input_tokens: u32,
+        output_tokens: u32,
+        request_type: &str,
+        cached: bool,
+    ) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO token_usage (provider, model, input_tokens, output_tokens, request_type, cached)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![provider, model, input_tokens, output_tokens, request_type, cached],
+        )?;
+        Ok(())
+    }
+
+    /// Usage totals per group, oldest/alphabetical first, optionally only
+    /// from `since` (a local date, YYYY-MM-DD) on
+    pub fn get_token_usage(&self, grouping: UsageGrouping, since: Option<&str>) -> Result<Vec<TokenUsageTotal>> {
+        let sql = format!(
+            "SELECT {key} AS grp, COUNT(*), SUM(u.cached), SUM(u.input_tokens), SUM(u.output_tokens),
+                    SUM({cost})
+             FROM token_usage u
+             WHERE ?1 IS NULL OR date(u.timestamp, 'localtime') >= ?1
+             GROUP BY grp
+             ORDER BY grp",
+            key = grouping.sql_key(),
+            cost = usage::COST_SQL,
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+
+        let totals = stmt.query_map(params![since], |row| {
+            Ok(TokenUsageTotal {
+                group: row.get(0)?,
+                requests: row.get(1)?,
+                cached_requests: row.get(2)?,
+                input_tokens: row.get(3)?,
+                output_tokens: row.get(4)?,
+                estimated_cost: row.get(5)?,
+            })
+        })?.collect::<Result<Vec<_>>>()?;
+
+        Ok(totals)
+    }
+
+    /// Tokens and estimated cost logged since the start of the current
+    /// quota period, leaving out local providers unless `include_local`
+    pub fn get_usage_since(&self, period: QuotaPeriod, include_local: bool) -> Result<(i64, f64)> {
+        let local = usage::LOCAL_PROVIDERS.iter()
+            .map(|p| format!("'{}'", p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!(
+            "SELECT COALESCE(SUM(u.input_tokens + u.output_tokens), 0), COALESCE(SUM({cost}), 0)
+             FROM token_usage u
+             WHERE date(u.timestamp, 'localtime') >= {start}
+               AND (?1 OR u.provider NOT IN ({local}))",
+            cost = usage::COST_SQL,
+            start = period.sql_start(),
+            local = local,
+        );
+
+        self.conn.query_row(&sql, params![include_local], |row| Ok((row.get(0)?, row.get(1)?)))
+    }
+
+    /// Estimated cost in USD of a call with the given token counts
+    pub fn estimate_cost(&self, provider: &str, model: &str, input_tokens: u32, output_tokens: u32) -> Result<f64> {
+        let sql = format!(
+            "SELECT {cost} FROM (SELECT ?1 AS provider, ?2 AS model, ?3 AS input_tokens, ?4 AS output_tokens) u",
+            cost = usage::COST_SQL,
+        );
+
+        self.conn.query_row(&sql, params![provider, model, input_tokens, output_tokens], |row| row.get(0))
+    }
+
+    pub fn get_model_prices(&self) -> Result<Vec<ModelPrice>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT provider, model, input_per_million, output_per_million
+             FROM model_prices
+             ORDER BY provider, model"
+        )?;
+
+        let prices = stmt.query_map([], |row| {
+            Ok(ModelPrice {
+                provider: row.get(0)?,
+                model: row.get(1)?,
+                input_per_million: row.get(2)?,
+                output_per_million: row.get(3)?,
+            })
+        })?.collect::<Result<Vec<_>>>()?;
+
+        Ok(prices)
+    }
+
+    pub fn set_model_price(&self, price: &ModelPrice) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO model_prices (provider, model, input_per_million, output_per_million)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![price.provider, price.model, price.input_per_million, price.output_per_million],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_model_price(&self, provider: &str, model: &str) -> Result<bool> {
+        let deleted = self.conn.execute(
+            "DELETE FROM model_prices WHERE provider = ?1 AND model = ?2",
+            params![provider, model],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    // Prompt templates: only edited ones are stored
+
+    /// The saved body of a template, if it has been edited
+    pub fn get_prompt_template(&self, name: &str) -> Result<Option<String>> {
+        let result = self.conn.query_row(
+            "SELECT body FROM prompt_templates WHERE name = ?1",
+            params![name],
+            |row| row.get(0),
+        );
+
+        match result {
+            Ok(body) => Ok(Some(body)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn set_prompt_template(&self, name: &str, body: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO prompt_templates (name, body) VALUES (?1, ?2)
+             ON CONFLICT(name) DO UPDATE SET body = excluded.body, updated_at = datetime('now')",
+            params![name, body],
+        )?;
+        Ok(())
+    }
+
+    /// Drop the saved body of one template, or of all of them, so the
+    /// built-in ones are used again. Returns how many were removed.
+    pub fn delete_prompt_templates(&self, name: Option<&str>) -> Result<usize> {
+        self.conn.execute(
+            "DELETE FROM prompt_templates WHERE ?1 IS NULL OR name = ?1",
+            params![name],
+        )
+    }
+
     // Application content queries
+    //
+    // Curated steps and questions come first, then saved AI and user ones in
+    // the order they were saved
     pub fn get_action_steps(&self, verse_id: i64) -> Result<Vec<ActionStep>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, verse_id, step_number, content, difficulty
+            "SELECT id, verse_id, step_number, content, difficulty, source, model
              FROM action_steps
              WHERE verse_id = ?1
              ORDER BY step_number"
@@ -443,6 +1042,8 @@ impl Database {
                 step_number: row.get(2)?,
                 content: row.get(3)?,
                 difficulty: row.get(4)?,
+                source: row.get(5)?,
+                model: row.get(6)?,
             })
         })?.collect::<Result<Vec<_>>>()?;
 
@@ -451,9 +1052,10 @@ impl Database {
 
     pub fn get_reflection_questions(&self, verse_id: i64) -> Result<Vec<ReflectionQuestion>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, verse_id, question, category
+            "SELECT id, verse_id, question, category, source, model
              FROM reflection_questions
-             WHERE verse_id = ?1"
+             WHERE verse_id = ?1
+             ORDER BY source != 'curated', id"
         )?;
 
         let questions = stmt.query_map(params![verse_id], |row| {
@@ -462,12 +1064,65 @@ impl Database {
                 verse_id: row.get(1)?,
                 question: row.get(2)?,
                 category: row.get(3)?,
+                source: row.get(4)?,
+                model: row.get(5)?,
             })
         })?.collect::<Result<Vec<_>>>()?;
 
         Ok(questions)
     }
 
+    /// Save steps for a verse, numbered after the ones it already has, in
+    /// one transaction. A step whose text the verse already has is skipped.
+    /// Returns the verse's steps.
+    pub fn save_action_steps(&self, verse_id: i64, steps: &[ActionStep]) -> Result<Vec<ActionStep>> {
+        let tx = self.conn.unchecked_transaction()?;
+        for step in steps {
+            tx.execute(
+                "INSERT INTO action_steps (verse_id, step_number, content, difficulty, source, model)
+                 SELECT ?1, (SELECT COALESCE(MAX(step_number), 0) + 1 FROM action_steps WHERE verse_id = ?1), ?2, ?3, ?4, ?5
+                 WHERE NOT EXISTS (SELECT 1 FROM action_steps WHERE verse_id = ?1 AND content = ?2)",
+                params![verse_id, step.content, step.difficulty, step.source, step.model],
+            )?;
+        }
+        tx.commit()?;
+
+        self.get_action_steps(verse_id)
+    }
+
+    /// Save questions for a verse like `save_action_steps`
+    pub fn save_reflection_questions(&self, verse_id: i64, questions: &[ReflectionQuestion]) -> Result<Vec<ReflectionQuestion>> {
+        let tx = self.conn.unchecked_transaction()?;
+        for question in questions {
+            tx.execute(
+                "INSERT INTO reflection_questions (verse_id, question, category, source, model)
+                 SELECT ?1, ?2, ?3, ?4, ?5
+                 WHERE NOT EXISTS (SELECT 1 FROM reflection_questions WHERE verse_id = ?1 AND question = ?2)",
+                params![verse_id, question.question, question.category, question.source, question.model],
+            )?;
+        }
+        tx.commit()?;
+
+        self.get_reflection_questions(verse_id)
+    }
+
+    /// Delete a saved step or question; curated ones are left untouched
+    pub fn delete_action_step(&self, id: i64) -> Result<bool> {
+        let deleted = self.conn.execute(
+            "DELETE FROM action_steps WHERE id = ?1 AND source != 'curated'",
+            params![id],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    pub fn delete_reflection_question(&self, id: i64) -> Result<bool> {
+        let deleted = self.conn.execute(
+            "DELETE FROM reflection_questions WHERE id = ?1 AND source != 'curated'",
+            params![id],
+        )?;
+        Ok(deleted > 0)
+    }
+
     pub fn get_verse_application(&self, verse_id: i64) -> Result<VerseApplication> {
         let action_steps = self.get_action_steps(verse_id)?;
         let reflection_questions = self.get_reflection_questions(verse_id)?;
@@ -479,21 +1134,33 @@ impl Database {
     }
 
     // Search functionality
-    pub fn search_verses(&self, query: &str, limit: Option<i64>) -> Result<Vec<VerseWithBook>> {
-        let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_else(|| " LIMIT 50".to_string());
-        let search_pattern = format!("%{}%", query);
+    //
+    // By default queries run against the normalized index, so "loves" finds
+    // "loveth" and "show" finds "shew"; an exact query matches the KJV words
+    // as written. Either way the snippet is the whole verse.
+    pub fn search_verses(&self, query: &MatchQuery, limit: Option<i64>, translation_id: i64) -> Result<Vec<SearchResult>> {
+        let exact = query.exact;
+        let (table, marked) = if exact {
+            ("verses_fts", "highlight(verses_fts, 0, '<mark>', '</mark>')")
+        } else {
+            // Matches are found in normalized text, then mapped back onto the KJV wording
+            ("verses_fts_normalized", "highlight(verses_fts_normalized, 0, char(1), char(2))")
+        };
 
         let mut stmt = self.conn.prepare(&format!(
-            "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation
-             FROM verses v
+            "SELECT v.id, v.book_id, v.chapter, v.verse, v.text, b.name, b.abbreviation,
+                    {marked},
+                    -bm25({table})
+             FROM {table}
+             JOIN verses v ON v.id = {table}.rowid
              JOIN books b ON v.book_id = b.id
-             WHERE v.text LIKE ?1
-             ORDER BY b.sort_order, v.chapter, v.verse{}",
-            limit_clause
+             WHERE {table} MATCH ?1 AND v.translation_id = ?3
+             ORDER BY bm25({table}), b.sort_order, v.chapter, v.verse
+             LIMIT ?2"
         ))?;
 
-        let verses = stmt.query_map(params![search_pattern], |row| {
-            Ok(VerseWithBook {
+        let mut results = stmt.query_map(params![query.expr, limit.unwrap_or(50), translation_id], |row| {
+            Ok(SearchResult {
                 id: row.get(0)?,
                 book_id: row.get(1)?,
                 chapter: row.get(2)?,
@@ -501,9 +1168,25 @@ impl Database {
                 text: row.get(4)?,
                 book_name: row.get(5)?,
                 book_abbreviation: row.get(6)?,
+                snippet: row.get(7)?,
+                score: row.get(8)?,
             })
         })?.collect::<Result<Vec<_>>>()?;
 
-        Ok(verses)
+        if !exact {
+            for result in &mut results {
+                result.snippet = search::transfer_highlights(&result.text, &result.snippet);
+            }
+        }
+
+        Ok(results)
+    }
+
+    pub fn record_search(&self, query: &str, search_type: &str, result_count: usize) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO search_history (query, search_type, result_count) VALUES (?1, ?2, ?3)",
+            params![query, search_type, result_count],
+        )?;
+        Ok(())
     }
 }
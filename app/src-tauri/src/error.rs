@@ -0,0 +1,183 @@
+// The error every command returns. It reaches the frontend as an object with
+// a stable `code`, a readable `message` and, for some codes, details:
+//
+//   { "code": "rateLimited", "message": "...", "retryAfterSecs": 8 }
+
+use serde::{Serialize, Serializer};
+use std::sync::PoisonError;
+use crate::llm::LlmError;
+use crate::usage::QuotaExceeded;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{0}")]
+    NotFound(String),
+    /// A Bible reference that can't be parsed or points outside the text
+    #[error("{0}")]
+    InvalidReference(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    /// The LLM server could not be reached or did not answer in time
+    #[error("{0}")]
+    LlmUnreachable(String),
+    /// The LLM API key is missing or was rejected
+    #[error("{0}")]
+    LlmAuth(String),
+    /// Still rate limited after retrying
+    #[error("{message}")]
+    RateLimited { message: String, retry_after_secs: Option<u64> },
+    #[error("{0}")]
+    QuotaExceeded(QuotaExceeded),
+    /// Any other LLM failure: an API error or a response we can't read
+    #[error("{0}")]
+    LlmFailed(String),
+    #[error("Generation cancelled")]
+    Cancelled,
+    #[error("Database error: {0}")]
+    Database(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "notFound",
+            Self::InvalidReference(_) => "invalidReference",
+            Self::InvalidInput(_) => "invalidInput",
+            Self::LlmUnreachable(_) => "llmUnreachable",
+            Self::LlmAuth(_) => "llmAuth",
+            Self::RateLimited { .. } => "rateLimited",
+            Self::QuotaExceeded(_) => "quotaExceeded",
+            Self::LlmFailed(_) => "llmFailed",
+            Self::Cancelled => "cancelled",
+            Self::Database(_) => "database",
+            Self::Internal(_) => "internal",
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = match self {
+            Self::QuotaExceeded(exceeded) => serde_json::to_value(exceeded).map_err(serde::ser::Error::custom)?,
+            Self::RateLimited { retry_after_secs, .. } => serde_json::json!({ "retryAfterSecs": retry_after_secs }),
+            _ => serde_json::json!({}),
+        };
+        error["code"] = self.code().into();
+        error["message"] = self.to_string().into();
+        error.serialize(serializer)
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(error: rusqlite::Error) -> Self {
+        match error {
+            rusqlite::Error::QueryReturnedNoRows => Self::NotFound("Not found".to_string()),
+            e => Self::Database(e.to_string()),
+        }
+    }
+}
+
+impl From<LlmError> for AppError {
+    fn from(error: LlmError) -> Self {
+        match &error {
+            LlmError::Unreachable(_) | LlmError::Timeout(_) => Self::LlmUnreachable(error.to_string()),
+            LlmError::Auth(_) => Self::LlmAuth(error.to_string()),
+            LlmError::RateLimited { retry_after, .. } => Self::RateLimited {
+                message: error.to_string(),
+                retry_after_secs: retry_after.map(|d| d.as_secs_f64().ceil() as u64),
+            },
+            LlmError::QuotaExceeded(exceeded) => Self::QuotaExceeded(exceeded.clone()),
+            LlmError::Api { .. } | LlmError::Other(_) => Self::LlmFailed(error.to_string()),
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for AppError {
+    fn from(error: PoisonError<T>) -> Self {
+        Self::Internal(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use serde_json::json;
+    use crate::usage::{QuotaPeriod, QuotaUnit};
+
+    fn serialized(error: AppError) -> serde_json::Value {
+        serde_json::to_value(error).unwrap()
+    }
+
+    fn quota() -> QuotaExceeded {
+        QuotaExceeded { period: QuotaPeriod::Daily, unit: QuotaUnit::Tokens, limit: 5000.0, used: 4500.0, requested: 1124.0 }
+    }
+
+    #[test]
+    fn serializes_a_code_and_message() {
+        let cases = [
+            (AppError::NotFound("Verse not found".into()), "notFound", "Verse not found"),
+            (AppError::InvalidReference("Unknown book: Hez".into()), "invalidReference", "Unknown book: Hez"),
+            (AppError::InvalidInput("Empty query".into()), "invalidInput", "Empty query"),
+            (AppError::LlmUnreachable("Request failed: refused".into()), "llmUnreachable", "Request failed: refused"),
+            (AppError::LlmAuth("Invalid API key".into()), "llmAuth", "Invalid API key"),
+            (AppError::LlmFailed("API error 400: bad".into()), "llmFailed", "API error 400: bad"),
+            (AppError::Cancelled, "cancelled", "Generation cancelled"),
+            (AppError::Database("disk I/O error".into()), "database", "Database error: disk I/O error"),
+            (AppError::Internal("poisoned".into()), "internal", "poisoned"),
+        ];
+
+        for (error, code, message) in cases {
+            assert_eq!(serialized(error), json!({ "code": code, "message": message }));
+        }
+    }
+
+    #[test]
+    fn serializes_rate_limit_and_quota_details() {
+        let error = AppError::RateLimited { message: "Rate limited (429): slow down".into(), retry_after_secs: Some(8) };
+        assert_eq!(serialized(error), json!({
+            "code": "rateLimited",
+            "message": "Rate limited (429): slow down",
+            "retryAfterSecs": 8,
+        }));
+
+        let error = AppError::RateLimited { message: "Rate limited (529): overloaded".into(), retry_after_secs: None };
+        assert_eq!(serialized(error)["retryAfterSecs"], serde_json::Value::Null);
+
+        let message = quota().to_string();
+        assert_eq!(serialized(AppError::QuotaExceeded(quota())), json!({
+            "code": "quotaExceeded",
+            "message": message,
+            "period": "daily",
+            "unit": "tokens",
+            "limit": 5000.0,
+            "used": 4500.0,
+            "requested": 1124.0,
+        }));
+    }
+
+    #[test]
+    fn maps_llm_errors() {
+        let code = |error: LlmError| AppError::from(error).code();
+
+        assert_eq!(code(LlmError::Unreachable("refused".into())), "llmUnreachable");
+        assert_eq!(code(LlmError::Timeout("after 120s".into())), "llmUnreachable");
+        assert_eq!(code(LlmError::Auth("Invalid API key".into())), "llmAuth");
+        assert_eq!(code(LlmError::QuotaExceeded(quota())), "quotaExceeded");
+        assert_eq!(code(LlmError::Api { status: 500, message: "boom".into(), retry_after: None }), "llmFailed");
+        assert_eq!(code(LlmError::Other("Failed to parse response".into())), "llmFailed");
+
+        // Waits are rounded up to whole seconds
+        let limited = LlmError::RateLimited { status: 429, message: "slow down".into(), retry_after: Some(Duration::from_millis(1500)) };
+        assert_eq!(serialized(limited.into()), json!({
+            "code": "rateLimited",
+            "message": "Rate limited (429): slow down",
+            "retryAfterSecs": 2,
+        }));
+
+        assert_eq!(AppError::from(rusqlite::Error::QueryReturnedNoRows).code(), "notFound");
+        assert_eq!(AppError::from(rusqlite::Error::InvalidQuery).code(), "database");
+    }
+}
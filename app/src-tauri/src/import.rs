@@ -0,0 +1,993 @@
+// Importing Bible texts from OSIS XML, USFM and Zefania XML
+
+use std::collections::HashMap;
+use std::path::Path;
+use roxmltree::{Document, Node, ParsingOptions};
+use crate::markup::{self, MarkupBuffer, Style};
+use crate::models::{Book, UnmappedVerse};
+use crate::versification::{self, Scheme};
+
+// Book ids with their OSIS and USFM identifiers
+const BOOK_CODES: &[(i64, &str, &str)] = &[
+    (1, "Gen", "GEN"), (2, "Exod", "EXO"), (3, "Lev", "LEV"), (4, "Num", "NUM"),
+    (5, "Deut", "DEU"), (6, "Josh", "JOS"), (7, "Judg", "JDG"), (8, "Ruth", "RUT"),
+    (9, "1Sam", "1SA"), (10, "2Sam", "2SA"), (11, "1Kgs", "1KI"), (12, "2Kgs", "2KI"),
+    (13, "1Chr", "1CH"), (14, "2Chr", "2CH"), (15, "Ezra", "EZR"), (16, "Neh", "NEH"),
+    (17, "Esth", "EST"), (18, "Job", "JOB"), (19, "Ps", "PSA"), (20, "Prov", "PRO"),
+    (21, "Eccl", "ECC"), (22, "Song", "SNG"), (23, "Isa", "ISA"), (24, "Jer", "JER"),
+    (25, "Lam", "LAM"), (26, "Ezek", "EZK"), (27, "Dan", "DAN"), (28, "Hos", "HOS"),
+    (29, "Joel", "JOL"), (30, "Amos", "AMO"), (31, "Obad", "OBA"), (32, "Jonah", "JON"),
+    (33, "Mic", "MIC"), (34, "Nah", "NAM"), (35, "Hab", "HAB"), (36, "Zeph", "ZEP"),
+    (37, "Hag", "HAG"), (38, "Zech", "ZEC"), (39, "Mal", "MAL"),
+    (40, "Matt", "MAT"), (41, "Mark", "MRK"), (42, "Luke", "LUK"), (43, "John", "JHN"),
+    (44, "Acts", "ACT"), (45, "Rom", "ROM"), (46, "1Cor", "1CO"), (47, "2Cor", "2CO"),
+    (48, "Gal", "GAL"), (49, "Eph", "EPH"), (50, "Phil", "PHP"), (51, "Col", "COL"),
+    (52, "1Thess", "1TH"), (53, "2Thess", "2TH"), (54, "1Tim", "1TI"), (55, "2Tim", "2TI"),
+    (56, "Titus", "TIT"), (57, "Phlm", "PHM"), (58, "Heb", "HEB"), (59, "Jas", "JAS"),
+    (60, "1Pet", "1PE"), (61, "2Pet", "2PE"), (62, "1John", "1JN"), (63, "2John", "2JN"),
+    (64, "3John", "3JN"), (65, "Jude", "JUD"), (66, "Rev", "REV"),
+];
+
+// USFM paragraph markers whose line is a heading or introduction, not verse text
+const USFM_SKIP_LINE: &[&str] = &[
+    "h", "toc", "toca", "mt", "mte", "ms", "mr", "s", "sr", "r", "sp", "sd", "rem", "ide",
+    "sts", "usfm", "cl", "cd", "imt", "imte", "is", "ip", "ipi", "im", "imi", "ipq", "imq", "ipr",
+    "iq", "ib", "ili", "iot", "io", "iex", "ie", "lit",
+];
+
+// USFM character markers whose content is skipped up to the matching end marker
+const USFM_SKIP_SPAN: &[&str] = &["f", "fe", "ef", "x", "ex", "fig", "ca", "va", "vp", "rq"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BibleFormat {
+    Osis,
+    Usfm,
+    Zefania,
+}
+
+impl BibleFormat {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "osis" => Ok(Self::Osis),
+            "usfm" | "sfm" => Ok(Self::Usfm),
+            "zefania" => Ok(Self::Zefania),
+            _ => Err(format!("Unknown Bible format '{}'; expected osis, usfm or zefania", name.trim())),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Osis => "osis",
+            Self::Usfm => "usfm",
+            Self::Zefania => "zefania",
+        }
+    }
+}
+
+/// A verse as read from the source, before it is matched against `books`
+#[derive(Debug, Clone)]
+pub struct ParsedVerse {
+    pub book_id: Option<i64>,
+    pub book: String,       // Book identifier as written in the source
+    pub chapter: i64,
+    pub verse: i64,         // 0 for a Psalm title
+    pub end_verse: Option<i64>,  // Last verse when several are merged into one
+    pub text: String,
+    pub markup: Option<String>,  // Text with italics and red letters, see markup.rs
+}
+
+#[derive(Debug, Clone)]
+pub struct ParsedBible {
+    pub format: BibleFormat,
+    pub title: Option<String>,
+    pub language: Option<String>,
+    pub verses: Vec<ParsedVerse>,
+}
+
+/// A verse ready to be written to the verses table
+#[derive(Debug, Clone)]
+pub struct ImportedVerse {
+    pub book_id: i64,
+    pub chapter: i64,
+    pub verse: i64,
+    pub end_verse: Option<i64>,
+    pub text: String,
+    pub markup: Option<String>,
+}
+
+fn osis_book_id(code: &str) -> Option<i64> {
+    BOOK_CODES.iter().find(|(_, osis, _)| osis.eq_ignore_ascii_case(code)).map(|(id, _, _)| *id)
+}
+
+fn usfm_book_id(code: &str) -> Option<i64> {
+    BOOK_CODES.iter().find(|(_, _, usfm)| usfm.eq_ignore_ascii_case(code)).map(|(id, _, _)| *id)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Read a Bible from a file, or from a directory of USFM files (one per book).
+/// The format is detected from the content when not given.
+pub fn read_bible(path: &Path, format: Option<BibleFormat>) -> Result<ParsedBible, String> {
+    if path.is_dir() {
+        let mut files: Vec<_> = std::fs::read_dir(path)
+            .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "usfm" | "sfm"))
+            })
+            .collect();
+        files.sort();
+
+        if files.is_empty() {
+            return Err(format!("No .usfm or .sfm files found in {}", path.display()));
+        }
+
+        let mut bible = ParsedBible { format: BibleFormat::Usfm, title: None, language: None, verses: Vec::new() };
+        for file in files {
+            let input = std::fs::read_to_string(&file)
+                .map_err(|e| format!("Cannot read {}: {}", file.display(), e))?;
+            let book = parse_usfm(&input)
+                .map_err(|e| format!("{}: {}", file.display(), e))?;
+            bible.verses.extend(book.verses);
+        }
+        return Ok(bible);
+    }
+
+    let input = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    parse_bible(&input, format)
+}
+
+/// Parse a Bible text in the given format, or detect it from the content
+pub fn parse_bible(input: &str, format: Option<BibleFormat>) -> Result<ParsedBible, String> {
+    let input = input.trim_start_matches('\u{feff}');
+
+    match format {
+        Some(BibleFormat::Usfm) => parse_usfm(input),
+        Some(BibleFormat::Osis) => parse_osis(input),
+        Some(BibleFormat::Zefania) => parse_zefania(input),
+        None if input.trim_start().starts_with('<') => {
+            let doc = parse_xml(input)?;
+            let root = doc.root_element().tag_name().name();
+            if root.eq_ignore_ascii_case("osis") {
+                read_osis(&doc)
+            } else if root.eq_ignore_ascii_case("xmlbible") {
+                read_zefania(&doc)
+            } else {
+                Err(format!("Unrecognized XML Bible format: root element <{}>", root))
+            }
+        }
+        None => parse_usfm(input),
+    }
+}
+
+/// Match parsed verses against the books table, renumbering them from the
+/// source's versification scheme to KJV. Verses in unknown books, chapters
+/// past the end of a book, empty verses and duplicates are reported instead
+/// of imported.
+pub fn map_verses(
+    parsed: Vec<ParsedVerse>,
+    books: &[Book],
+    scheme: Scheme,
+) -> (Vec<ImportedVerse>, Vec<UnmappedVerse>) {
+    let mut verses: Vec<ImportedVerse> = Vec::with_capacity(parsed.len());
+    let mut unmapped = Vec::new();
+    // KJV position -> (index in verses, chapter and verse in the source)
+    let mut seen: HashMap<(i64, i64, i64), (usize, i64, i64)> = HashMap::new();
+
+    for v in parsed {
+        let reference = format!("{} {}:{}", v.book, v.chapter, v.verse);
+        let book = v.book_id.and_then(|id| books.iter().find(|b| b.id == id));
+
+        // Store everything in KJV numbering
+        let (chapter, verse, end_verse) = match book {
+            Some(b) => {
+                let (chapter, verse) = versification::convert(b.id, v.chapter, v.verse, scheme, Scheme::Kjv);
+                let end_verse = v.end_verse
+                    .map(|end| versification::convert(b.id, v.chapter, end, scheme, Scheme::Kjv).1)
+                    .filter(|&end| end > verse);
+                (chapter, verse, end_verse)
+            }
+            None => (v.chapter, v.verse, v.end_verse),
+        };
+
+        let previous = book.and_then(|b| seen.get(&(b.id, chapter, verse)).copied());
+
+        // Two source verses numbered as one KJV verse (a two-verse Psalm title) are joined
+        if let Some((i, source_chapter, source_verse)) = previous {
+            if (source_chapter, source_verse) != (v.chapter, v.verse) && !v.text.is_empty() {
+                let joined = &mut verses[i];
+                if joined.markup.is_some() || v.markup.is_some() {
+                    let first = joined.markup.take().unwrap_or_else(|| markup::escape(&joined.text));
+                    let second = v.markup.unwrap_or_else(|| markup::escape(&v.text));
+                    joined.markup = Some(format!("{} {}", first, second));
+                }
+                joined.text.push(' ');
+                joined.text.push_str(&v.text);
+                continue;
+            }
+        }
+
+        let reason = match book {
+            None => Some(format!("unknown book '{}'", v.book)),
+            Some(b) if chapter < 1 || chapter > b.chapter_count => {
+                Some(format!("{} has {} chapters", b.name, b.chapter_count))
+            }
+            Some(_) if verse < 0 => Some("invalid verse number".to_string()),
+            Some(_) if v.text.is_empty() => Some("no text".to_string()),
+            Some(_) if previous.is_some() => Some("duplicate verse".to_string()),
+            Some(_) => None,
+        };
+
+        match (reason, book) {
+            (None, Some(b)) => {
+                seen.insert((b.id, chapter, verse), (verses.len(), v.chapter, v.verse));
+                verses.push(ImportedVerse {
+                    book_id: b.id,
+                    chapter,
+                    verse,
+                    end_verse,
+                    text: v.text,
+                    markup: v.markup,
+                });
+            }
+            (reason, _) => unmapped.push(UnmappedVerse {
+                reference,
+                reason: reason.unwrap_or_default(),
+            }),
+        }
+    }
+
+    (verses, unmapped)
+}
+
+// XML formats
+
+fn parse_xml(input: &str) -> Result<Document<'_>, String> {
+    let options = ParsingOptions { allow_dtd: true, ..ParsingOptions::default() };
+    Document::parse_with_options(input, options).map_err(|e| format!("Malformed XML: {}", e))
+}
+
+fn location(node: Node) -> String {
+    let pos = node.document().text_pos_at(node.range().start);
+    format!("line {}, column {}", pos.row, pos.col)
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    node.descendants()
+        .find(|n| n.tag_name().name().eq_ignore_ascii_case(name))
+        .and_then(|n| n.text())
+        .map(collapse_whitespace)
+        .filter(|t| !t.is_empty())
+}
+
+pub fn parse_osis(input: &str) -> Result<ParsedBible, String> {
+    read_osis(&parse_xml(input)?)
+}
+
+pub fn parse_zefania(input: &str) -> Result<ParsedBible, String> {
+    read_zefania(&parse_xml(input)?)
+}
+
+struct OsisVerse {
+    id: String,
+    end_id: Option<String>,  // Last id of merged verses ("Gen.1.1 Gen.1.2")
+    location: String,
+    text: String,
+    markup: Option<String>,
+}
+
+#[derive(Default)]
+struct OsisWalk {
+    chapter: Option<String>,  // osisID of the enclosing chapter, for Psalm titles
+    current: Option<OsisVerse>,
+    verses: Vec<OsisVerse>,
+    buffer: MarkupBuffer,
+    quotes: Vec<String>,  // sIDs of open <q who="Jesus"/> milestones
+}
+
+impl OsisWalk {
+    fn start(&mut self, id: &str, end_id: Option<&str>, node: Node) {
+        self.finish();
+        self.buffer.begin();
+        self.current = Some(OsisVerse {
+            id: id.to_string(),
+            end_id: end_id.map(str::to_string),
+            location: location(node),
+            text: String::new(),
+            markup: None,
+        });
+    }
+
+    fn finish(&mut self) {
+        if let Some(mut v) = self.current.take() {
+            (v.text, v.markup) = self.buffer.finish();
+            self.verses.push(v);
+        }
+    }
+}
+
+/// Verse text is collected in document order, so both container verses
+/// (<verse osisID="..">text</verse>) and milestones (<verse sID=".."/> text
+/// <verse eID=".."/>) work, including milestones that cross paragraphs.
+/// Psalm titles are read as verse 0 of their chapter.
+fn read_osis(doc: &Document) -> Result<ParsedBible, String> {
+    let root = doc.root_element();
+    if !root.tag_name().name().eq_ignore_ascii_case("osis") {
+        return Err(format!("Not an OSIS document: root element is <{}>", root.tag_name().name()));
+    }
+
+    let osis_text = root.children().find(|n| n.has_tag_name("osisText"));
+    let language = osis_text
+        .and_then(|n| n.attribute((roxmltree::NS_XML_URI, "lang")))
+        .map(str::to_string);
+    let title = osis_text
+        .and_then(|n| n.children().find(|c| c.has_tag_name("header")))
+        .and_then(|h| child_text(h, "title"));
+
+    let mut walk = OsisWalk::default();
+    walk_osis(root, &mut walk)?;
+    walk.finish();
+
+    let mut verses = Vec::with_capacity(walk.verses.len());
+    for v in walk.verses {
+        let mut verse = parse_osis_id(&v.id, &v.location)?;
+        if let Some(end_id) = &v.end_id {
+            let end = parse_osis_id(end_id, &v.location)?;
+            if end.book == verse.book && end.chapter == verse.chapter && end.verse > verse.verse {
+                verse.end_verse = Some(end.verse);
+            }
+        }
+        verse.text = v.text;
+        verse.markup = v.markup;
+        verses.push(verse);
+    }
+
+    Ok(ParsedBible { format: BibleFormat::Osis, title, language, verses })
+}
+
+fn walk_osis(node: Node, walk: &mut OsisWalk) -> Result<(), String> {
+    for child in node.children() {
+        if child.is_text() {
+            if walk.current.is_some() {
+                walk.buffer.push_text(child.text().unwrap_or_default());
+            }
+            continue;
+        }
+        if !child.is_element() {
+            continue;
+        }
+
+        match child.tag_name().name() {
+            "verse" => {
+                if child.attribute("eID").is_some() {
+                    walk.finish();
+                    continue;
+                }
+
+                let mut ids = child.attribute("osisID").or_else(|| child.attribute("sID"))
+                    .unwrap_or_default()
+                    .split_whitespace();
+                let id = ids.next()
+                    .ok_or_else(|| format!("OSIS {}: <verse> without an osisID", location(child)))?;
+                walk.start(id, ids.last(), child);
+
+                if child.attribute("sID").is_none() {
+                    walk_osis(child, walk)?;
+                    walk.finish();
+                }
+            }
+            "chapter" => {
+                if let Some(id) = child.attribute("osisID").or_else(|| child.attribute("sID")) {
+                    walk.chapter = Some(id.to_string());
+                }
+                walk_osis(child, walk)?;
+            }
+            "title" if child.attribute("type") == Some("psalm") => {
+                let Some(chapter) = walk.chapter.clone() else { continue };
+                walk.start(&format!("{}.0", chapter), None, child);
+                walk_osis(child, walk)?;
+                walk.finish();
+            }
+            "note" | "header" => {}
+            "title" if child.attribute("canonical") != Some("true") => {}
+            "transChange" if child.attribute("type") == Some("added") => {
+                walk.buffer.open(Style::Added);
+                walk_osis(child, walk)?;
+                walk.buffer.close(Style::Added);
+            }
+            // Words of Christ, as a container or as sID/eID milestones
+            "q" if child.attribute("who") == Some("Jesus") || child.attribute("eID").is_some() => {
+                if let Some(id) = child.attribute("eID") {
+                    if let Some(pos) = walk.quotes.iter().position(|q| q == id) {
+                        walk.quotes.remove(pos);
+                        walk.buffer.close(Style::WordsOfChrist);
+                    }
+                } else if let Some(id) = child.attribute("sID") {
+                    walk.quotes.push(id.to_string());
+                    walk.buffer.open(Style::WordsOfChrist);
+                } else {
+                    walk.buffer.open(Style::WordsOfChrist);
+                    walk_osis(child, walk)?;
+                    walk.buffer.close(Style::WordsOfChrist);
+                }
+            }
+            name => {
+                walk_osis(child, walk)?;
+                if matches!(name, "l" | "lb" | "lg" | "p" | "q" | "item") && walk.current.is_some() {
+                    walk.buffer.push_text(" ");
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse "Gen.1.1" (optionally "KJV:Gen.1.1") into an empty verse
+fn parse_osis_id(id: &str, location: &str) -> Result<ParsedVerse, String> {
+    let reference = id.rsplit(':').next().unwrap_or(id);
+    let parts: Vec<&str> = reference.split('.').collect();
+
+    let (book, chapter, verse) = match parts.as_slice() {
+        [book, chapter, verse] => (*book, chapter.parse::<i64>(), verse.parse::<i64>()),
+        _ => return Err(format!("OSIS {}: invalid osisID '{}', expected Book.Chapter.Verse", location, id)),
+    };
+
+    match (chapter, verse) {
+        (Ok(chapter), Ok(verse)) => Ok(ParsedVerse {
+            book_id: osis_book_id(book),
+            book: book.to_string(),
+            chapter,
+            verse,
+            end_verse: None,
+            text: String::new(),
+            markup: None,
+        }),
+        _ => Err(format!("OSIS {}: invalid osisID '{}', expected Book.Chapter.Verse", location, id)),
+    }
+}
+
+fn read_zefania(doc: &Document) -> Result<ParsedBible, String> {
+    let root = doc.root_element();
+    if !root.tag_name().name().eq_ignore_ascii_case("xmlbible") {
+        return Err(format!("Not a Zefania document: root element is <{}>", root.tag_name().name()));
+    }
+
+    let info = root.children().find(|n| n.tag_name().name().eq_ignore_ascii_case("information"));
+    let title = info.and_then(|i| child_text(i, "title"))
+        .or_else(|| root.attribute("biblename").map(str::to_string));
+    let language = info.and_then(|i| child_text(i, "language")).map(|l| l.to_lowercase());
+
+    let number = |node: Node, attr: &str| -> Result<i64, String> {
+        let value = node.attribute(attr)
+            .ok_or_else(|| format!("Zefania {}: <{}> without {}", location(node), node.tag_name().name(), attr))?;
+        value.trim().parse::<i64>()
+            .map_err(|_| format!("Zefania {}: invalid {} '{}'", location(node), attr, value))
+    };
+    let is = |node: &Node, name: &str| node.tag_name().name().eq_ignore_ascii_case(name);
+
+    let mut verses = Vec::new();
+    for book in root.children().filter(|n| is(n, "biblebook")) {
+        let book_number = number(book, "bnumber")?;
+        let book_name = book.attribute("bname").map(str::to_string).unwrap_or_else(|| book_number.to_string());
+        let book_id = BOOK_CODES.iter().any(|(id, _, _)| *id == book_number).then_some(book_number);
+
+        for chapter in book.children().filter(|n| is(n, "chapter")) {
+            let chapter_number = number(chapter, "cnumber")?;
+
+            for verse in chapter.children().filter(|n| is(n, "vers")) {
+                let mut buffer = MarkupBuffer::default();
+                zefania_text(verse, &mut buffer);
+                let (text, markup) = buffer.finish();
+
+                verses.push(ParsedVerse {
+                    book_id,
+                    book: book_name.clone(),
+                    chapter: chapter_number,
+                    verse: number(verse, "vnumber")?,
+                    end_verse: None,
+                    text,
+                    markup,
+                });
+            }
+        }
+    }
+
+    Ok(ParsedBible { format: BibleFormat::Zefania, title, language, verses })
+}
+
+/// The style of a Zefania <STYLE> element: fs="italic" for supplied words,
+/// a red css color for words of Christ
+fn zefania_style(node: Node) -> Option<Style> {
+    let css = node.attribute("css").unwrap_or_default().to_lowercase().replace(' ', "");
+    if node.attribute("fs").is_some_and(|fs| fs.eq_ignore_ascii_case("italic")) {
+        Some(Style::Added)
+    } else if ["color:red", "color:#ff0000", "color:#f00"].iter().any(|c| css.contains(c)) {
+        Some(Style::WordsOfChrist)
+    } else {
+        None
+    }
+}
+
+fn zefania_text(node: Node, out: &mut MarkupBuffer) {
+    for child in node.children() {
+        if child.is_text() {
+            out.push_text(child.text().unwrap_or_default());
+        } else if child.is_element() {
+            match child.tag_name().name().to_uppercase().as_str() {
+                "NOTE" | "XREF" | "DIV" => {}
+                "BR" => out.push_text(" "),
+                "STYLE" => match zefania_style(child) {
+                    Some(style) => {
+                        out.open(style);
+                        zefania_text(child, out);
+                        out.close(style);
+                    }
+                    None => zefania_text(child, out),
+                },
+                _ => zefania_text(child, out),
+            }
+        }
+    }
+}
+
+// USFM
+
+struct UsfmState {
+    book: Option<(String, Option<i64>)>,
+    chapter: Option<i64>,
+    verse: Option<ParsedVerse>,
+    verses: Vec<ParsedVerse>,
+    buffer: MarkupBuffer,
+}
+
+impl UsfmState {
+    fn push_text(&mut self, text: &str) {
+        if self.verse.is_some() {
+            // Attributes such as \w word|strong="H1234"\w* are not verse text
+            let text = text.split('|').next().unwrap_or_default();
+            self.buffer.push_text(text);
+        }
+    }
+
+    fn start_verse(&mut self, verse: ParsedVerse) {
+        self.finish_verse();
+        self.buffer.begin();
+        self.verse = Some(verse);
+    }
+
+    fn finish_verse(&mut self) {
+        if let Some(mut v) = self.verse.take() {
+            (v.text, v.markup) = self.buffer.finish();
+            self.verses.push(v);
+        }
+    }
+}
+
+/// Split the marker name off the start of `s` (after the backslash):
+/// letters, digits and a '+' prefix, plus a trailing '*' for end markers
+fn split_marker(s: &str) -> (&str, &str) {
+    let mut end = s.find(|c: char| !(c.is_ascii_alphanumeric() || c == '+')).unwrap_or(s.len());
+    if s[end..].starts_with('*') {
+        end += 1;
+    }
+    (&s[..end], &s[end..])
+}
+
+/// Split the first whitespace-delimited word off `s`
+fn split_word(s: &str) -> (&str, &str) {
+    let s = s.trim_start();
+    let end = s.find(char::is_whitespace).unwrap_or(s.len());
+    (&s[..end], &s[end..])
+}
+
+pub fn parse_usfm(input: &str) -> Result<ParsedBible, String> {
+    let mut state = UsfmState { book: None, chapter: None, verse: None, verses: Vec::new(), buffer: MarkupBuffer::default() };
+    // End marker of a footnote or cross reference being skipped, and where it started
+    let mut skip_until: Option<(String, usize)> = None;
+
+    for (index, line) in input.lines().enumerate() {
+        let line_no = index + 1;
+        let mut rest = line;
+
+        loop {
+            let Some(pos) = rest.find('\\') else {
+                if skip_until.is_none() {
+                    state.push_text(rest);
+                }
+                break;
+            };
+            if skip_until.is_none() {
+                state.push_text(&rest[..pos]);
+            }
+
+            let (marker, after) = split_marker(&rest[pos + 1..]);
+            let marker = marker.trim_start_matches('+');
+            // The space after an opening marker is a delimiter; after an end marker it is text
+            rest = if marker.ends_with('*') { after } else { after.strip_prefix(' ').unwrap_or(after) };
+
+            if let Some((end, _)) = &skip_until {
+                if marker == end {
+                    skip_until = None;
+                }
+                continue;
+            }
+
+            let base = marker.trim_end_matches(|c: char| c.is_ascii_digit());
+            match marker {
+                "" => return Err(format!("USFM line {}: stray backslash", line_no)),
+                "id" => {
+                    let (code, _) = split_word(rest);
+                    if code.is_empty() {
+                        return Err(format!("USFM line {}: \\id without a book code", line_no));
+                    }
+                    state.finish_verse();
+                    state.book = Some((code.to_string(), usfm_book_id(code)));
+                    state.chapter = None;
+                    break;
+                }
+                "c" => {
+                    if state.book.is_none() {
+                        return Err(format!("USFM line {}: \\c before \\id", line_no));
+                    }
+                    let (number, after) = split_word(rest);
+                    let chapter = number.parse::<i64>()
+                        .map_err(|_| format!("USFM line {}: invalid chapter number '{}'", line_no, number))?;
+                    state.finish_verse();
+                    state.chapter = Some(chapter);
+                    rest = after;
+                }
+                "v" => {
+                    let (Some((book, book_id)), Some(chapter)) = (&state.book, state.chapter) else {
+                        return Err(format!("USFM line {}: \\v before \\c", line_no));
+                    };
+                    // Verse bridges ("3-4") are filed under the first number; segments ("3a") are ignored
+                    let (number, after) = split_word(rest);
+                    let mut numbers = number.split('-')
+                        .map(|n| n.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse::<i64>());
+                    let verse = numbers.next()
+                        .and_then(|n| n.ok())
+                        .ok_or_else(|| format!("USFM line {}: invalid verse number '{}'", line_no, number))?;
+                    let end_verse = numbers.next().and_then(|n| n.ok()).filter(|&end| end > verse);
+
+                    let verse = ParsedVerse {
+                        book_id: *book_id,
+                        book: book.clone(),
+                        chapter,
+                        verse,
+                        end_verse,
+                        text: String::new(),
+                        markup: None,
+                    };
+                    state.start_verse(verse);
+                    rest = after;
+                }
+                "d" => {
+                    // A Psalm title becomes verse 0 of its chapter
+                    let (Some((book, book_id)), Some(chapter)) = (&state.book, state.chapter) else {
+                        return Err(format!("USFM line {}: \\d before \\c", line_no));
+                    };
+                    let title = ParsedVerse {
+                        book_id: *book_id,
+                        book: book.clone(),
+                        chapter,
+                        verse: 0,
+                        end_verse: None,
+                        text: String::new(),
+                        markup: None,
+                    };
+                    state.start_verse(title);
+                }
+                // Supplied words and words of Christ
+                "add" => state.buffer.open(Style::Added),
+                "add*" => state.buffer.close(Style::Added),
+                "wj" => state.buffer.open(Style::WordsOfChrist),
+                "wj*" => state.buffer.close(Style::WordsOfChrist),
+                _ if USFM_SKIP_LINE.contains(&base) => break,
+                _ if USFM_SKIP_SPAN.contains(&marker) => skip_until = Some((format!("{}*", marker), line_no)),
+                // Paragraph and character styles: the text belongs to the current verse
+                _ => {}
+            }
+        }
+
+        if skip_until.is_none() {
+            state.push_text(" ");
+        }
+    }
+
+    if let Some((end, line_no)) = skip_until {
+        return Err(format!("USFM line {}: unterminated note, expected \\{}", line_no, end));
+    }
+    state.finish_verse();
+
+    Ok(ParsedBible { format: BibleFormat::Usfm, title: None, language: None, verses: state.verses })
+}
+
+/// A link read from OpenBible.info's cross reference file
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedCrossReference {
+    pub from: [i64; 3],           // (book_id, chapter, verse)
+    pub to: [i64; 3],             // First verse of the target
+    pub to_end: Option<[i64; 3]>, // Last verse when the target is a range
+    pub votes: i64,
+}
+
+/// Read OpenBible.info's cross_references.txt
+pub fn read_cross_references(path: &Path) -> Result<Vec<ParsedCrossReference>, String> {
+    let input = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+    parse_cross_references(&input)
+}
+
+/// Parse OpenBible.info's cross references: a header line, then one link per
+/// line as "Gen.1.1<TAB>Prov.8.22-Prov.8.30<TAB>59" (from, to or a range, votes)
+pub fn parse_cross_references(input: &str) -> Result<Vec<ParsedCrossReference>, String> {
+    let mut references = Vec::new();
+
+    for (index, line) in input.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("From Verse") {
+            continue;
+        }
+
+        let location = format!("line {}", index + 1);
+        let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let [from, to, votes] = fields.as_slice() else {
+            return Err(format!("Cross references {}: expected 3 tab-separated fields, found {}", location, fields.len()));
+        };
+
+        let (to, to_end) = match to.split_once('-') {
+            Some((first, last)) => (first, Some(last)),
+            None => (*to, None),
+        };
+        let votes = votes.parse::<i64>()
+            .map_err(|_| format!("Cross references {}: invalid votes '{}'", location, votes))?;
+
+        references.push(ParsedCrossReference {
+            from: cross_reference_verse(from, &location)?,
+            to: cross_reference_verse(to, &location)?,
+            to_end: to_end.map(|end| cross_reference_verse(end, &location)).transpose()?,
+            votes,
+        });
+    }
+
+    if references.is_empty() {
+        return Err("No cross references found: expected OpenBible.info's cross_references.txt".to_string());
+    }
+    Ok(references)
+}
+
+fn cross_reference_verse(reference: &str, location: &str) -> Result<[i64; 3], String> {
+    let verse = parse_osis_id(reference, location)?;
+    match verse.book_id {
+        Some(book_id) => Ok([book_id, verse.chapter, verse.verse]),
+        None => Err(format!("Cross references {}: unknown book '{}'", location, verse.book)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(id: i64, name: &str, chapter_count: i64) -> Book {
+        Book {
+            id,
+            name: name.to_string(),
+            abbreviation: name[..3].to_string(),
+            testament: if id < 40 { "OT" } else { "NT" }.to_string(),
+            chapter_count,
+            sort_order: id,
+        }
+    }
+
+    fn books() -> Vec<Book> {
+        vec![book(1, "Genesis", 50), book(19, "Psalms", 150), book(39, "Malachi", 4), book(43, "John", 21)]
+    }
+
+    fn verses(bible: &ParsedBible) -> Vec<(Option<i64>, i64, i64, &str)> {
+        bible.verses.iter().map(|v| (v.book_id, v.chapter, v.verse, v.text.as_str())).collect()
+    }
+
+    const OSIS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osis xmlns="http://www.bibletechnologies.net/2003/OSIS/namespace">
+<osisText osisIDWork="TST" xml:lang="en">
+<header><work osisWork="TST"><title>Test Bible</title></work></header>
+<div type="book" osisID="Gen">
+<chapter osisID="Gen.1">
+<title>The Creation</title>
+<verse osisID="Gen.1.1">In the <w lemma="strong:H7225">beginning</w> God<note>A note</note> created.</verse>
+<p><verse sID="Gen.1.2" osisID="Gen.1.2"/>And the earth
+<lb/>was void.<verse eID="Gen.1.2"/></p>
+<verse osisID="Gen.1.3 Gen.1.4">Two verses.</verse>
+</chapter>
+</div>
+<div type="book" osisID="Ps"><chapter osisID="Ps.3">
+<title type="psalm" canonical="true">A Psalm of David.</title>
+<verse osisID="Ps.3.1">LORD, how are they increased</verse>
+</chapter></div>
+<div type="book" osisID="Tob"><chapter osisID="Tob.1"><verse osisID="Tob.1.1">Tobit</verse></chapter></div>
+</osisText>
+</osis>"#;
+
+    #[test]
+    fn reads_osis() {
+        let bible = parse_bible(OSIS, None).unwrap();
+        assert_eq!(bible.format, BibleFormat::Osis);
+        assert_eq!(bible.title.as_deref(), Some("Test Bible"));
+        assert_eq!(bible.language.as_deref(), Some("en"));
+        assert_eq!(verses(&bible), vec![
+            (Some(1), 1, 1, "In the beginning God created."),
+            (Some(1), 1, 2, "And the earth was void."),
+            (Some(1), 1, 3, "Two verses."),
+            (Some(19), 3, 0, "A Psalm of David."),
+            (Some(19), 3, 1, "LORD, how are they increased"),
+            (None, 1, 1, "Tobit"),
+        ]);
+        assert_eq!(bible.verses[2].end_verse, Some(4));
+    }
+
+    #[test]
+    fn reports_osis_errors_with_their_location() {
+        let error = parse_osis(r#"<osis><osisText><verse osisID="Gen.x.1">a</verse></osisText></osis>"#).unwrap_err();
+        assert!(error.contains("line 1, column 17"), "{}", error);
+        assert!(error.contains("invalid osisID 'Gen.x.1'"), "{}", error);
+
+        let error = parse_osis("<osis><osisText><verse>a</verse></osisText></osis>").unwrap_err();
+        assert!(error.contains("without an osisID"), "{}", error);
+
+        let error = parse_osis("<osis><osisText>\n</osisTex></osis>").unwrap_err();
+        assert!(error.starts_with("Malformed XML"), "{}", error);
+
+        assert!(parse_osis("<XMLBIBLE/>").unwrap_err().contains("Not an OSIS document"));
+    }
+
+    const USFM: &str = "\\id GEN Test\n\\h Genesis\n\\mt1 Genesis\n\\c 1\n\\s1 The Creation\n\\p\n\
+        \\v 1 In the \\w beginning|strong=\"H7225\"\\w* God\\f + \\fr 1:1 \\ft A note\\f* created.\n\
+        \\v 2 And the earth\n\\q1 was void.\n\\v 3-4 Two verses.\n\\v 5a Half a verse.\n\
+        \\id PSA\n\\c 3\n\\d A Psalm of David.\n\\v 1 LORD, how are they increased\n";
+
+    #[test]
+    fn reads_usfm() {
+        let bible = parse_bible(USFM, None).unwrap();
+        assert_eq!(bible.format, BibleFormat::Usfm);
+        assert_eq!(verses(&bible), vec![
+            (Some(1), 1, 1, "In the beginning God created."),
+            (Some(1), 1, 2, "And the earth was void."),
+            (Some(1), 1, 3, "Two verses."),
+            (Some(1), 1, 5, "Half a verse."),
+            (Some(19), 3, 0, "A Psalm of David."),
+            (Some(19), 3, 1, "LORD, how are they increased"),
+        ]);
+        assert_eq!(bible.verses[2].end_verse, Some(4));
+    }
+
+    #[test]
+    fn reports_usfm_errors_with_their_line() {
+        let error = |input: &str| parse_usfm(input).unwrap_err();
+
+        assert_eq!(error("\\id GEN\n\\c 1\n\\v x text"), "USFM line 3: invalid verse number 'x'");
+        assert_eq!(error("\\id GEN\n\\v 1 text"), "USFM line 2: \\v before \\c");
+        assert_eq!(error("\\c 1"), "USFM line 1: \\c before \\id");
+        assert_eq!(error("\\id GEN\n\\c 1\n\\v 1 a \\f + b\nc"), "USFM line 3: unterminated note, expected \\f*");
+        assert_eq!(error("\\id GEN\n\\c 1\n\\v 1 a \\ b"), "USFM line 3: stray backslash");
+    }
+
+    const ZEFANIA: &str = r#"<?xml version="1.0"?>
+<XMLBIBLE biblename="Zefania Test">
+<INFORMATION><title>Test Bible</title><language>ENG</language></INFORMATION>
+<BIBLEBOOK bnumber="43" bname="John"><CHAPTER cnumber="3">
+<VERS vnumber="16">For God so loved<NOTE>A note</NOTE> the<BR/>world.</VERS>
+<VERS vnumber="17"></VERS>
+</CHAPTER></BIBLEBOOK>
+<BIBLEBOOK bnumber="70" bname="Tobit"><CHAPTER cnumber="1"><VERS vnumber="1">Tobit</VERS></CHAPTER></BIBLEBOOK>
+</XMLBIBLE>"#;
+
+    #[test]
+    fn reads_zefania() {
+        let bible = parse_bible(ZEFANIA, None).unwrap();
+        assert_eq!(bible.format, BibleFormat::Zefania);
+        assert_eq!(bible.title.as_deref(), Some("Test Bible"));
+        assert_eq!(bible.language.as_deref(), Some("eng"));
+        assert_eq!(verses(&bible), vec![
+            (Some(43), 3, 16, "For God so loved the world."),
+            (Some(43), 3, 17, ""),
+            (None, 1, 1, "Tobit"),
+        ]);
+
+        let error = parse_zefania(r#"<XMLBIBLE><BIBLEBOOK bnumber="one"/></XMLBIBLE>"#).unwrap_err();
+        assert!(error.contains("invalid bnumber 'one'"), "{}", error);
+        assert!(parse_bible("<html/>", None).unwrap_err().contains("Unrecognized XML Bible format"));
+    }
+
+    #[test]
+    fn maps_verses_and_reports_the_rest() {
+        let mut parsed = parse_bible(ZEFANIA, None).unwrap().verses;
+        parsed.extend(parse_usfm("\\id JHN\n\\c 3\n\\v 16 Again.\n\\c 22\n\\v 1 Too far.\n").unwrap().verses);
+
+        let (verses, unmapped) = map_verses(parsed, &books(), Scheme::Kjv);
+        assert_eq!(verses.len(), 1);
+        assert_eq!(verses[0].text, "For God so loved the world.");
+
+        let reasons: Vec<(&str, &str)> = unmapped.iter().map(|u| (u.reference.as_str(), u.reason.as_str())).collect();
+        assert_eq!(reasons, vec![
+            ("John 3:17", "no text"),
+            ("Tobit 1:1", "unknown book 'Tobit'"),
+            ("JHN 3:16", "duplicate verse"),
+            ("JHN 22:1", "John has 21 chapters"),
+        ]);
+    }
+
+    #[test]
+    fn maps_other_versifications_to_kjv() {
+        let usfm = "\\id MAL\n\\c 3\n\\v 18 a\n\\v 19 b\n\\id PSA\n\\c 51\n\\v 1 Title one\n\\v 2 title two.\n\\v 3 Have mercy\n";
+        let (verses, unmapped) = map_verses(parse_usfm(usfm).unwrap().verses, &books(), Scheme::Hebrew);
+        assert!(unmapped.is_empty(), "{:?}", unmapped);
+
+        let numbered: Vec<(i64, i64, i64, &str)> = verses.iter().map(|v| (v.book_id, v.chapter, v.verse, v.text.as_str())).collect();
+        assert_eq!(numbered, vec![
+            (39, 3, 18, "a"),
+            (39, 4, 1, "b"),
+            // A two-verse Hebrew Psalm title is one KJV title
+            (19, 51, 0, "Title one title two."),
+            (19, 51, 1, "Have mercy"),
+        ]);
+    }
+
+    #[test]
+    fn reads_supplied_words_and_words_of_christ() {
+        let usfm = "\\id MAT\n\\c 5\n\\v 1 And he \\add was\\add* set: \\wj Blessed\n\\v 2 are \\wj* the poor.\n";
+        let bible = parse_usfm(usfm).unwrap();
+        assert_eq!(bible.verses[0].text, "And he was set: Blessed");
+        assert_eq!(bible.verses[0].markup.as_deref(), Some("And he <i>was</i> set: <j>Blessed</j>"));
+        assert_eq!(bible.verses[1].markup.as_deref(), Some("<j>are</j> the poor."));
+
+        let osis = r#"<osis><osisText><div type="book" osisID="Matt"><chapter osisID="Matt.5">
+<verse sID="Matt.5.1" osisID="Matt.5.1"/>He said <q who="Jesus" sID="q1" marker=""/>Blessed <transChange type="added">are</transChange><verse eID="Matt.5.1"/>
+<verse sID="Matt.5.2" osisID="Matt.5.2"/>the poor<q eID="q1" marker=""/>.<verse eID="Matt.5.2"/>
+<verse osisID="Matt.5.3">Peter said <q who="Peter">Lord</q></verse>
+</chapter></div></osisText></osis>"#;
+        let bible = parse_osis(osis).unwrap();
+        assert_eq!(bible.verses[0].text, "He said Blessed are");
+        assert_eq!(bible.verses[0].markup.as_deref(), Some("He said <j>Blessed <i>are</i></j>"));
+        assert_eq!(bible.verses[1].markup.as_deref(), Some("<j>the poor</j>."));
+        assert_eq!(bible.verses[2].markup, None);
+
+        let zefania = r#"<XMLBIBLE><BIBLEBOOK bnumber="40"><CHAPTER cnumber="5"><VERS vnumber="3"><STYLE css="color: #FF0000">Blessed <STYLE fs="italic">are</STYLE></STYLE> the poor</VERS></CHAPTER></BIBLEBOOK></XMLBIBLE>"#;
+        let bible = parse_zefania(zefania).unwrap();
+        assert_eq!(bible.verses[0].text, "Blessed are the poor");
+        assert_eq!(bible.verses[0].markup.as_deref(), Some("<j>Blessed <i>are</i></j> the poor"));
+    }
+
+    #[test]
+    fn reads_format_names() {
+        assert_eq!(BibleFormat::from_name(" SFM "), Ok(BibleFormat::Usfm));
+        assert_eq!(BibleFormat::from_name("zefania").map(|f| f.name()), Ok("zefania"));
+        assert!(BibleFormat::from_name("docx").unwrap_err().contains("expected osis, usfm or zefania"));
+    }
+
+    #[test]
+    fn reads_openbible_cross_references() {
+        let tsv = "\u{feff}From Verse\tTo Verse\tVotes\t#www.openbible.info CC-BY 2024-05-20\n\
+                   Gen.1.1\tProv.8.22-Prov.8.30\t59\n\
+                   Gen.1.1\tJohn.1.1\t348\n\
+                   \n\
+                   Ps.3.0\t2Sam.15.14\t-2\n";
+        let references = parse_cross_references(tsv).unwrap();
+        assert_eq!(references, vec![
+            ParsedCrossReference { from: [1, 1, 1], to: [20, 8, 22], to_end: Some([20, 8, 30]), votes: 59 },
+            ParsedCrossReference { from: [1, 1, 1], to: [43, 1, 1], to_end: None, votes: 348 },
+            ParsedCrossReference { from: [19, 3, 0], to: [10, 15, 14], to_end: None, votes: -2 },
+        ]);
+
+        assert!(parse_cross_references("Gen.1.1\tJohn.1.1\n").unwrap_err().contains("line 1: expected 3 tab-separated fields"));
+        assert!(parse_cross_references("Gen.1.1\tJohn.1.1\tmany\n").unwrap_err().contains("invalid votes 'many'"));
+        assert!(parse_cross_references("Gen.1.1\tTob.1.1\t3\n").unwrap_err().contains("unknown book 'Tob'"));
+        assert!(parse_cross_references("Gen.1\tJohn.1.1\t3\n").unwrap_err().contains("invalid osisID 'Gen.1'"));
+        assert!(parse_cross_references("From Verse\tTo Verse\tVotes\n").is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Mutex;
 use tauri::Manager;
 
@@ -6,12 +7,25 @@ mod models;
 mod commands;
 mod seed;
 mod llm;
+mod reference;
+mod search;
+pub mod import;
+mod parallel;
+mod versification;
+mod markup;
+mod usage;
+mod error;
+mod application;
+mod templates;
+mod semantic;
 
 use db::Database;
 
 // Application state
 pub struct AppState {
     pub db: Mutex<Database>,
+    // In-flight AI generations by request id; sending cancels one
+    pub generations: Mutex<HashMap<String, tokio::sync::oneshot::Sender<()>>>,
 }
 
 // Initialize the application
@@ -34,6 +48,7 @@ pub fn run() {
             // Store in app state
             app.manage(AppState {
                 db: Mutex::new(db),
+                generations: Mutex::new(HashMap::new()),
             });
 
             Ok(())
@@ -41,16 +56,32 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // Bible commands
             commands::get_books,
+            commands::get_translations,
             commands::get_verses,
             commands::get_verse,
             commands::get_verse_by_reference,
+            commands::get_passages,
             commands::get_random_verse,
+            commands::compare_passages,
+            commands::convert_versification,
+            // Import commands
+            commands::import_bible,
+            // Cross reference commands
+            commands::get_cross_references,
+            commands::get_cross_references_to,
+            commands::add_cross_reference,
+            commands::delete_cross_reference,
+            commands::import_cross_references,
             // Topic commands
             commands::get_topics,
             commands::get_verses_by_topic,
             commands::get_topic_by_slug,
             // Application commands
             commands::get_verse_application,
+            commands::save_action_steps,
+            commands::save_reflection_questions,
+            commands::delete_action_step,
+            commands::delete_reflection_question,
             // Search commands
             commands::search_verses,
             // User data commands
@@ -63,9 +94,21 @@ pub fn run() {
             commands::update_settings,
             // AI/LLM commands
             commands::check_llm_connection,
+            commands::pull_ollama_model,
+            commands::cancel_generation,
+            commands::clear_ai_cache,
+            commands::get_token_usage,
+            commands::get_model_prices,
+            commands::set_model_price,
+            commands::delete_model_price,
+            commands::get_prompt_templates,
+            commands::update_prompt_template,
+            commands::preview_prompt_template,
+            commands::reset_prompt_template,
             commands::get_ai_insight,
             commands::generate_action_steps,
             commands::generate_reflection_questions,
+            commands::ai_search,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,13 +1,33 @@
-use reqwest::Client;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::{Client, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
 use std::time::Duration;
+use tokio::sync::OnceCell;
+use crate::usage::QuotaExceeded;
+
+// Streamed generations can run longer than the client's 120s default
+const STREAM_TIMEOUT: Duration = Duration::from_secs(600);
+
+// Upper bound on the length of a completion
+pub const MAX_OUTPUT_TOKENS: u32 = 1024;
+
+const DEFAULT_TEMPERATURE: f32 = 0.7;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmConfig {
-    pub provider: String,        // "lmstudio", "openai", "claude"
+    pub provider: String,        // A name in PROVIDERS: "lmstudio", "ollama", "openai", "claude"
     pub base_url: Option<String>,
     pub model: Option<String>,
     pub api_key: Option<String>,
+    pub keep_alive: Option<String>,  // Ollama: how long the model stays loaded, e.g. "10m"
+    pub num_ctx: Option<u32>,        // Ollama: context window size
+    pub temperature: Option<f32>,    // None: DEFAULT_TEMPERATURE
+    pub stop_sequences: Vec<String>, // Text that ends a completion early
 }
 
 impl Default for LlmConfig {
@@ -17,16 +37,61 @@ impl Default for LlmConfig {
             base_url: Some("http://localhost:1234".to_string()),
             model: None,
             api_key: None,
+            keep_alive: None,
+            num_ctx: None,
+            temperature: None,
+            stop_sequences: Vec::new(),
         }
     }
 }
 
+/// A model a provider offers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LlmModel {
+    pub name: String,
+    pub size: Option<u64>,  // Bytes on disk, for local models
+}
+
+/// Whether one provider of an `LlmClient` can be reached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendStatus {
+    pub provider: String,
+    pub model: Option<String>,  // As configured; None for the provider's default
+    pub connected: bool,
+}
+
+/// Asks for a JSON object matching `schema` rather than free text. Providers
+/// enforce it as far as they can: a JSON schema response format for OpenAI
+/// and LM Studio, `format` for Ollama, and a tool the model must call for
+/// Claude. The text of the response is the JSON.
+#[derive(Debug, Clone)]
+pub struct JsonOutput {
+    pub name: &'static str,  // Letters, digits and underscores
+    pub description: &'static str,
+    pub schema: serde_json::Value,
+}
+
 #[derive(Debug, Serialize)]
 struct OpenAiRequest {
     model: String,
     messages: Vec<ChatMessage>,
     max_tokens: Option<u32>,
     temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    include_usage: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -54,6 +119,7 @@ struct ModelInfo {
 #[derive(Debug, Deserialize)]
 struct Choice {
     message: ChatMessage,
+    finish_reason: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -63,22 +129,65 @@ struct Usage {
     total_tokens: u32,
 }
 
+// One `data:` chunk of an OpenAI-compatible stream
+#[derive(Debug, Deserialize)]
+struct OpenAiStreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    usage: Option<Usage>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamChoice {
+    delta: StreamDelta,
+    finish_reason: Option<String>,  // Set on the last chunk
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
+// The Messages API takes the system prompt at the top level; messages are
+// only "user" and "assistant"
 #[derive(Debug, Serialize)]
 struct ClaudeRequest {
     model: String,
     max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
     messages: Vec<ChatMessage>,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop_sequences: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ClaudeTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+struct ClaudeTool {
+    name: String,
+    description: String,
+    input_schema: serde_json::Value,
 }
 
 #[derive(Debug, Deserialize)]
 struct ClaudeResponse {
     content: Vec<ClaudeContent>,
+    stop_reason: Option<String>,
     usage: ClaudeUsage,
 }
 
+// A content block: text, or the input of a tool_use block
 #[derive(Debug, Deserialize)]
 struct ClaudeContent {
-    text: String,
+    text: Option<String>,
+    input: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -87,9 +196,200 @@ struct ClaudeUsage {
     output_tokens: u32,
 }
 
-pub struct LlmClient {
-    client: Client,
-    config: LlmConfig,
+// Anthropic Messages stream events (ping, content_block_start/stop and
+// message_stop carry nothing we need)
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClaudeStreamEvent {
+    MessageStart { message: ClaudeStreamMessage },
+    ContentBlockDelta { delta: ClaudeDelta },
+    MessageDelta { delta: ClaudeMessageDelta, usage: ClaudeOutputUsage },
+    Error { error: ClaudeError },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClaudeStreamMessage {
+    usage: ClaudeUsage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClaudeDelta {
+    text: Option<String>,  // Set for text_delta
+    partial_json: Option<String>,  // Set for input_json_delta, a piece of tool input
+}
+
+#[derive(Debug, Deserialize)]
+struct ClaudeMessageDelta {
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClaudeOutputUsage {
+    output_tokens: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClaudeError {
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<ChatMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,  // A JSON schema the reply must follow
+    options: OllamaOptions,
+}
+
+#[derive(Debug, Serialize)]
+struct OllamaOptions {
+    num_predict: u32,
+    temperature: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_ctx: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stop: Vec<String>,
+}
+
+// A chat response, or one line of a streamed one. Counts come with the
+// final (done) line.
+#[derive(Debug, Deserialize)]
+struct OllamaChatResponse {
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+    done_reason: Option<String>,  // With the final line
+    #[serde(default)]
+    prompt_eval_count: u32,
+    #[serde(default)]
+    eval_count: u32,
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaMessage {
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaTagsResponse {
+    models: Vec<OllamaModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OllamaModel {
+    name: String,
+    size: Option<u64>,
+}
+
+/// Progress of a model pull, as reported by /api/pull
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PullProgress {
+    #[serde(default)]  // An error line has only `error`
+    pub status: String,
+    #[serde(default)]
+    pub total: Option<u64>,
+    #[serde(default)]
+    pub completed: Option<u64>,
+    #[serde(default, skip_serializing)]
+    error: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct SseEvent {
+    event: Option<String>,
+    data: String,
+}
+
+/// Splits a server-sent events body into events as chunks arrive. Bytes are
+/// buffered until an event is complete, so multi-byte characters split
+/// across chunks survive.
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend(chunk.iter().filter(|&&b| b != b'\r'));
+
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+            let block: Vec<u8> = self.buffer.drain(..pos + 2).collect();
+            let block = String::from_utf8_lossy(&block);
+
+            let mut event = None;
+            let mut data = Vec::new();
+            for line in block.lines() {
+                if let Some(name) = line.strip_prefix("event:") {
+                    event = Some(name.trim().to_string());
+                } else if let Some(value) = line.strip_prefix("data:") {
+                    data.push(value.strip_prefix(' ').unwrap_or(value));
+                }
+            }
+
+            if !data.is_empty() {
+                events.push(SseEvent { event, data: data.join("\n") });
+            }
+        }
+
+        events
+    }
+}
+
+/// Read a server-sent events response, handing each event to `on_event`
+async fn read_events(
+    mut response: Response,
+    mut on_event: impl FnMut(SseEvent) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut parser = SseParser::default();
+
+    while let Some(chunk) = response.chunk().await
+        .map_err(|e| format!("Stream interrupted: {}", e))?
+    {
+        for event in parser.push(&chunk) {
+            on_event(event)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Read a newline-delimited JSON response, handing each non-empty line to
+/// `on_line`
+async fn read_lines(
+    mut response: Response,
+    mut on_line: impl FnMut(&str) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut buffer: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = response.chunk().await
+        .map_err(|e| format!("Stream interrupted: {}", e))?
+    {
+        buffer.extend_from_slice(&chunk);
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            if !line.trim().is_empty() {
+                on_line(line.trim())?;
+            }
+        }
+    }
+
+    let rest = String::from_utf8_lossy(&buffer);
+    if !rest.trim().is_empty() {
+        on_line(rest.trim())?;
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -97,97 +397,568 @@ pub struct LlmResponse {
     pub text: String,
     pub input_tokens: u32,
     pub output_tokens: u32,
+    /// Why generation ended as the provider reports it, e.g. "stop",
+    /// "end_turn" or "max_tokens"/"length" for a truncated response
+    pub stop_reason: Option<String>,
 }
 
-impl LlmClient {
-    pub fn new(config: LlmConfig) -> Self {
+/// One LLM backend. `LlmClient` resolves the configured one from `PROVIDERS`.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// The model to use when none is configured
+    async fn default_model(&self) -> String;
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+    ) -> Result<LlmResponse, LlmError>;
+
+    /// Like `generate`, but streams the completion, calling `on_token` with
+    /// each piece of text as it arrives. Token usage comes with the result.
+    async fn stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+        on_token: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<LlmResponse, LlmError>;
+
+    async fn list_models(&self) -> Result<Vec<LlmModel>, LlmError>;
+
+    /// Whether the provider is reachable and has what it needs (an API key)
+    async fn health_check(&self) -> Result<bool, LlmError>;
+}
+
+type ProviderFactory = fn(&LlmConfig, Client) -> Box<dyn LlmProvider>;
+
+/// Providers by their `Settings::llm_provider` name
+const PROVIDERS: &[(&str, ProviderFactory)] = &[
+    ("lmstudio", |config, client| {
+        Box::new(OpenAiCompatible::new(client, config, "LM Studio", "http://localhost:1234/v1", None, false))
+    }),
+    ("ollama", |config, client| Box::new(OllamaProvider::new(client, config))),
+    ("openai", |config, client| {
+        Box::new(OpenAiCompatible::new(client, config, "OpenAI", "https://api.openai.com/v1", Some("gpt-4o-mini"), true))
+    }),
+    ("claude", |config, client| Box::new(ClaudeProvider::new(client, config))),
+];
+
+pub fn create_provider(config: &LlmConfig, client: Client) -> Result<Box<dyn LlmProvider>, String> {
+    PROVIDERS.iter()
+        .find(|(name, _)| *name == config.provider)
+        .map(|(_, factory)| factory(config, client))
+        .ok_or_else(|| format!("Unsupported provider: {}", config.provider))
+}
+
+/// Why a generation failed
+#[derive(Debug, thiserror::Error)]
+pub enum LlmError {
+    /// The server could not be reached
+    #[error("Request failed: {0}")]
+    Unreachable(String),
+    #[error("Request timed out: {0}")]
+    Timeout(String),
+    /// The API key is missing or was rejected
+    #[error("{0}")]
+    Auth(String),
+    /// 429, or Claude's 529 (overloaded)
+    #[error("Rate limited ({status}): {message}")]
+    RateLimited { status: u16, message: String, retry_after: Option<Duration> },
+    #[error("API error {status}: {message}")]
+    Api { status: u16, message: String, retry_after: Option<Duration> },
+    /// Refused before it was sent
+    #[error("{0}")]
+    QuotaExceeded(QuotaExceeded),
+    #[error("{0}")]
+    Other(String),
+}
+
+/// The category of an `LlmError`, for the UI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LlmErrorKind {
+    Unreachable,
+    Timeout,
+    Auth,
+    RateLimited,
+    Server,  // 5xx
+    Api,
+    QuotaExceeded,
+    Other,
+}
+
+impl LlmError {
+    pub fn kind(&self) -> LlmErrorKind {
+        match self {
+            Self::Unreachable(_) => LlmErrorKind::Unreachable,
+            Self::Timeout(_) => LlmErrorKind::Timeout,
+            Self::Auth(_) => LlmErrorKind::Auth,
+            Self::RateLimited { .. } => LlmErrorKind::RateLimited,
+            Self::Api { status, .. } if *status >= 500 => LlmErrorKind::Server,
+            Self::Api { .. } => LlmErrorKind::Api,
+            Self::QuotaExceeded(_) => LlmErrorKind::QuotaExceeded,
+            Self::Other(_) => LlmErrorKind::Other,
+        }
+    }
+
+    /// Whether the next provider in the fallback chain should be tried
+    pub fn is_unavailable(&self) -> bool {
+        matches!(
+            self.kind(),
+            LlmErrorKind::Unreachable | LlmErrorKind::Timeout | LlmErrorKind::RateLimited | LlmErrorKind::Server
+        )
+    }
+
+    /// Whether the same request may succeed if sent again shortly. A server
+    /// that can't be reached (usually a local one that isn't running) or has
+    /// timed out goes straight to the next provider instead.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self.kind(), LlmErrorKind::RateLimited | LlmErrorKind::Server)
+    }
+
+    /// How long the server asked us to wait before trying again
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } | Self::Api { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    fn from_send(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            Self::Timeout(error.to_string())
+        } else {
+            Self::Unreachable(error.to_string())
+        }
+    }
+
+    /// The error for a response with a non-success status
+    async fn from_response(response: Response) -> Self {
+        let status = response.status().as_u16();
+        let retry_after = retry_after(response.headers(), Utc::now());
+        let message = response.text().await.unwrap_or_default();
+
+        match status {
+            401 | 403 => Self::Auth(format!("API key rejected ({}): {}", status, message)),
+            429 | 529 => Self::RateLimited { status, message, retry_after },
+            _ => Self::Api { status, message, retry_after },
+        }
+    }
+}
+
+impl From<String> for LlmError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+/// Send a request, turning a failed send or an error status into an LlmError
+async fn send(req: RequestBuilder) -> Result<Response, LlmError> {
+    let response = req.send().await.map_err(LlmError::from_send)?;
+
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(LlmError::from_response(response).await)
+    }
+}
+
+/// How long a rate-limited or failing server asks us to wait: `retry-after-ms`
+/// and `retry-after` (seconds or an HTTP date), then the reset time of an
+/// exhausted OpenAI (`x-ratelimit-*`) or Claude (`anthropic-ratelimit-*`)
+/// limit
+fn retry_after(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+
+    if let Some(ms) = header("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        return Some(Duration::from_secs_f64(ms.max(0.0) / 1000.0));
+    }
+    if let Some(value) = header("retry-after") {
+        if let Ok(secs) = value.parse::<f64>() {
+            return Some(Duration::from_secs_f64(secs.max(0.0)));
+        }
+        if let Ok(date) = DateTime::parse_from_rfc2822(value) {
+            return Some((date.with_timezone(&Utc) - now).to_std().unwrap_or_default());
+        }
+    }
+
+    let mut wait = None;
+    for limit in ["requests", "tokens"] {
+        if header(&format!("x-ratelimit-remaining-{}", limit)) == Some("0") {
+            let reset = header(&format!("x-ratelimit-reset-{}", limit)).and_then(parse_reset_duration);
+            wait = wait.max(reset);
+        }
+    }
+    for limit in ["requests", "tokens", "input-tokens", "output-tokens"] {
+        if header(&format!("anthropic-ratelimit-{}-remaining", limit)) == Some("0") {
+            let reset = header(&format!("anthropic-ratelimit-{}-reset", limit))
+                .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+                .map(|date| (date.with_timezone(&Utc) - now).to_std().unwrap_or_default());
+            wait = wait.max(reset);
+        }
+    }
+    wait
+}
+
+/// An OpenAI reset duration such as "20ms", "1s" or "6m0s"
+fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut rest = value;
+
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+        let number: f64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+        let unit = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        total += number * match &rest[..unit] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return None,
+        };
+        rest = &rest[unit..];
+    }
+
+    Some(Duration::from_secs_f64(total))
+}
+
+/// The start of `text`, at most `max` bytes, cut on a character boundary so
+/// bodies with multibyte text can be quoted in errors
+fn truncate(text: &str, max: usize) -> &str {
+    if text.len() <= max {
+        return text;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// How a request is retried when the provider is rate limited or briefly
+/// failing
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+    max_attempts: u32,  // Including the first
+    max_wait: Duration,  // Total time spent waiting between attempts
+    base_delay: Duration,  // Doubled after each attempt, with jitter
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            max_wait: Duration::from_secs(60),
+            base_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait before retrying after `attempt` (from 1) failed with
+    /// `error`, having already waited `waited`; None to give up
+    fn delay(&self, error: &LlmError, attempt: u32, waited: Duration) -> Option<Duration> {
+        if !error.is_retryable() || attempt >= self.max_attempts {
+            return None;
+        }
+
+        let delay = error.retry_after().unwrap_or_else(|| {
+            // Between half and all of the exponential delay
+            let delay = self.base_delay.saturating_mul(1u32 << (attempt - 1).min(16));
+            let random = RandomState::new().hash_one(attempt) % 1000;
+            delay / 2 + delay.mul_f64(random as f64 / 2000.0)
+        });
+
+        (waited + delay <= self.max_wait).then_some(delay)
+    }
+}
+
+/// A failed attempt that is about to be retried
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RetryNotice {
+    pub provider: String,
+    pub kind: LlmErrorKind,
+    pub message: String,
+    pub attempt: u32,  // The attempt that failed, from 1
+    pub max_attempts: u32,
+    pub delay_ms: u64,
+}
+
+/// A response, with the provider and model that produced it
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub response: LlmResponse,
+    pub provider: String,
+    pub model: String,
+}
+
+/// One provider in the chain, with the model it uses resolved once
+struct Backend {
+    config: LlmConfig,
+    provider: Box<dyn LlmProvider>,
+    model: OnceCell<String>,
+}
+
+impl Backend {
+    fn new(config: LlmConfig) -> Result<Self, String> {
         let client = Client::builder()
             .timeout(Duration::from_secs(120))
             .build()
             .expect("Failed to create HTTP client");
 
-        Self { client, config }
+        let provider = create_provider(&config, client)?;
+        Ok(Self { config, provider, model: OnceCell::new() })
     }
 
-    /// Get the normalized base URL with /v1 suffix
-    fn get_base_url(&self) -> String {
-        let raw_base_url = self.config.base_url.clone().unwrap_or_else(|| {
-            if self.config.provider == "openai" {
-                "https://api.openai.com/v1".to_string()
-            } else {
-                "http://localhost:1234/v1".to_string()
+    /// The configured model, or the provider's default
+    async fn model(&self) -> String {
+        self.model.get_or_init(|| async {
+            match self.config.model.clone() {
+                Some(m) => m,
+                None => self.provider.default_model().await,
             }
-        });
+        }).await.clone()
+    }
+}
 
-        let base_url = raw_base_url.trim_end_matches('/').to_string();
+/// The configured provider, then any fallbacks. A request that is rate
+/// limited or answered with 5xx is retried following the `RetryPolicy`; a
+/// generation then moves on to the next provider when one is unavailable
+/// (unreachable, timed out, still rate limited or failing). Other errors
+/// are returned as they are.
+pub struct LlmClient {
+    backends: Vec<Backend>,  // Never empty
+    retry: RetryPolicy,
+    json: Option<JsonOutput>,
+}
 
-        // Ensure /v1 suffix for LM Studio compatibility
-        if !base_url.ends_with("/v1") {
-            format!("{}/v1", base_url)
-        } else {
-            base_url
-        }
+impl LlmClient {
+    pub fn new(config: LlmConfig) -> Result<Self, String> {
+        Ok(Self { backends: vec![Backend::new(config)?], retry: RetryPolicy::default(), json: None })
     }
 
-    /// Fetch available models from LM Studio
-    async fn get_available_models(&self) -> Result<Vec<String>, String> {
-        let base_url = self.get_base_url();
-        let url = format!("{}/models", base_url);
+    /// Add a provider to try when the ones before it are unavailable
+    pub fn with_fallback(mut self, config: LlmConfig) -> Result<Self, String> {
+        self.backends.push(Backend::new(config)?);
+        Ok(self)
+    }
 
-        println!("[LLM] Fetching available models from: {}", url);
+    /// Ask every provider for JSON in this shape instead of free text
+    pub fn with_json_output(mut self, json: JsonOutput) -> Self {
+        self.json = Some(json);
+        self
+    }
 
-        let response = self.client.get(&url)
-            .timeout(Duration::from_secs(10))
-            .send()
-            .await
-            .map_err(|e| format!("Failed to fetch models: {}", e))?;
+    /// The configured (first) provider
+    pub fn provider(&self) -> &str {
+        &self.backends[0].config.provider
+    }
 
-        if !response.status().is_success() {
-            return Err(format!("Failed to fetch models: {}", response.status()));
-        }
+    /// The model set for `provider` in the settings; None when its server
+    /// picks one
+    pub fn configured_model(&self, provider: &str) -> Option<&str> {
+        self.backends.iter()
+            .find(|b| b.config.provider == provider)
+            .and_then(|b| b.config.model.as_deref())
+    }
 
-        let body = response.text().await
-            .map_err(|e| format!("Failed to read models response: {}", e))?;
+    /// Wait before retrying after `attempt` failed, telling `on_retry`; false
+    /// to give up
+    async fn wait_to_retry(
+        &self,
+        provider: &str,
+        error: &LlmError,
+        attempt: u32,
+        waited: &mut Duration,
+        on_retry: &(impl Fn(&RetryNotice) + Send + Sync),
+    ) -> bool {
+        let Some(delay) = self.retry.delay(error, attempt, *waited) else {
+            return false;
+        };
 
-        println!("[LLM] Models response: {}", &body[..body.len().min(300)]);
+        println!("[LLM] {} attempt {} failed, retrying in {:?}: {}", provider, attempt, delay, error);
+        on_retry(&RetryNotice {
+            provider: provider.to_string(),
+            kind: error.kind(),
+            message: error.to_string(),
+            attempt,
+            max_attempts: self.retry.max_attempts,
+            delay_ms: delay.as_millis() as u64,
+        });
+        tokio::time::sleep(delay).await;
+        *waited += delay;
+        true
+    }
 
-        let models_response: ModelsResponse = serde_json::from_str(&body)
-            .map_err(|e| format!("Failed to parse models: {}", e))?;
+    /// Generate with the first provider that is available. `check` is called
+    /// with each provider and model before it is used; an error skips that
+    /// provider. `on_retry` is told about each retry. When every provider
+    /// fails, the first error is returned.
+    pub async fn generate(
+        &self,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        check: impl Fn(&str, &str) -> Result<(), LlmError> + Send + Sync,
+        on_retry: impl Fn(&RetryNotice) + Send + Sync,
+    ) -> Result<Completion, LlmError> {
+        let mut first_error = None;
+
+        for backend in &self.backends {
+            let provider = &backend.config.provider;
+            let model = backend.model().await;
+            if let Err(e) = check(provider, &model) {
+                first_error.get_or_insert(e);
+                continue;
+            }
 
-        Ok(models_response.data.iter().map(|m| m.id.clone()).collect())
-    }
+            let mut attempt = 1;
+            let mut waited = Duration::ZERO;
+            let result = loop {
+                match backend.provider.generate(&model, prompt, system_prompt, self.json.as_ref()).await {
+                    Err(e) if self.wait_to_retry(provider, &e, attempt, &mut waited, &on_retry).await => attempt += 1,
+                    result => break result,
+                }
+            };
 
-    pub async fn generate(&self, prompt: &str, system_prompt: Option<&str>) -> Result<LlmResponse, String> {
-        match self.config.provider.as_str() {
-            "lmstudio" | "openai" => self.call_openai_compatible(prompt, system_prompt).await,
-            "claude" => self.call_claude(prompt, system_prompt).await,
-            _ => Err(format!("Unsupported provider: {}", self.config.provider)),
+            match result {
+                Ok(response) => return Ok(Completion { response, provider: provider.clone(), model }),
+                Err(e) if e.is_unavailable() => {
+                    println!("[LLM] {} unavailable, trying the next provider: {}", provider, e);
+                    first_error.get_or_insert(e);
+                }
+                Err(e) => return Err(e),
+            }
         }
+
+        Err(first_error.expect("LlmClient has at least one provider"))
     }
 
-    async fn call_openai_compatible(&self, prompt: &str, system_prompt: Option<&str>) -> Result<LlmResponse, String> {
-        let base_url = self.get_base_url();
-        println!("[LLM] Using base_url: {}", base_url);
+    /// Like `generate`, but streams the completion and calls `on_token` with
+    /// each piece of text as it arrives. Token usage comes with the result.
+    /// Once text has arrived, a failure is not retried, with the same
+    /// provider or another.
+    pub async fn generate_stream(
+        &self,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        check: impl Fn(&str, &str) -> Result<(), LlmError> + Send + Sync,
+        on_retry: impl Fn(&RetryNotice) + Send + Sync,
+        mut on_token: impl FnMut(&str) + Send,
+    ) -> Result<Completion, LlmError> {
+        let mut first_error = None;
+
+        for backend in &self.backends {
+            let provider = &backend.config.provider;
+            let model = backend.model().await;
+            if let Err(e) = check(provider, &model) {
+                first_error.get_or_insert(e);
+                continue;
+            }
 
-        // Get model name - for LM Studio, try to fetch actual model if not configured
-        let model = if let Some(m) = self.config.model.clone() {
-            m
-        } else if self.config.provider == "openai" {
-            "gpt-4o-mini".to_string()
-        } else {
-            // For LM Studio, try to get the actual loaded model name
-            match self.get_available_models().await {
-                Ok(models) if !models.is_empty() => {
-                    println!("[LLM] Using first available model: {}", models[0]);
-                    models[0].clone()
+            let mut streamed = false;
+            let mut attempt = 1;
+            let mut waited = Duration::ZERO;
+            let result = loop {
+                let result = backend.provider.stream(&model, prompt, system_prompt, self.json.as_ref(), &mut |token: &str| {
+                    streamed = true;
+                    on_token(token);
+                }).await;
+
+                match result {
+                    Err(e) if !streamed && self.wait_to_retry(provider, &e, attempt, &mut waited, &on_retry).await => attempt += 1,
+                    result => break result,
                 }
-                _ => {
-                    println!("[LLM] Could not fetch models, using 'local-model'");
-                    "local-model".to_string()
+            };
+
+            match result {
+                Ok(response) => return Ok(Completion { response, provider: provider.clone(), model }),
+                Err(e) if e.is_unavailable() && !streamed => {
+                    println!("[LLM] {} unavailable, trying the next provider: {}", provider, e);
+                    first_error.get_or_insert(e);
                 }
+                Err(e) => return Err(e),
             }
-        };
+        }
+
+        Err(first_error.expect("LlmClient has at least one provider"))
+    }
+
+    /// The models the configured (first) provider offers; fallbacks aren't asked
+    pub async fn list_models(&self) -> Result<Vec<LlmModel>, LlmError> {
+        self.backends[0].provider.list_models().await
+    }
+
+    /// Whether each provider can be reached, the configured one first and
+    /// then the fallbacks in the order they are tried
+    pub async fn check_connections(&self) -> Vec<BackendStatus> {
+        let mut statuses = Vec::with_capacity(self.backends.len());
+        for backend in &self.backends {
+            statuses.push(BackendStatus {
+                provider: backend.config.provider.clone(),
+                model: backend.config.model.clone(),
+                connected: backend.provider.health_check().await.unwrap_or(false),
+            });
+        }
+        statuses
+    }
+}
+
+/// OpenAI's chat completions API, which LM Studio also serves
+pub struct OpenAiCompatible {
+    client: Client,
+    label: &'static str,  // For messages, e.g. "LM Studio"
+    base_url: String,
+    api_key: Option<String>,
+    default_model: Option<&'static str>,  // None: the first model the server lists
+    requires_key: bool,  // Cloud APIs, as opposed to local servers
+    temperature: f32,
+    stop: Vec<String>,
+}
 
+impl OpenAiCompatible {
+    fn new(
+        client: Client,
+        config: &LlmConfig,
+        label: &'static str,
+        default_url: &str,
+        default_model: Option<&'static str>,
+        requires_key: bool,
+    ) -> Self {
+        let base_url = config.base_url.clone()
+            .unwrap_or_else(|| default_url.to_string())
+            .trim_end_matches('/')
+            .to_string();
+
+        // Ensure /v1 suffix for LM Studio compatibility
+        let base_url = if base_url.ends_with("/v1") { base_url } else { format!("{}/v1", base_url) };
+
+        Self {
+            client,
+            label,
+            base_url,
+            api_key: config.api_key.clone(),
+            default_model,
+            requires_key,
+            temperature: config.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            stop: config.stop_sequences.clone(),
+        }
+    }
+
+    fn request(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+        stream: bool,
+    ) -> RequestBuilder {
+        println!("[LLM] Using base_url: {}", self.base_url);
         println!("[LLM] Using model: {}", model);
 
         let mut messages = Vec::new();
@@ -205,56 +976,93 @@ impl LlmClient {
         });
 
         let request = OpenAiRequest {
-            model,
+            model: model.to_string(),
             messages,
-            max_tokens: Some(1024),
-            temperature: Some(0.7),
+            max_tokens: Some(MAX_OUTPUT_TOKENS),
+            temperature: Some(self.temperature),
+            stop: self.stop.clone(),
+            response_format: json.map(|json| serde_json::json!({
+                "type": "json_schema",
+                "json_schema": { "name": json.name, "schema": json.schema, "strict": true },
+            })),
+            stream: stream.then_some(true),
+            // Ask for a final usage chunk; servers that don't support it just omit usage
+            stream_options: stream.then_some(StreamOptions { include_usage: true }),
         };
 
-        let url = format!("{}/chat/completions", base_url);
+        let url = format!("{}/chat/completions", self.base_url);
 
         println!("[LLM] Full request URL: {}", url);
 
-        // Log request body for debugging
-        let request_json = serde_json::to_string_pretty(&request)
-            .unwrap_or_else(|_| "Failed to serialize".to_string());
-        println!("[LLM] Request body:\n{}", request_json);
+        // The body holds the user's verses, notes and prompts, so only its size is logged
+        let body_size = serde_json::to_vec(&request).map_or(0, |body| body.len());
+        println!("[LLM] {} request for {}: {} messages, {} bytes", self.label, model, request.messages.len(), body_size);
 
         let mut req = self.client.post(&url)
             .header("Content-Type", "application/json")
             .json(&request);
 
-        if let Some(api_key) = &self.config.api_key {
+        if let Some(api_key) = &self.api_key {
             req = req.header("Authorization", format!("Bearer {}", api_key));
         }
 
-        println!("[LLM] Sending request to: {}", url);
+        if stream {
+            req = req.timeout(STREAM_TIMEOUT);
+        }
 
-        let response = req.send().await
-            .map_err(|e| format!("Request failed: {}", e))?;
+        req
+    }
+}
 
-        let status = response.status();
-        println!("[LLM] Response status: {}", status);
+#[async_trait]
+impl LlmProvider for OpenAiCompatible {
+    async fn default_model(&self) -> String {
+        if let Some(model) = self.default_model {
+            return model.to_string();
+        }
+
+        // For local servers, use the model that is actually loaded
+        match self.list_models().await {
+            Ok(models) if !models.is_empty() => {
+                println!("[LLM] Using first available model: {}", models[0].name);
+                models[0].name.clone()
+            }
+            _ => {
+                println!("[LLM] Could not fetch models, using 'local-model'");
+                "local-model".to_string()
+            }
+        }
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+    ) -> Result<LlmResponse, LlmError> {
+        let req = self.request(model, prompt, system_prompt, json, false);
+
+        println!("[LLM] Sending request");
+
+        let response = send(req).await?;
+        println!("[LLM] Response status: {}", response.status());
 
         // Get the raw body text first for debugging
         let body_text = response.text().await
-            .map_err(|e| format!("Failed to read response body: {}", e))?;
-
-        println!("[LLM] Response body (first 500 chars): {}", &body_text[..body_text.len().min(500)]);
+            .map_err(LlmError::from_send)?;
 
-        if !status.is_success() {
-            return Err(format!("API error {}: {}", status, body_text));
-        }
+        println!("[LLM] Response body: {} bytes", body_text.len());
 
         // Check if LM Studio returned an error in the body despite 200 status
         // (some versions of LM Studio do this incorrectly)
         if body_text.contains("\"error\"") && !body_text.contains("\"choices\"") {
             println!("[LLM] Detected error in response body despite 200 status");
-            return Err(format!("LLM server error: {}", body_text));
+            return Err(format!("LLM server error: {}", body_text).into());
         }
 
         let result: OpenAiResponse = serde_json::from_str(&body_text)
-            .map_err(|e| format!("Failed to parse response: {} - Body: {}", e, &body_text[..body_text.len().min(200)]))?;
+            .map_err(|e| format!("Failed to parse response: {} - Body: {}", e, truncate(&body_text, 200)))?;
 
         let choice = result.choices.first()
             .ok_or_else(|| "No response generated".to_string())?;
@@ -269,16 +1077,150 @@ impl LlmClient {
             text: choice.message.content.clone(),
             input_tokens: usage.prompt_tokens,
             output_tokens: usage.completion_tokens,
+            stop_reason: choice.finish_reason.clone(),
         })
     }
 
-    async fn call_claude(&self, prompt: &str, system_prompt: Option<&str>) -> Result<LlmResponse, String> {
-        let api_key = self.config.api_key.clone()
-            .ok_or_else(|| "Claude API key required".to_string())?;
+    async fn stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+        on_token: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<LlmResponse, LlmError> {
+        let req = self.request(model, prompt, system_prompt, json, true);
+
+        println!("[LLM] Sending streaming request");
+
+        let response = send(req).await?;
+        println!("[LLM] Response status: {}", response.status());
+
+        let mut result = LlmResponse { text: String::new(), input_tokens: 0, output_tokens: 0, stop_reason: None };
+
+        read_events(response, |event| {
+            if event.data == "[DONE]" {
+                return Ok(());
+            }
+
+            let chunk: OpenAiStreamChunk = serde_json::from_str(&event.data)
+                .map_err(|e| format!("Failed to parse stream chunk: {} - Data: {}", e, truncate(&event.data, 200)))?;
+
+            if let Some(error) = chunk.error {
+                return Err(format!("LLM server error: {}", error));
+            }
+
+            for choice in chunk.choices {
+                if let Some(content) = choice.delta.content.filter(|c| !c.is_empty()) {
+                    on_token(&content);
+                    result.text.push_str(&content);
+                }
+                if choice.finish_reason.is_some() {
+                    result.stop_reason = choice.finish_reason;
+                }
+            }
+
+            if let Some(usage) = chunk.usage {
+                result.input_tokens = usage.prompt_tokens;
+                result.output_tokens = usage.completion_tokens;
+            }
+
+            Ok(())
+        }).await?;
+
+        println!("[LLM] Stream finished: {} chars, {} tokens in, {} out",
+                 result.text.len(), result.input_tokens, result.output_tokens);
+
+        Ok(result)
+    }
+
+    async fn list_models(&self) -> Result<Vec<LlmModel>, LlmError> {
+        let url = format!("{}/models", self.base_url);
+
+        println!("[LLM] Fetching available models from: {}", url);
+
+        let mut req = self.client.get(&url).timeout(Duration::from_secs(10));
+        if let Some(api_key) = &self.api_key {
+            req = req.header("Authorization", format!("Bearer {}", api_key));
+        }
 
-        let model = self.config.model.clone()
-            .unwrap_or_else(|| "claude-3-haiku-20240307".to_string());
+        let response = send(req).await?;
+
+        let body = response.text().await
+            .map_err(LlmError::from_send)?;
+
+        println!("[LLM] Models response: {} bytes", body.len());
+
+        let models_response: ModelsResponse = serde_json::from_str(&body)
+            .map_err(|e| format!("Failed to parse models: {}", e))?;
+
+        Ok(models_response.data.into_iter().map(|m| LlmModel { name: m.id, size: None }).collect())
+    }
+
+    async fn health_check(&self) -> Result<bool, LlmError> {
+        if self.requires_key && self.api_key.is_none() {
+            return Err(LlmError::Auth(format!("{} API key required", self.label)));
+        }
+
+        let url = format!("{}/models", self.base_url);
+        println!("[LLM Check] Testing connection to: {}", url);
+
+        let mut req = self.client.get(&url).timeout(Duration::from_secs(5));
+        if let Some(api_key) = &self.api_key {
+            req = req.header("Authorization", format!("Bearer {}", api_key));
+        }
+
+        let response = req.send()
+            .await
+            .map_err(LlmError::from_send)?;
+
+        let status = response.status();
+        println!("[LLM Check] Response status: {}", status);
+
+        Ok(status.is_success())
+    }
+}
+
+/// Ollama's native API (/api/chat, /api/tags, /api/pull)
+pub struct OllamaProvider {
+    client: Client,
+    base_url: String,
+    keep_alive: Option<String>,
+    num_ctx: Option<u32>,
+    temperature: f32,
+    stop: Vec<String>,
+}
+
+impl OllamaProvider {
+    pub fn new(client: Client, config: &LlmConfig) -> Self {
+        let base_url = config.base_url.clone()
+            .unwrap_or_else(|| "http://localhost:11434".to_string());
+
+        // Accept the OpenAI-compatible URL (.../v1) that older setups use
+        let base_url = base_url.trim_end_matches('/');
+        let base_url = base_url.strip_suffix("/v1")
+            .or_else(|| base_url.strip_suffix("/api"))
+            .unwrap_or(base_url)
+            .to_string();
+
+        Self {
+            client,
+            base_url,
+            keep_alive: config.keep_alive.clone(),
+            num_ctx: config.num_ctx,
+            temperature: config.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            stop: config.stop_sequences.clone(),
+        }
+    }
 
+    fn request(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+        stream: bool,
+    ) -> RequestBuilder {
         let mut messages = Vec::new();
 
         if let Some(sys) = system_prompt {
@@ -293,98 +1235,348 @@ impl LlmClient {
             content: prompt.to_string(),
         });
 
-        let request = ClaudeRequest {
-            model,
-            max_tokens: 1024,
+        let request = OllamaChatRequest {
+            model: model.to_string(),
             messages,
+            stream,
+            keep_alive: self.keep_alive.clone(),
+            format: json.map(|json| json.schema.clone()),
+            options: OllamaOptions {
+                num_predict: MAX_OUTPUT_TOKENS,
+                temperature: self.temperature,
+                num_ctx: self.num_ctx,
+                stop: self.stop.clone(),
+            },
         };
 
-        let response = self.client
-            .post("https://api.anthropic.com/v1/messages")
+        let url = format!("{}/api/chat", self.base_url);
+        println!("[LLM] Ollama request to {} with model {}", url, model);
+
+        let mut req = self.client.post(&url).json(&request);
+
+        if stream {
+            req = req.timeout(STREAM_TIMEOUT);
+        }
+
+        req
+    }
+
+    /// Download a model, calling `on_progress` as /api/pull reports progress
+    pub async fn pull_model(&self, model: &str, mut on_progress: impl FnMut(&PullProgress)) -> Result<(), LlmError> {
+        let response = send(self.client
+            .post(format!("{}/api/pull", self.base_url))
+            .json(&serde_json::json!({ "model": model, "stream": true }))
+            // Large models take a while
+            .timeout(Duration::from_secs(60 * 60))
+        ).await?;
+
+        read_lines(response, |line| {
+            let progress: PullProgress = serde_json::from_str(line)
+                .map_err(|e| format!("Failed to parse pull progress: {}", e))?;
+
+            if let Some(error) = progress.error {
+                return Err(format!("Pull failed: {}", error));
+            }
+
+            on_progress(&progress);
+            Ok(())
+        }).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    async fn default_model(&self) -> String {
+        match self.list_models().await {
+            Ok(models) if !models.is_empty() => {
+                println!("[LLM] Using first installed model: {}", models[0].name);
+                models[0].name.clone()
+            }
+            _ => {
+                println!("[LLM] Could not list Ollama models, using 'llama3.2'");
+                "llama3.2".to_string()
+            }
+        }
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+    ) -> Result<LlmResponse, LlmError> {
+        let response = send(self.request(model, prompt, system_prompt, json, false)).await?;
+
+        let result: OllamaChatResponse = response.json().await
+            .map_err(|e| format!("Failed to parse response: {}", e))?;
+
+        if let Some(error) = result.error {
+            return Err(format!("LLM server error: {}", error).into());
+        }
+
+        Ok(LlmResponse {
+            text: result.message.map(|m| m.content).unwrap_or_default(),
+            input_tokens: result.prompt_eval_count,
+            output_tokens: result.eval_count,
+            stop_reason: result.done_reason,
+        })
+    }
+
+    async fn stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+        on_token: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<LlmResponse, LlmError> {
+        let response = send(self.request(model, prompt, system_prompt, json, true)).await?;
+
+        let mut result = LlmResponse { text: String::new(), input_tokens: 0, output_tokens: 0, stop_reason: None };
+
+        read_lines(response, |line| {
+            let chunk: OllamaChatResponse = serde_json::from_str(line)
+                .map_err(|e| format!("Failed to parse stream chunk: {} - Data: {}", e, truncate(line, 200)))?;
+
+            if let Some(error) = chunk.error {
+                return Err(format!("LLM server error: {}", error));
+            }
+
+            if let Some(content) = chunk.message.map(|m| m.content).filter(|c| !c.is_empty()) {
+                on_token(&content);
+                result.text.push_str(&content);
+            }
+
+            if chunk.done {
+                result.input_tokens = chunk.prompt_eval_count;
+                result.output_tokens = chunk.eval_count;
+                result.stop_reason = chunk.done_reason;
+            }
+
+            Ok(())
+        }).await?;
+
+        Ok(result)
+    }
+
+    async fn list_models(&self) -> Result<Vec<LlmModel>, LlmError> {
+        let response = send(self.client
+            .get(format!("{}/api/tags", self.base_url))
+            .timeout(Duration::from_secs(10))
+        ).await?;
+
+        let tags: OllamaTagsResponse = response.json().await
+            .map_err(|e| format!("Failed to parse models: {}", e))?;
+
+        Ok(tags.models.into_iter().map(|m| LlmModel { name: m.name, size: m.size }).collect())
+    }
+
+    async fn health_check(&self) -> Result<bool, LlmError> {
+        let url = format!("{}/api/tags", self.base_url);
+        println!("[LLM Check] Testing connection to: {}", url);
+
+        let response = self.client.get(&url)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .map_err(LlmError::from_send)?;
+
+        println!("[LLM Check] Response status: {}", response.status());
+        Ok(response.status().is_success())
+    }
+}
+
+const CLAUDE_API_URL: &str = "https://api.anthropic.com";
+
+/// Anthropic's Messages API
+pub struct ClaudeProvider {
+    client: Client,
+    base_url: String,  // Ends in /v1
+    api_key: Option<String>,
+    temperature: f32,
+    stop_sequences: Vec<String>,
+}
+
+impl ClaudeProvider {
+    fn new(client: Client, config: &LlmConfig) -> Self {
+        // A proxy or gateway in front of the API, given with or without /v1
+        let base_url = config.base_url.as_deref()
+            .unwrap_or(CLAUDE_API_URL)
+            .trim_end_matches('/');
+        let base_url = format!("{}/v1", base_url.strip_suffix("/v1").unwrap_or(base_url));
+
+        Self {
+            client,
+            base_url,
+            api_key: config.api_key.clone(),
+            temperature: config.temperature.unwrap_or(DEFAULT_TEMPERATURE),
+            stop_sequences: config.stop_sequences.clone(),
+        }
+    }
+
+    fn api_key(&self) -> Result<&str, LlmError> {
+        self.api_key.as_deref().ok_or_else(|| LlmError::Auth("Claude API key required".to_string()))
+    }
+
+    fn request(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+        stream: bool,
+    ) -> Result<RequestBuilder, LlmError> {
+        let api_key = self.api_key()?;
+
+        let request = ClaudeRequest {
+            model: model.to_string(),
+            max_tokens: MAX_OUTPUT_TOKENS,
+            system: system_prompt.map(str::to_string),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: prompt.to_string(),
+            }],
+            temperature: self.temperature,
+            stop_sequences: self.stop_sequences.clone(),
+            // Claude has no JSON mode; a tool it must call gets the same
+            // result, with the JSON as the tool's input
+            tools: json.map(|json| ClaudeTool {
+                name: json.name.to_string(),
+                description: json.description.to_string(),
+                input_schema: json.schema.clone(),
+            }).into_iter().collect(),
+            tool_choice: json.map(|json| serde_json::json!({ "type": "tool", "name": json.name })),
+            stream: stream.then_some(true),
+        };
+
+        let mut req = self.client
+            .post(format!("{}/messages", self.base_url))
             .header("x-api-key", api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+            .json(&request);
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let text = response.text().await.unwrap_or_default();
-            return Err(format!("API error {}: {}", status, text));
+        if stream {
+            req = req.timeout(STREAM_TIMEOUT);
         }
 
+        Ok(req)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for ClaudeProvider {
+    async fn default_model(&self) -> String {
+        "claude-3-haiku-20240307".to_string()
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+    ) -> Result<LlmResponse, LlmError> {
+        let response = send(self.request(model, prompt, system_prompt, json, false)?).await?;
+
         let result: ClaudeResponse = response.json().await
             .map_err(|e| format!("Failed to parse response: {}", e))?;
 
-        let text = result.content.first()
-            .map(|c| c.text.clone())
-            .unwrap_or_default();
+        // With JSON output the answer is the tool's input; otherwise it may
+        // be split across several text blocks
+        let text = match result.content.iter().find_map(|c| c.input.as_ref()) {
+            Some(input) if json.is_some() => input.to_string(),
+            _ => result.content.into_iter().filter_map(|c| c.text).collect(),
+        };
 
         Ok(LlmResponse {
             text,
             input_tokens: result.usage.input_tokens,
             output_tokens: result.usage.output_tokens,
+            stop_reason: result.stop_reason,
         })
     }
 
-    pub async fn check_connection(&self) -> Result<bool, String> {
-        // Simple health check
-        match self.config.provider.as_str() {
-            "lmstudio" | "ollama" => {
-                let base_url = self.get_base_url();
-                let url = format!("{}/models", base_url);
-                println!("[LLM Check] Testing connection to: {}", url);
-
-                let response = self.client.get(&url)
-                    .timeout(Duration::from_secs(5))
-                    .send()
-                    .await
-                    .map_err(|e| format!("Connection failed: {}", e))?;
-
-                let status = response.status();
-                println!("[LLM Check] Response status: {}", status);
-
-                if status.is_success() {
-                    // Try to log what models are available
-                    if let Ok(body) = response.text().await {
-                        println!("[LLM Check] Available models: {}", &body[..body.len().min(500)]);
-                    }
+    async fn stream(
+        &self,
+        model: &str,
+        prompt: &str,
+        system_prompt: Option<&str>,
+        json: Option<&JsonOutput>,
+        on_token: &mut (dyn for<'t> FnMut(&'t str) + Send),
+    ) -> Result<LlmResponse, LlmError> {
+        let response = send(self.request(model, prompt, system_prompt, json, true)?).await?;
+
+        let mut result = LlmResponse { text: String::new(), input_tokens: 0, output_tokens: 0, stop_reason: None };
+
+        read_events(response, |event| {
+            let event: ClaudeStreamEvent = serde_json::from_str(&event.data)
+                .map_err(|e| format!("Failed to parse stream event: {}", e))?;
+
+            match event {
+                ClaudeStreamEvent::MessageStart { message } => {
+                    result.input_tokens = message.usage.input_tokens;
+                    result.output_tokens = message.usage.output_tokens;
                 }
-
-                Ok(status.is_success())
-            }
-            "openai" => {
-                // For OpenAI, try to list models if API key is set
-                if let Some(api_key) = &self.config.api_key {
-                    let response = self.client
-                        .get("https://api.openai.com/v1/models")
-                        .header("Authorization", format!("Bearer {}", api_key))
-                        .timeout(Duration::from_secs(5))
-                        .send()
-                        .await
-                        .map_err(|e| format!("Connection failed: {}", e))?;
-                    Ok(response.status().is_success())
-                } else {
-                    Err("OpenAI API key required".to_string())
+                ClaudeStreamEvent::ContentBlockDelta { delta } => {
+                    if let Some(text) = delta.text.or(delta.partial_json) {
+                        on_token(&text);
+                        result.text.push_str(&text);
+                    }
                 }
-            }
-            "claude" => {
-                // For Claude, we can't easily test without making a real request
-                // Just check if API key is set
-                if self.config.api_key.is_some() {
-                    Ok(true)
-                } else {
-                    Err("Claude API key required".to_string())
+                // Output tokens are cumulative
+                ClaudeStreamEvent::MessageDelta { delta, usage } => {
+                    result.output_tokens = usage.output_tokens;
+                    result.stop_reason = delta.stop_reason;
                 }
+                ClaudeStreamEvent::Error { error } => return Err(format!("API error: {}", error.message)),
+                ClaudeStreamEvent::Other => {}
             }
-            _ => Ok(false),
-        }
+
+            Ok(())
+        }).await?;
+
+        Ok(result)
+    }
+
+    async fn list_models(&self) -> Result<Vec<LlmModel>, LlmError> {
+        let response = send(self.client
+            .get(format!("{}/models", self.base_url))
+            .header("x-api-key", self.api_key()?)
+            .header("anthropic-version", "2023-06-01")
+            .timeout(Duration::from_secs(10))
+        ).await?;
+
+        let models_response: ModelsResponse = response.json().await
+            .map_err(|e| format!("Failed to parse models: {}", e))?;
+
+        Ok(models_response.data.into_iter().map(|m| LlmModel { name: m.id, size: None }).collect())
+    }
+
+    async fn health_check(&self) -> Result<bool, LlmError> {
+        // We can't easily test without making a real request, so just check
+        // that the API key is set
+        self.api_key().map(|_| true)
     }
 }
 
-// Prompt templates for Bible verse application
+/// Key for the AI response cache: a SHA-256 of everything that determines
+/// the response
+pub fn cache_key(provider: &str, model: &str, system_prompt: Option<&str>, prompt: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [provider, model, system_prompt.unwrap_or_default(), prompt] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The built-in prompts. Apart from the JSON repair prompt they are the
+/// defaults of the editable templates in `templates`, with `{{variable}}`
+/// placeholders.
 pub mod prompts {
     pub const SYSTEM_PROMPT: &str = r#"You are a knowledgeable Bible study assistant helping Christians understand and apply Scripture to their daily lives. Your responses should be:
 - Rooted in biblical truth and context
@@ -393,46 +1585,32 @@ pub mod prompts {
 - Respectful of the KJV translation
 Keep responses concise but meaningful."#;
 
-    pub fn verse_insight_prompt(verse_text: &str, reference: &str) -> String {
-        format!(
-            r#"Provide a brief, practical insight for this Bible verse:
+    pub const VERSE_INSIGHT: &str = r#"Provide a brief, practical insight for this Bible verse:
 
-{} - "{}"
+{{reference}} - "{{verse_text}}"
 
 Include:
 1. What this verse meant in its original context (1-2 sentences)
 2. How it applies to modern life (2-3 sentences)
 3. One key takeaway for today
 
-Keep the total response under 200 words."#,
-            reference, verse_text
-        )
-    }
+Keep the total response under 200 words."#;
 
-    pub fn action_steps_prompt(verse_text: &str, reference: &str, topic: &str) -> String {
-        format!(
-            r#"Generate 3 practical action steps for applying this Bible verse about {}:
+    pub const ACTION_STEPS: &str = r#"Generate 3 practical action steps for applying this Bible verse about {{topic}}:
 
-{} - "{}"
+{{reference}} - "{{verse_text}}"
 
 For each step:
 - Make it specific and doable this week
-- Label difficulty: (easy/medium/challenging)
+- Label difficulty: easy, medium or challenging
 - Keep each step to 1-2 sentences
 
-Format as:
-1. [Easy] Step description
-2. [Medium] Step description
-3. [Challenging] Step description"#,
-            topic, reference, verse_text
-        )
-    }
+Reply with only JSON in this form:
+{"steps": [{"content": "Step description", "difficulty": "easy"}]}"#;
 
-    pub fn reflection_questions_prompt(verse_text: &str, reference: &str) -> String {
-        format!(
-            r#"Generate 4 reflection questions for personal study of this Bible verse:
+    pub const REFLECTION_QUESTIONS: &str = r#"Generate 4 reflection questions for personal study of this Bible verse:
 
-{} - "{}"
+{{reference}} - "{{verse_text}}"
 
 Create one question for each category:
 1. Personal: How does this apply to my inner life?
@@ -440,22 +1618,358 @@ Create one question for each category:
 3. Spiritual: What does this reveal about God or faith?
 4. Practical: What concrete action can I take?
 
-Make questions thought-provoking but not overwhelming."#,
-            reference, verse_text
-        )
-    }
+Make questions thought-provoking but not overwhelming.
 
-    pub fn semantic_search_prompt(query: &str) -> String {
-        format!(
-            r#"A user is searching for Bible verses about: "{}"
+Reply with only JSON in this form, with category one of personal, relational, spiritual or practical:
+{"questions": [{"question": "Question text", "category": "personal"}]}"#;
+
+    pub const SEMANTIC_SEARCH: &str = r#"A user is searching for Bible verses about: "{{query}}"
 
 List 5-10 relevant Bible topics or themes that would match this search, separated by commas. Focus on:
 - The main topic
 - Related emotional/spiritual needs
 - Common life situations this relates to
 
-Just list the topics, nothing else."#,
-            query
+Just list the topics, nothing else."#;
+
+    /// Ask again for JSON after `reply` to `prompt` failed validation with `problem`
+    pub fn json_repair_prompt(prompt: &str, reply: &str, problem: &str) -> String {
+        format!(
+            r#"{}
+
+Your previous reply was:
+{}
+
+It could not be used because {}. Reply again with only the corrected JSON."#,
+            prompt, reply, problem
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn event(event: Option<&str>, data: &str) -> SseEvent {
+        SseEvent { event: event.map(str::to_string), data: data.to_string() }
+    }
+
+    #[test]
+    fn parses_server_sent_events() {
+        let mut parser = SseParser::default();
+        let events = parser.push(b"event: message_start\ndata: {\"a\":1}\n\ndata: [DONE]\n\n");
+        assert_eq!(events, vec![event(Some("message_start"), "{\"a\":1}"), event(None, "[DONE]")]);
+    }
+
+    #[test]
+    fn waits_for_events_split_across_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(b"data: {\"text\":").is_empty());
+        assert!(parser.push(b"\"Grace\"}\n").is_empty());
+        assert_eq!(parser.push(b"\ndata: x"), vec![event(None, "{\"text\":\"Grace\"}")]);
+        assert_eq!(parser.push(b"\n\n"), vec![event(None, "x")]);
+    }
+
+    #[test]
+    fn keeps_characters_split_across_chunks() {
+        let body = "data: Grâce\n\n".as_bytes();
+        let cut = body.iter().position(|&b| b >= 0x80).unwrap() + 1;
+
+        let mut parser = SseParser::default();
+        assert!(parser.push(&body[..cut]).is_empty());
+        assert_eq!(parser.push(&body[cut..]), vec![event(None, "Grâce")]);
+    }
+
+    #[test]
+    fn reads_crlf_line_endings_and_multi_line_data() {
+        let mut parser = SseParser::default();
+        let events = parser.push(b"event:delta\r\ndata:first\r\ndata: second\r\n\r\n");
+        assert_eq!(events, vec![event(Some("delta"), "first\nsecond")]);
+    }
+
+    #[test]
+    fn skips_comments_and_events_without_data() {
+        let mut parser = SseParser::default();
+        let events = parser.push(b": keep-alive\n\nevent: ping\n\nid: 7\ndata: ok\n\n");
+        assert_eq!(events, vec![event(None, "ok")]);
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn parses_openai_reset_durations() {
+        assert_eq!(parse_reset_duration("20ms"), Some(Duration::from_millis(20)));
+        assert_eq!(parse_reset_duration("1s"), Some(Duration::from_secs(1)));
+        assert_eq!(parse_reset_duration("1.5s"), Some(Duration::from_millis(1500)));
+        assert_eq!(parse_reset_duration("6m0s"), Some(Duration::from_secs(360)));
+        assert_eq!(parse_reset_duration("1h2m3s"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse_reset_duration("10"), None);
+        assert_eq!(parse_reset_duration("5d"), None);
+        assert_eq!(parse_reset_duration("soon"), None);
+    }
+
+    #[test]
+    fn truncates_on_character_boundaries() {
+        assert_eq!(truncate("short", 200), "short");
+        assert_eq!(truncate("abcdef", 3), "abc");
+        // "é" takes two bytes and "主" three; a cut inside one backs off before it
+        assert_eq!(truncate("aé", 2), "a");
+        assert_eq!(truncate("主主", 4), "主");
+        assert_eq!(truncate(&"é".repeat(150), 200), "é".repeat(100));
+    }
+
+    #[test]
+    fn reads_retry_after_headers() {
+        let now = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let wait = |pairs: &[(&'static str, &str)]| retry_after(&headers(pairs), now);
+
+        assert_eq!(wait(&[]), None);
+        assert_eq!(wait(&[("retry-after", "7")]), Some(Duration::from_secs(7)));
+        assert_eq!(wait(&[("retry-after", "Wed, 01 May 2024 12:00:30 GMT")]), Some(Duration::from_secs(30)));
+        // A date already past means no wait
+        assert_eq!(wait(&[("retry-after", "Wed, 01 May 2024 11:59:00 GMT")]), Some(Duration::ZERO));
+        assert_eq!(wait(&[("retry-after", "later")]), None);
+        // retry-after-ms is the more precise of the two
+        assert_eq!(wait(&[("retry-after-ms", "250"), ("retry-after", "1")]), Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn waits_for_exhausted_rate_limits_to_reset() {
+        let now = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let wait = |pairs: &[(&'static str, &str)]| retry_after(&headers(pairs), now);
+
+        // OpenAI: only a limit with nothing remaining counts, and the longest wait wins
+        assert_eq!(wait(&[
+            ("x-ratelimit-remaining-requests", "0"), ("x-ratelimit-reset-requests", "2s"),
+            ("x-ratelimit-remaining-tokens", "0"), ("x-ratelimit-reset-tokens", "6m0s"),
+        ]), Some(Duration::from_secs(360)));
+        assert_eq!(wait(&[
+            ("x-ratelimit-remaining-requests", "3"), ("x-ratelimit-reset-requests", "2s"),
+        ]), None);
+
+        // Claude: reset times are RFC 3339 dates
+        assert_eq!(wait(&[
+            ("anthropic-ratelimit-requests-remaining", "0"), ("anthropic-ratelimit-requests-reset", "2024-05-01T12:00:05Z"),
+            ("anthropic-ratelimit-output-tokens-remaining", "0"), ("anthropic-ratelimit-output-tokens-reset", "2024-05-01T12:00:20Z"),
+        ]), Some(Duration::from_secs(20)));
+        assert_eq!(wait(&[
+            ("anthropic-ratelimit-tokens-remaining", "1000"), ("anthropic-ratelimit-tokens-reset", "2024-05-01T12:00:20Z"),
+        ]), None);
+    }
+
+    #[test]
+    fn retries_within_the_policy() {
+        let policy = RetryPolicy::default();
+        let rate_limited = |retry_after| LlmError::RateLimited { status: 429, message: String::new(), retry_after };
+
+        // The server's wait is used as given
+        let error = rate_limited(Some(Duration::from_secs(5)));
+        assert_eq!(policy.delay(&error, 1, Duration::ZERO), Some(Duration::from_secs(5)));
+        // ...unless it would go past the total wait, or attempts run out
+        assert_eq!(policy.delay(&error, 1, Duration::from_secs(58)), None);
+        assert_eq!(policy.delay(&error, policy.max_attempts, Duration::ZERO), None);
+
+        // Otherwise half to all of the doubled base delay
+        let delay = policy.delay(&rate_limited(None), 3, Duration::ZERO).unwrap();
+        assert!(delay >= Duration::from_secs(2) && delay <= Duration::from_secs(4), "{:?}", delay);
+
+        assert_eq!(policy.delay(&LlmError::Auth("bad key".to_string()), 1, Duration::ZERO), None);
+    }
+
+    /// Read one HTTP request: the head and a body of its content-length
+    async fn read_request(socket: &mut TcpStream) -> String {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap();
+            data.extend_from_slice(&buf[..n]);
+            let request = String::from_utf8_lossy(&data).to_string();
+            if let Some(head) = request.find("\r\n\r\n") {
+                let length = request[..head].lines()
+                    .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                    .unwrap_or(0);
+                if n == 0 || data.len() >= head + 4 + length {
+                    return request;
+                }
+            }
+            if n == 0 {
+                return request;
+            }
+        }
+    }
+
+    /// A local Ollama: /api/tags answers with two models, and /api/chat and
+    /// /api/pull stream NDJSON in chunks that split lines and characters.
+    /// Returns its URL and the requests it has received.
+    async fn mock_ollama() -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let received = requests.clone();
+        tokio::spawn(async move {
+            // An unparsable line over 200 bytes whose 200th byte falls inside a character
+            let garbled = format!("x{}\n", "\u{e9}".repeat(150));
+
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let request = read_request(&mut socket).await;
+                received.lock().unwrap().push(request.clone());
+
+                let (content_type, chunks): (&str, Vec<&str>) = match request.lines().next().unwrap_or_default() {
+                    line if line.starts_with("POST /api/chat") && request.contains("\"garbled\"") => ("application/x-ndjson", vec![
+                        garbled.as_str(),
+                    ]),
+                    line if line.starts_with("GET /api/tags") => ("application/json", vec![
+                        r#"{"models":[{"name":"mistral:latest","size":4109865159,"details":{"family":"llama"}},{"name":"llama3.2:latest"}]}"#,
+                    ]),
+                    line if line.starts_with("POST /api/chat") => ("application/x-ndjson", vec![
+                        "{\"message\":{\"role\":\"assistant\",\"content\":\"Be \"},\"done\":false}\n{\"message\":{\"role\":\"assistant\",\"content\":\"st",
+                        "ill\"},\"done\":false}\n{\"message\":{\"role\":\"assistant\",\"content\":\" and know \\u00e9\"},\"done\":false}\n",
+                        "{\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true,\"done_reason\":\"stop\",\"prompt_eval_count\":12,\"eval_count\":4}",
+                    ]),
+                    line if line.starts_with("POST /api/pull") && request.contains("\"missing\"") => ("application/x-ndjson", vec![
+                        "{\"status\":\"pulling manifest\"}\n{\"error\":\"pull model manifest: file does not exist\"}\n",
+                    ]),
+                    line if line.starts_with("POST /api/pull") => ("application/x-ndjson", vec![
+                        "{\"status\":\"pulling manifest\"}\n{\"status\":\"downloading\",\"total\":100,",
+                        "\"completed\":50}\n{\"status\":\"success\"}\n",
+                    ]),
+                    _ => ("text/plain", vec!["not found"]),
+                };
+
+                socket.write_all(format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: {}\r\ntransfer-encoding: chunked\r\nconnection: close\r\n\r\n",
+                    content_type,
+                ).as_bytes()).await.unwrap();
+                for chunk in chunks {
+                    socket.write_all(format!("{:x}\r\n{}\r\n", chunk.len(), chunk).as_bytes()).await.unwrap();
+                    socket.flush().await.unwrap();
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+                socket.write_all(b"0\r\n\r\n").await.unwrap();
+            }
+        });
+
+        (url, requests)
+    }
+
+    fn ollama_config(url: &str) -> LlmConfig {
+        LlmConfig {
+            provider: "ollama".to_string(),
+            // The OpenAI-compatible URL older setups use still works
+            base_url: Some(format!("{}/v1/", url)),
+            keep_alive: Some("10m".to_string()),
+            num_ctx: Some(8192),
+            ..LlmConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn lists_installed_ollama_models() {
+        let (url, _) = mock_ollama().await;
+        let client = LlmClient::new(ollama_config(&url)).unwrap();
+
+        assert!(client.check_connections().await[0].connected);
+        let models = client.list_models().await.unwrap();
+        let models: Vec<(&str, Option<u64>)> = models.iter().map(|m| (m.name.as_str(), m.size)).collect();
+        assert_eq!(models, vec![("mistral:latest", Some(4109865159)), ("llama3.2:latest", None)]);
+
+        // Without a configured model, the first installed one is used
+        assert_eq!(client.backends[0].model().await, "mistral:latest");
+    }
+
+    #[tokio::test]
+    async fn checks_every_provider() {
+        let (url, _) = mock_ollama().await;
+        let unreachable = LlmConfig {
+            provider: "lmstudio".to_string(),
+            base_url: Some("http://127.0.0.1:9".to_string()),
+            model: Some("local-model".to_string()),
+            ..LlmConfig::default()
+        };
+        let client = LlmClient::new(unreachable.clone()).unwrap()
+            .with_fallback(ollama_config(&url)).unwrap();
+
+        let statuses: Vec<(String, Option<String>, bool)> = client.check_connections().await.into_iter()
+            .map(|s| (s.provider, s.model, s.connected))
+            .collect();
+        assert_eq!(statuses, vec![
+            ("lmstudio".to_string(), Some("local-model".to_string()), false),
+            ("ollama".to_string(), None, true),
+        ]);
+
+        // Cache keys use the models from the settings, without asking the servers
+        assert_eq!(client.configured_model("lmstudio"), Some("local-model"));
+        assert_eq!(client.configured_model("ollama"), None);
+        assert_eq!(client.configured_model("claude"), None);
+    }
+
+    #[tokio::test]
+    async fn streams_ollama_chat() {
+        let (url, requests) = mock_ollama().await;
+        let client = LlmClient::new(ollama_config(&url)).unwrap();
+
+        let mut tokens = Vec::new();
+        let completion = client.generate_stream("Psalm 46:10", Some("Be brief"), |_, _| Ok(()), |_| {}, |token| tokens.push(token.to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(tokens, vec!["Be ", "still", " and know \u{e9}"]);
+        let response = completion.response;
+        assert_eq!(response.text, "Be still and know \u{e9}");
+        assert_eq!((response.input_tokens, response.output_tokens), (12, 4));
+        assert_eq!(completion.model, "mistral:latest");
+
+        let requests = requests.lock().unwrap();
+        let chat = requests.iter().find(|r| r.starts_with("POST /api/chat")).unwrap();
+        let body: serde_json::Value = serde_json::from_str(&chat[chat.find("\r\n\r\n").unwrap() + 4..]).unwrap();
+        assert_eq!(body["model"], "mistral:latest");
+        assert_eq!(body["stream"], true);
+        assert_eq!(body["keep_alive"], "10m");
+        assert_eq!(body["options"]["num_ctx"], 8192);
+        assert_eq!(body["messages"][0]["content"], "Be brief");
+        assert_eq!(body["messages"][1]["content"], "Psalm 46:10");
+    }
+
+    #[tokio::test]
+    async fn reports_unparsable_ollama_lines() {
+        let (url, _) = mock_ollama().await;
+        let config = LlmConfig { model: Some("garbled".to_string()), ..ollama_config(&url) };
+        let client = LlmClient::new(config).unwrap();
+
+        let error = client.generate_stream("Psalm 46:10", None, |_, _| Ok(()), |_| {}, |_| {})
+            .await
+            .unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("Failed to parse stream chunk"), "{}", message);
+        assert!(message.ends_with(&format!("Data: x{}", "\u{e9}".repeat(99))), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn pulls_ollama_models() {
+        let (url, requests) = mock_ollama().await;
+        let ollama = OllamaProvider::new(Client::new(), &ollama_config(&url));
+
+        let mut progress = Vec::new();
+        ollama.pull_model("mistral", |p| progress.push((p.status.clone(), p.completed, p.total))).await.unwrap();
+        assert_eq!(progress, vec![
+            ("pulling manifest".to_string(), None, None),
+            ("downloading".to_string(), Some(50), Some(100)),
+            ("success".to_string(), None, None),
+        ]);
+        assert!(requests.lock().unwrap().iter().any(|r| r.contains("\"model\":\"mistral\"")));
+
+        let error = ollama.pull_model("missing", |_| {}).await.unwrap_err();
+        assert!(error.to_string().contains("Pull failed: pull model manifest: file does not exist"), "{}", error);
+    }
+}
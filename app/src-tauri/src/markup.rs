@@ -0,0 +1,210 @@
+// Inline verse markup: KJV italics (words supplied by the translators) and
+// red letters (words of Christ).
+//
+// Markup is the verse text with <i>..</i> around supplied words and
+// <j>..</j> around words of Christ; '&', '<' and '>' in the text itself are
+// escaped as &amp;, &lt; and &gt;. It is stored next to the plain text,
+// which stays what search indexes.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Added,
+    WordsOfChrist,
+}
+
+impl Style {
+    fn tag(self) -> &'static str {
+        match self {
+            Self::Added => "i",
+            Self::WordsOfChrist => "j",
+        }
+    }
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// The plain text of a markup string
+pub fn plain_text(markup: &str) -> String {
+    let mut text = String::with_capacity(markup.len());
+    let mut rest = markup;
+
+    while let Some(pos) = rest.find('<') {
+        text.push_str(&rest[..pos]);
+        rest = rest[pos..].find('>').map_or("", |end| &rest[pos + end + 1..]);
+    }
+    text.push_str(rest);
+
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&")
+}
+
+/// Collects the markup of one verse at a time. Styles may stay open across
+/// verses (a quotation of Christ running over several verses), so they are
+/// closed at the end of each verse and reopened at the start of the next.
+#[derive(Debug, Default)]
+pub struct MarkupBuffer {
+    open: Vec<Style>,
+    markup: String,
+}
+
+impl MarkupBuffer {
+    /// Start a new verse, discarding anything collected since the last one
+    pub fn begin(&mut self) {
+        self.markup.clear();
+        for style in &self.open {
+            self.markup.push('<');
+            self.markup.push_str(style.tag());
+            self.markup.push('>');
+        }
+    }
+
+    pub fn push_text(&mut self, text: &str) {
+        self.markup.push_str(&escape(text));
+    }
+
+    pub fn open(&mut self, style: Style) {
+        self.open.push(style);
+        self.markup.push('<');
+        self.markup.push_str(style.tag());
+        self.markup.push('>');
+    }
+
+    /// Close a style, keeping the tags properly nested if others were opened
+    /// inside it. Closing a style that is not open does nothing.
+    pub fn close(&mut self, style: Style) {
+        let Some(pos) = self.open.iter().rposition(|&s| s == style) else { return };
+        let inner = self.open.split_off(pos + 1);
+        self.open.pop();
+
+        for s in inner.iter().rev().chain([&style]) {
+            self.markup.push_str("</");
+            self.markup.push_str(s.tag());
+            self.markup.push('>');
+        }
+        for &s in &inner {
+            self.open(s);
+        }
+    }
+
+    /// Finish the verse, returning its plain text (whitespace collapsed) and
+    /// its markup, or None when it has no styled words
+    pub fn finish(&mut self) -> (String, Option<String>) {
+        let mut markup = std::mem::take(&mut self.markup);
+        for style in self.open.iter().rev() {
+            markup.push_str("</");
+            markup.push_str(style.tag());
+            markup.push('>');
+        }
+
+        let text = collapse(&plain_text(&markup));
+        // Keep spaces outside the tags, and drop the empty pair left by a
+        // style reopened at the very end of a verse
+        let mut markup = collapse(&markup);
+        for tag in ["i", "j"] {
+            markup = markup
+                .replace(&format!("<{}> ", tag), &format!(" <{}>", tag))
+                .replace(&format!(" </{}>", tag), &format!("</{}> ", tag))
+                .replace(&format!("<{}></{}>", tag, tag), "");
+        }
+        let markup = collapse(&markup);
+
+        (text, markup.contains('<').then_some(markup))
+    }
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_supplied_words_and_words_of_christ() {
+        let mut buffer = MarkupBuffer::default();
+        buffer.begin();
+        buffer.push_text("And he said unto them, ");
+        buffer.open(Style::WordsOfChrist);
+        buffer.push_text("Follow me, and I will make you ");
+        buffer.open(Style::Added);
+        buffer.push_text("to be");
+        buffer.close(Style::Added);
+        buffer.push_text(" fishers of men.");
+        buffer.close(Style::WordsOfChrist);
+
+        let (text, markup) = buffer.finish();
+        assert_eq!(text, "And he said unto them, Follow me, and I will make you to be fishers of men.");
+        assert_eq!(markup.as_deref(), Some("And he said unto them, <j>Follow me, and I will make you <i>to be</i> fishers of men.</j>"));
+    }
+
+    #[test]
+    fn returns_no_markup_for_a_plain_verse() {
+        let mut buffer = MarkupBuffer::default();
+        buffer.begin();
+        buffer.push_text("  In the beginning\n God created ");
+        assert_eq!(buffer.finish(), ("In the beginning God created".to_string(), None));
+
+        // A style opened and closed around nothing leaves no tags
+        buffer.begin();
+        buffer.push_text("Jesus wept.");
+        buffer.open(Style::Added);
+        buffer.close(Style::Added);
+        assert_eq!(buffer.finish(), ("Jesus wept.".to_string(), None));
+    }
+
+    #[test]
+    fn keeps_overlapping_styles_nested() {
+        let mut buffer = MarkupBuffer::default();
+        buffer.begin();
+        buffer.open(Style::WordsOfChrist);
+        buffer.push_text("I am ");
+        buffer.open(Style::Added);
+        buffer.push_text("he");
+        buffer.close(Style::WordsOfChrist);
+        buffer.push_text(" that");
+        buffer.close(Style::Added);
+        // Closing a style that isn't open does nothing
+        buffer.close(Style::WordsOfChrist);
+
+        let (text, markup) = buffer.finish();
+        assert_eq!(text, "I am he that");
+        assert_eq!(markup.as_deref(), Some("<j>I am <i>he</i></j> <i>that</i>"));
+    }
+
+    #[test]
+    fn carries_open_styles_into_the_next_verse() {
+        let mut buffer = MarkupBuffer::default();
+        buffer.begin();
+        buffer.push_text("He said, ");
+        buffer.open(Style::WordsOfChrist);
+        buffer.push_text("Blessed ");
+        assert_eq!(buffer.finish().1.as_deref(), Some("He said, <j>Blessed</j>"));
+
+        buffer.begin();
+        buffer.push_text("are the poor");
+        buffer.close(Style::WordsOfChrist);
+        buffer.push_text(".");
+        assert_eq!(buffer.finish().1.as_deref(), Some("<j>are the poor</j>."));
+
+        // The quotation is closed, so nothing carries over
+        buffer.begin();
+        buffer.push_text("And he went up.");
+        assert_eq!(buffer.finish().1, None);
+    }
+
+    #[test]
+    fn escapes_text() {
+        let mut buffer = MarkupBuffer::default();
+        buffer.begin();
+        buffer.open(Style::Added);
+        buffer.push_text("<this> & that");
+        buffer.close(Style::Added);
+
+        let (text, markup) = buffer.finish();
+        assert_eq!(text, "<this> & that");
+        assert_eq!(markup.as_deref(), Some("<i>&lt;this&gt; &amp; that</i>"));
+        assert_eq!(plain_text(markup.as_deref().unwrap()), text);
+    }
+}
@@ -12,14 +12,27 @@ pub struct Book {
     pub sort_order: i64,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Translation {
+    pub id: i64,
+    pub code: String,
+    pub name: String,
+    pub language: String,
+    pub year: Option<i64>,
+    pub verse_count: i64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Verse {
     pub id: i64,
     pub book_id: i64,
     pub chapter: i64,
-    pub verse: i64,
+    pub verse: i64,              // 0 for a Psalm title
+    pub end_verse: Option<i64>,  // Last verse covered when this one is merged with the next
     pub text: String,
+    pub markup: Option<String>,  // Text with <i> (supplied words) and <j> (words of Christ) tags
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,6 +45,132 @@ pub struct VerseWithBook {
     pub text: String,
     pub book_name: String,
     pub book_abbreviation: String,
+    pub markup: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Passage {
+    pub reference: String,
+    pub book_id: i64,
+    pub start_chapter: i64,
+    pub start_verse: Option<i64>,
+    pub end_chapter: i64,
+    pub end_verse: Option<i64>,
+    pub verses: Vec<VerseWithBook>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerseLocation {
+    pub book_id: i64,
+    pub chapter: i64,
+    pub verse: i64,  // 0 for a Psalm title in KJV numbering
+}
+
+// Parallel Models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParallelPassage {
+    pub reference: String,
+    pub book_id: i64,
+    pub translations: Vec<String>,  // Translation codes, in the order of each row's cells
+    pub rows: Vec<ParallelRow>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParallelRow {
+    pub chapter: i64,
+    pub verse: i64,
+    pub cells: Vec<ParallelCell>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParallelCell {
+    // "present", "merged" (text is in verse merged_into), "versification"
+    // (numbered differently: a Psalm title or a verse past the chapter end),
+    // or "missing" (omitted by the translation)
+    pub status: String,
+    pub verse_id: Option<i64>,
+    pub text: Option<String>,
+    pub end_verse: Option<i64>,
+    pub merged_into: Option<i64>,
+}
+
+// Import Models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub translation_id: i64,
+    pub code: String,
+    pub name: String,
+    pub format: String,
+    pub imported: i64,
+    pub unmapped: Vec<UnmappedVerse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnmappedVerse {
+    pub reference: String,  // As written in the source, e.g. "TOB 1:1"
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrossReferenceImportReport {
+    pub imported: i64,
+    pub skipped: i64,       // Links to verses the KJV doesn't have
+}
+
+// Search Models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub id: i64,
+    pub book_id: i64,
+    pub chapter: i64,
+    pub verse: i64,
+    pub text: String,
+    pub book_name: String,
+    pub book_abbreviation: String,
+    pub snippet: String,  // Verse text with <mark> around matched terms
+    pub score: f64,       // BM25 relevance, higher is better
+}
+
+// A verse found by AI search, with what it matched
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticMatch {
+    pub id: i64,
+    pub book_id: i64,
+    pub chapter: i64,
+    pub verse: i64,
+    pub text: String,
+    pub book_name: String,
+    pub book_abbreviation: String,
+    pub snippet: String,       // Verse text, with <mark> around the words of a matched theme
+    pub score: f64,            // Sum over its matches, higher is better
+    pub themes: Vec<String>,   // The themes it matched
+    pub topics: Vec<String>,   // Names of the matched topics it is in
+    pub explanation: String,   // Why it matched, for display
+}
+
+// Cross Reference Models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrossReference {
+    pub id: i64,
+    pub from_verse_id: i64,
+    pub to_verse_id: i64,
+    pub to_verse_end_id: Option<i64>,
+    pub votes: i64,
+    pub source: String,     // "openbible" (imported) or "user"
+    pub note: Option<String>,
+    pub reference: String,  // The linked passage: the target, or the source for reverse lookups
+    pub text: String,       // Text of the first verse of the linked passage
 }
 
 // Topic Models
@@ -71,6 +210,8 @@ pub struct ActionStep {
     pub step_number: i64,
     pub content: String,
     pub difficulty: String,
+    pub source: String,  // "curated", "ai" or "user"
+    pub model: Option<String>,  // The model that generated an "ai" step
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -80,6 +221,8 @@ pub struct ReflectionQuestion {
     pub verse_id: i64,
     pub question: String,
     pub category: String,
+    pub source: String,  // "curated", "ai" or "user"
+    pub model: Option<String>,  // The model that generated an "ai" question
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -129,8 +272,30 @@ pub struct Settings {
     pub llm_base_url: Option<String>,
     pub llm_model: Option<String>,
     pub llm_api_key: Option<String>,  // API key for OpenAI/Claude
+    pub llm_fallbacks: Vec<LlmFallback>,  // Tried in order when the provider above is unavailable
+    pub llm_temperature: Option<f32>,  // None: the default, 0.7
+    pub llm_stop_sequences: Vec<String>,  // Text that ends a completion early
+    pub ollama_keep_alive: Option<String>,  // How long Ollama keeps the model loaded, e.g. "10m"
+    pub ollama_num_ctx: Option<u32>,  // Ollama context window size
     pub daily_verse_enabled: bool,
     pub daily_verse_time: String,
+    pub ai_cache_hours: i64,  // How long AI responses are reused; 0 disables the cache
+    // Token and dollar (USD) caps on AI usage; None means no cap
+    pub quota_daily_tokens: Option<i64>,
+    pub quota_monthly_tokens: Option<i64>,
+    pub quota_daily_cost: Option<f64>,
+    pub quota_monthly_cost: Option<f64>,
+    pub quota_local_providers: bool,  // Also cap lmstudio/ollama, which are exempt by default
+}
+
+/// A provider to use when the configured one is unavailable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LlmFallback {
+    pub provider: String,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub api_key: Option<String>,
 }
 
 impl Default for Settings {
@@ -144,8 +309,53 @@ impl Default for Settings {
             llm_base_url: Some("http://localhost:1234/v1".to_string()),
             llm_model: None,
             llm_api_key: None,
+            llm_fallbacks: Vec::new(),
+            llm_temperature: None,
+            llm_stop_sequences: Vec::new(),
+            ollama_keep_alive: None,
+            ollama_num_ctx: None,
             daily_verse_enabled: true,
             daily_verse_time: "07:00".to_string(),
+            ai_cache_hours: 24 * 7,
+            quota_daily_tokens: None,
+            quota_monthly_tokens: None,
+            quota_daily_cost: None,
+            quota_monthly_cost: None,
+            quota_local_providers: false,
         }
     }
 }
+
+// Token Usage Models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsageTotal {
+    pub group: String,  // Day (YYYY-MM-DD), month (YYYY-MM), provider or model
+    pub requests: i64,
+    pub cached_requests: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+    pub estimated_cost: f64,  // USD, from model_prices
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelPrice {
+    pub provider: String,
+    pub model: String,  // Prefix of the model names this price applies to
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+// Prompt Template Models
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptTemplate {
+    pub name: String,
+    pub description: String,
+    pub variables: Vec<String>,  // Placeholders the body may use, as {{name}}
+    pub required_variables: Vec<String>,  // Placeholders the body must use
+    pub body: String,            // The body in use
+    pub default_body: String,
+    pub customized: bool,        // Whether `body` is an edited one
+}
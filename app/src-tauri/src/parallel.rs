@@ -0,0 +1,206 @@
+// Verse-by-verse comparison of a passage across translations
+
+use std::collections::{BTreeSet, HashMap};
+use crate::db::Database;
+use crate::error::AppError;
+use crate::models::*;
+use crate::reference::{self, BookIndex, VerseRef};
+
+/// Align the verses of several translations row by row. Every verse number
+/// found in any translation gets a row; translations without it are marked
+/// merged, versification or missing rather than left out.
+pub fn align(columns: &[Vec<Verse>]) -> Vec<ParallelRow> {
+    // Verses covered by a merge get their own row too
+    let keys: BTreeSet<(i64, i64)> = columns.iter()
+        .flatten()
+        .flat_map(|v| (v.verse..=v.end_verse.unwrap_or(v.verse)).map(move |n| (v.chapter, n)))
+        .collect();
+
+    let lookups: Vec<HashMap<(i64, i64), &Verse>> = columns.iter()
+        .map(|verses| verses.iter().map(|v| ((v.chapter, v.verse), v)).collect())
+        .collect();
+
+    // Last verse number of each chapter, per translation
+    let chapter_ends: Vec<HashMap<i64, i64>> = columns.iter()
+        .map(|verses| {
+            let mut ends = HashMap::new();
+            for v in verses {
+                let end = ends.entry(v.chapter).or_insert(0);
+                *end = (*end).max(v.end_verse.unwrap_or(v.verse));
+            }
+            ends
+        })
+        .collect();
+
+    keys.into_iter()
+        .map(|(chapter, verse)| {
+            let cells = lookups.iter().zip(&chapter_ends).zip(columns)
+                .map(|((lookup, ends), verses)| {
+                    if let Some(v) = lookup.get(&(chapter, verse)) {
+                        return ParallelCell {
+                            status: "present".to_string(),
+                            verse_id: Some(v.id),
+                            text: Some(v.text.clone()),
+                            end_verse: v.end_verse,
+                            merged_into: None,
+                        };
+                    }
+
+                    let merged = verses.iter().find(|v| {
+                        v.chapter == chapter && v.verse < verse && v.end_verse.is_some_and(|end| end >= verse)
+                    });
+                    let status = match (merged, ends.get(&chapter)) {
+                        (Some(_), _) => "merged",
+                        (None, Some(&end)) if verse == 0 || verse > end => "versification",
+                        _ => "missing",
+                    };
+
+                    ParallelCell {
+                        status: status.to_string(),
+                        verse_id: merged.map(|v| v.id),
+                        text: None,
+                        end_verse: None,
+                        merged_into: merged.map(|v| v.verse),
+                    }
+                })
+                .collect();
+
+            ParallelRow { chapter, verse, cells }
+        })
+        .collect()
+}
+
+/// Parse a chapter or passage reference and align it across translations
+/// (given as resolved (id, code) pairs, in display order)
+pub fn compare_passages(
+    db: &Database,
+    input: &str,
+    translations: &[(i64, String)],
+) -> Result<Vec<ParallelPassage>, AppError> {
+    let index = BookIndex::new(db.get_books()?);
+    let refs = reference::parse_passages(input, &index).map_err(AppError::InvalidReference)?;
+
+    let mut passages = Vec::with_capacity(refs.len());
+    for p in refs {
+        let start = VerseRef { book_id: p.book_id, chapter: p.start_chapter, verse: p.start_verse };
+        let end = VerseRef { book_id: p.book_id, chapter: p.end_chapter, verse: p.end_verse };
+        reference::validate(db, &index, &start)?;
+        reference::validate(db, &index, &end)?;
+
+        let book = index.get(p.book_id)
+            .ok_or_else(|| AppError::InvalidReference(format!("Unknown book id: {}", p.book_id)))?;
+
+        // A passage from the start of a chapter includes its Psalm title
+        let mut columns = Vec::with_capacity(translations.len());
+        for (translation_id, _) in translations {
+            columns.push(db.get_verse_range(
+                p.book_id,
+                p.start_chapter,
+                p.start_verse.unwrap_or(0),
+                p.end_chapter,
+                p.end_verse.unwrap_or(i64::MAX),
+                *translation_id,
+            )?);
+        }
+
+        passages.push(ParallelPassage {
+            reference: p.label(book),
+            book_id: p.book_id,
+            translations: translations.iter().map(|(_, code)| code.clone()).collect(),
+            rows: align(&columns),
+        });
+    }
+
+    Ok(passages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verse(id: i64, chapter: i64, verse: i64, end_verse: Option<i64>) -> Verse {
+        Verse {
+            id,
+            book_id: 1,
+            chapter,
+            verse,
+            end_verse,
+            text: format!("{}:{}", chapter, verse),
+            markup: None,
+        }
+    }
+
+    fn statuses(rows: &[ParallelRow]) -> Vec<(i64, i64, Vec<&str>)> {
+        rows.iter()
+            .map(|r| (r.chapter, r.verse, r.cells.iter().map(|c| c.status.as_str()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn aligns_present_verses() {
+        let rows = align(&[
+            vec![verse(1, 1, 1, None), verse(2, 1, 2, None)],
+            vec![verse(11, 1, 1, None), verse(12, 1, 2, None)],
+        ]);
+        assert_eq!(statuses(&rows), vec![(1, 1, vec!["present", "present"]), (1, 2, vec!["present", "present"])]);
+
+        let cell = &rows[1].cells[1];
+        assert_eq!(cell.verse_id, Some(12));
+        assert_eq!(cell.text.as_deref(), Some("1:2"));
+        assert_eq!(cell.merged_into, None);
+    }
+
+    #[test]
+    fn points_merged_verses_at_the_bridge() {
+        // The second translation bridges verses 4-5 into one
+        let rows = align(&[
+            vec![verse(1, 1, 3, None), verse(2, 1, 4, None), verse(3, 1, 5, None)],
+            vec![verse(11, 1, 3, None), verse(12, 1, 4, Some(5))],
+        ]);
+        assert_eq!(statuses(&rows), vec![
+            (1, 3, vec!["present", "present"]),
+            (1, 4, vec!["present", "present"]),
+            (1, 5, vec!["present", "merged"]),
+        ]);
+        assert_eq!(rows[1].cells[1].end_verse, Some(5));
+
+        let merged = &rows[2].cells[1];
+        assert_eq!(merged.verse_id, Some(12));
+        assert_eq!(merged.merged_into, Some(4));
+        assert_eq!(merged.text, None);
+    }
+
+    #[test]
+    fn marks_versification_differences() {
+        // A Psalm title numbered as verse 0 in one translation only
+        let rows = align(&[
+            vec![verse(1, 3, 1, None), verse(2, 3, 2, None)],
+            vec![verse(10, 3, 0, None), verse(11, 3, 1, None), verse(12, 3, 2, None)],
+        ]);
+        assert_eq!(statuses(&rows)[0], (3, 0, vec!["versification", "present"]));
+
+        // 3 John 1:15 is part of verse 14 in the KJV, so it runs past the KJV's chapter end
+        let rows = align(&[
+            vec![verse(1, 1, 13, None), verse(2, 1, 14, None)],
+            vec![verse(11, 1, 13, None), verse(12, 1, 14, None), verse(13, 1, 15, None)],
+        ]);
+        let last = &rows[2];
+        assert_eq!((last.verse, last.cells[0].status.as_str()), (15, "versification"));
+        assert_eq!(last.cells[0].verse_id, None);
+    }
+
+    #[test]
+    fn marks_omitted_verses_missing() {
+        // Verse 21 omitted within the chapter, as some translations do with Matthew 17:21
+        let rows = align(&[
+            vec![verse(1, 17, 20, None), verse(2, 17, 21, None), verse(3, 17, 22, None)],
+            vec![verse(11, 17, 20, None), verse(12, 17, 22, None)],
+        ]);
+        assert_eq!(statuses(&rows)[1], (17, 21, vec!["present", "missing"]));
+        assert_eq!(rows[1].cells[1].verse_id, None);
+
+        // A translation with nothing in the chapter doesn't know where it ends
+        let rows = align(&[vec![verse(1, 17, 20, None)], vec![]]);
+        assert_eq!(statuses(&rows), vec![(17, 20, vec!["present", "missing"])]);
+    }
+}
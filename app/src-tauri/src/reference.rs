@@ -0,0 +1,685 @@
+use std::collections::HashMap;
+use crate::db::{Database, KJV_TRANSLATION_ID};
+use crate::error::AppError;
+use crate::models::*;
+use crate::versification::{self, Scheme};
+
+// Alternate names and abbreviations not stored in `books`, keyed by book id
+// (book ids follow the canonical order used by seed::seed_books)
+const BOOK_ALIASES: &[(i64, &[&str])] = &[
+    (1, &["Gn", "Ge"]),
+    (2, &["Ex", "Exo"]),
+    (3, &["Lv", "Le"]),
+    (4, &["Nm", "Nu"]),
+    (5, &["Dt", "De"]),
+    (6, &["Jos", "Jsh"]),
+    (7, &["Jdg", "Jg", "Jdgs"]),
+    (8, &["Rth", "Ru"]),
+    (9, &["1 Sa", "1 Sm", "1 Kingdoms"]),
+    (10, &["2 Sa", "2 Sm", "2 Kingdoms"]),
+    (11, &["1 Ki", "1 Kgs", "1 Kin", "3 Kingdoms"]),
+    (12, &["2 Ki", "2 Kgs", "2 Kin", "4 Kingdoms"]),
+    (13, &["1 Ch", "1 Chron", "1 Paralipomenon"]),
+    (14, &["2 Ch", "2 Chron", "2 Paralipomenon"]),
+    (15, &["Ezr"]),
+    (16, &["Ne"]),
+    (17, &["Est", "Es"]),
+    (18, &["Jb"]),
+    (19, &["Psalm", "Psa", "Psm", "Pss"]),
+    (20, &["Pr", "Prv", "Pro"]),
+    (21, &["Ecc", "Ec", "Qoh", "Qoheleth"]),
+    (22, &["So", "Sos", "Song of Songs", "Canticles", "Canticle of Canticles", "Cant"]),
+    (23, &["Is"]),
+    (24, &["Je", "Jr"]),
+    (25, &["La"]),
+    (26, &["Eze", "Ezk"]),
+    (27, &["Da", "Dn"]),
+    (28, &["Ho"]),
+    (29, &["Jl", "Joe"]),
+    (30, &["Am"]),
+    (31, &["Ob", "Oba"]),
+    (32, &["Jon", "Jnh"]),
+    (33, &["Mc"]),
+    (34, &["Na"]),
+    (35, &["Hb"]),
+    (36, &["Zep", "Zp"]),
+    (37, &["Hg"]),
+    (38, &["Zec", "Zc"]),
+    (39, &["Ml"]),
+    (40, &["Mt", "Mat"]),
+    (41, &["Mk", "Mr", "Mar", "Mrk"]),
+    (42, &["Lk", "Lu", "Luk"]),
+    (43, &["Jn", "Jhn", "Joh"]),
+    (44, &["Ac", "Act"]),
+    (45, &["Ro", "Rm"]),
+    (46, &["1 Co"]),
+    (47, &["2 Co"]),
+    (48, &["Ga"]),
+    (49, &["Ephes"]),
+    (50, &["Php", "Pp", "Philip"]),
+    (51, &["Co"]),
+    (52, &["1 Th", "1 Thes", "1 Thessalonian"]),
+    (53, &["2 Th", "2 Thes", "2 Thessalonian"]),
+    (54, &["1 Ti", "1 Tm"]),
+    (55, &["2 Ti", "2 Tm"]),
+    (56, &["Tit"]),
+    (57, &["Philem", "Phm", "Pm"]),
+    (58, &["He"]),
+    (59, &["Jm", "Ja", "Jam"]),
+    (60, &["1 Pe", "1 Pt", "1 P"]),
+    (61, &["2 Pe", "2 Pt", "2 P"]),
+    (62, &["1 Jn", "1 Jhn", "1 Jo", "1 J"]),
+    (63, &["2 Jn", "2 Jhn", "2 Jo", "2 J"]),
+    (64, &["3 Jn", "3 Jhn", "3 Jo", "3 J"]),
+    (65, &["Jud", "Jd"]),
+    (66, &["Re", "Rv", "Revelations", "Apocalypse", "Apoc"]),
+];
+
+/// A parsed reference to a chapter or a single verse
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerseRef {
+    pub book_id: i64,
+    pub chapter: i64,
+    pub verse: Option<i64>,
+}
+
+/// Lookup table from every accepted book spelling to its `books` row
+pub struct BookIndex {
+    books: Vec<Book>,
+    aliases: HashMap<String, usize>,
+}
+
+impl BookIndex {
+    pub fn new(books: Vec<Book>) -> Self {
+        let mut aliases = HashMap::new();
+
+        for (i, book) in books.iter().enumerate() {
+            aliases.insert(book_key(&book.name), i);
+            aliases.insert(book_key(&book.abbreviation), i);
+        }
+
+        for (book_id, names) in BOOK_ALIASES {
+            if let Some(i) = books.iter().position(|b| b.id == *book_id) {
+                for name in names.iter() {
+                    aliases.entry(book_key(name)).or_insert(i);
+                }
+            }
+        }
+
+        Self { books, aliases }
+    }
+
+    pub fn get(&self, book_id: i64) -> Option<&Book> {
+        self.books.iter().find(|b| b.id == book_id)
+    }
+
+    /// Resolve a book name, abbreviation or unambiguous prefix of the full name
+    pub fn resolve(&self, name: &str) -> Option<&Book> {
+        let key = book_key(name);
+        if key.is_empty() {
+            return None;
+        }
+
+        if let Some(&i) = self.aliases.get(&key) {
+            return Some(&self.books[i]);
+        }
+
+        // Fall back to a unique prefix of the full name ("Gene", "Revel")
+        if key.len() >= 3 {
+            let mut matches = self.books.iter().filter(|b| book_key(&b.name).starts_with(&key));
+            if let (Some(book), None) = (matches.next(), matches.next()) {
+                return Some(book);
+            }
+        }
+
+        None
+    }
+}
+
+/// Normalize a book name for lookup: ordinal prefixes become digits,
+/// case, spaces and periods are ignored ("II Kings" -> "2kings")
+fn book_key(name: &str) -> String {
+    let (ordinal, rest) = split_ordinal(name.trim());
+    let mut key = ordinal.map(|n| n.to_string()).unwrap_or_default();
+    key.extend(
+        rest.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(|c| c.to_lowercase()),
+    );
+    key
+}
+
+/// Split a leading book ordinal ("1", "II", "First", "3rd") from the rest of a name
+fn split_ordinal(s: &str) -> (Option<u8>, &str) {
+    const ORDINALS: &[(&str, u8)] = &[
+        ("first", 1), ("second", 2), ("third", 3),
+        ("1st", 1), ("2nd", 2), ("3rd", 3),
+        ("iii", 3), ("ii", 2), ("i", 1),
+        ("1", 1), ("2", 2), ("3", 3),
+    ];
+
+    let lower = s.to_ascii_lowercase();
+    for (prefix, n) in ORDINALS {
+        if !lower.starts_with(prefix) {
+            continue;
+        }
+        let rest = &s[prefix.len()..];
+        let next = rest.chars().next();
+        let is_digit = prefix.chars().all(|c| c.is_ascii_digit());
+        // Words and numerals must be followed by a separator ("I John", not "Isaiah");
+        // digits may run straight into the name ("1Cor")
+        let separated = matches!(next, Some(' ') | Some('.') | Some('_'));
+        if separated || (is_digit && next.is_some_and(|c| c.is_alphabetic())) {
+            return (Some(*n), rest.trim_start_matches(['.', ' ', '_']));
+        }
+    }
+
+    (None, s)
+}
+
+/// Split a reference into its book name and the numeric remainder ("Jn3.16" -> ("Jn", "3.16"))
+pub fn split_book(input: &str) -> Result<(&str, &str), String> {
+    let input = input.trim();
+    let (_, after_ordinal) = split_ordinal(input);
+    let name_start = input.len() - after_ordinal.len();
+
+    let name_end = after_ordinal
+        .char_indices()
+        .find(|(_, c)| c.is_ascii_digit())
+        .map(|(i, _)| name_start + i)
+        .unwrap_or(input.len());
+
+    let book = input[..name_end].trim_end_matches([' ', '.']);
+    if book.is_empty() || !after_ordinal.starts_with(|c: char| c.is_alphabetic()) {
+        return Err(format!("Missing book name in reference: '{}'", input));
+    }
+
+    Ok((book, input[name_end..].trim()))
+}
+
+/// A chapter or verse number: digits only, so "-1" and "+2" are rejected
+fn parse_number(s: &str) -> Option<i64> {
+    s.bytes().all(|b| b.is_ascii_digit()).then(|| s.parse().ok()).flatten()
+}
+
+/// Parse "chapter", "chapter:verse", "chapter.verse" or "chapter verse"
+fn parse_chapter_verse(s: &str) -> Option<(i64, Option<i64>)> {
+    let mut parts = s
+        .split(|c: char| c == ':' || c == '.' || c.is_whitespace())
+        .filter(|p| !p.is_empty());
+
+    let chapter = parse_number(parts.next()?)?;
+    let verse = match parts.next() {
+        Some(v) => Some(parse_number(v)?),
+        None => None,
+    };
+
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((chapter, verse))
+}
+
+/// Parse a single-verse or whole-chapter reference such as "John 3:16",
+/// "1 Jn 4:8", "II Kings 2:11", "Jn3.16" or "john 3 16"
+pub fn parse_reference(input: &str, index: &BookIndex) -> Result<VerseRef, String> {
+    let (book_name, numbers) = split_book(input)?;
+
+    let book = index.resolve(book_name)
+        .ok_or_else(|| format!("Unknown book: '{}'", book_name))?;
+
+    let (mut chapter, mut verse) = parse_chapter_verse(numbers)
+        .ok_or_else(|| format!("Invalid chapter or verse in reference: '{}'", input.trim()))?;
+
+    // Single-chapter books are usually cited by verse alone ("Jude 3")
+    if book.chapter_count == 1 && verse.is_none() {
+        verse = Some(chapter);
+        chapter = 1;
+    }
+
+    Ok(VerseRef {
+        book_id: book.id,
+        chapter,
+        verse,
+    })
+}
+
+/// Check that a parsed reference points at an existing chapter and verse
+pub fn validate(db: &Database, index: &BookIndex, r: &VerseRef) -> Result<(), AppError> {
+    let book = index.get(r.book_id)
+        .ok_or_else(|| AppError::InvalidReference(format!("Unknown book id: {}", r.book_id)))?;
+
+    if r.chapter < 1 || r.chapter > book.chapter_count {
+        return Err(AppError::InvalidReference(format!(
+            "{} has {} chapter{}; chapter {} is out of range",
+            book.name,
+            book.chapter_count,
+            if book.chapter_count == 1 { "" } else { "s" },
+            r.chapter
+        )));
+    }
+
+    if let Some(verse) = r.verse {
+        let verse_count = db.get_verse_count(r.book_id, r.chapter)?;
+        if verse < 1 || verse > verse_count {
+            return Err(AppError::InvalidReference(format!(
+                "{} {} has {} verses; verse {} is out of range",
+                book.name, r.chapter, verse_count, verse
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a reference and look up the verse it points to. A chapter
+/// reference without a verse resolves to the first verse of the chapter.
+pub fn lookup_verse(db: &Database, input: &str, translation_id: i64, scheme: Scheme) -> Result<VerseWithBook, AppError> {
+    let index = BookIndex::new(db.get_books()?);
+    let mut r = parse_reference(input, &index).map_err(AppError::InvalidReference)?;
+    (r.chapter, r.verse) = to_kjv(r.book_id, r.chapter, r.verse, scheme);
+    validate(db, &index, &r)?;
+
+    let verse_id = db.find_verse_id(r.book_id, r.chapter, r.verse.unwrap_or(1))?
+        .ok_or_else(|| AppError::NotFound(format!("Verse not found: {}", input.trim())))?;
+
+    Ok(db.get_verse(verse_id, translation_id)?)
+}
+
+/// A contiguous span of verses within one book. A `None` start verse means
+/// "from the start of the chapter"; a `None` end verse means "to the end".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassageRef {
+    pub book_id: i64,
+    pub start_chapter: i64,
+    pub start_verse: Option<i64>,
+    pub end_chapter: i64,
+    pub end_verse: Option<i64>,
+}
+
+impl PassageRef {
+    /// Canonical label such as "Romans 8:28-39", "Genesis 1:26-2:3" or "Luke 11"
+    pub fn label(&self, book: &Book) -> String {
+        let start = match self.start_verse {
+            Some(v) if book.chapter_count == 1 => v.to_string(),
+            Some(v) => format!("{}:{}", self.start_chapter, v),
+            None => self.start_chapter.to_string(),
+        };
+
+        let end = match (self.end_verse, self.start_verse) {
+            (None, None) if self.end_chapter == self.start_chapter => None,
+            (None, _) => Some(self.end_chapter.to_string()),
+            (Some(v), Some(sv)) if self.end_chapter == self.start_chapter => {
+                if v == sv { None } else { Some(v.to_string()) }
+            }
+            (Some(v), _) => Some(format!("{}:{}", self.end_chapter, v)),
+        };
+
+        match end {
+            Some(end) => format!("{} {}-{}", book.name, start, end),
+            None => format!("{} {}", book.name, start),
+        }
+    }
+}
+
+/// One side of a range: "3:16" names both chapter and verse, a bare "16" is
+/// read as a chapter or a verse depending on what precedes it
+#[derive(Debug, Clone, Copy)]
+enum Endpoint {
+    Full(i64, i64),
+    Bare(i64),
+}
+
+fn parse_endpoint(s: &str) -> Option<Endpoint> {
+    match parse_chapter_verse(s)? {
+        (chapter, Some(verse)) => Some(Endpoint::Full(chapter, verse)),
+        (n, None) => Some(Endpoint::Bare(n)),
+    }
+}
+
+/// Parse a compound reference such as "Matt 5:3-12, 6:9-13; Luke 11" or
+/// "Gen 1:26-2:3" into one passage per segment, in the order given.
+///
+/// A book name on its own means the whole book. Segments without a book
+/// name continue the previous book. After a comma a
+/// bare number continues the current chapter's verses ("John 3:16, 18");
+/// after a semicolon it starts a new chapter ("Rom 8:28; 12").
+pub fn parse_passages(input: &str, index: &BookIndex) -> Result<Vec<PassageRef>, String> {
+    let mut passages = Vec::new();
+    let mut book: Option<&Book> = None;
+    let mut chapter: Option<i64> = None;
+    let mut verse_level = false;
+    let mut separator = ';';
+    let mut rest = input;
+
+    loop {
+        let end = rest.find([',', ';']).unwrap_or(rest.len());
+        let segment = rest[..end].trim();
+
+        // A book name alone ("Jude", "3 John") is the whole book
+        let whole_book = segment.ends_with(|c: char| c.is_alphabetic())
+            .then(|| index.resolve(segment))
+            .flatten();
+
+        if let Some(b) = whole_book {
+            passages.push(PassageRef {
+                book_id: b.id,
+                start_chapter: 1,
+                start_verse: None,
+                end_chapter: b.chapter_count,
+                end_verse: None,
+            });
+            book = Some(b);
+            chapter = Some(b.chapter_count);
+            verse_level = false;
+        } else if !segment.is_empty() {
+            let numbers = match split_book(segment) {
+                Ok((name, numbers)) => {
+                    let b = index.resolve(name)
+                        .ok_or_else(|| format!("Unknown book: '{}'", name))?;
+                    book = Some(b);
+                    chapter = None;
+                    verse_level = false;
+                    numbers
+                }
+                Err(_) => segment,
+            };
+
+            let b = book.ok_or_else(|| format!("Missing book name in reference: '{}'", segment))?;
+            let invalid = || format!("Invalid passage reference: '{}'", segment);
+
+            let (left, right) = match numbers.split_once(['-', '–', '—']) {
+                Some((l, r)) => (l, Some(r)),
+                None => (numbers, None),
+            };
+
+            let continues_verses = separator == ',' && verse_level && chapter.is_some();
+            let (start_chapter, start_verse) = match parse_endpoint(left).ok_or_else(invalid)? {
+                Endpoint::Full(c, v) => (c, Some(v)),
+                Endpoint::Bare(n) if b.chapter_count == 1 => (1, Some(n)),
+                Endpoint::Bare(n) if continues_verses => (chapter.unwrap_or(1), Some(n)),
+                Endpoint::Bare(n) => (n, None),
+            };
+
+            let (end_chapter, end_verse) = match right {
+                None => (start_chapter, start_verse),
+                Some(r) => match parse_endpoint(r).ok_or_else(invalid)? {
+                    Endpoint::Full(c, v) => (c, Some(v)),
+                    Endpoint::Bare(n) if start_verse.is_some() => (start_chapter, Some(n)),
+                    Endpoint::Bare(n) => (n, None),
+                },
+            };
+
+            let starts_after_end = (start_chapter, start_verse.unwrap_or(1))
+                > (end_chapter, end_verse.unwrap_or(i64::MAX));
+            if starts_after_end {
+                return Err(format!("Passage ends before it starts: '{}'", segment));
+            }
+
+            passages.push(PassageRef {
+                book_id: b.id,
+                start_chapter,
+                start_verse,
+                end_chapter,
+                end_verse,
+            });
+
+            chapter = Some(end_chapter);
+            verse_level = end_verse.is_some();
+        }
+
+        match rest[end..].chars().next() {
+            Some(c) => {
+                separator = c;
+                rest = &rest[end + c.len_utf8()..];
+            }
+            None => break,
+        }
+    }
+
+    if passages.is_empty() {
+        return Err(format!("No passages found in reference: '{}'", input.trim()));
+    }
+
+    Ok(passages)
+}
+
+/// Renumber a chapter and optional verse cited in another versification
+/// scheme to the KJV numbering the database uses. A whole chapter follows
+/// where its first verse lands.
+fn to_kjv(book_id: i64, chapter: i64, verse: Option<i64>, scheme: Scheme) -> (i64, Option<i64>) {
+    let (kjv_chapter, kjv_verse) = versification::convert(book_id, chapter, verse.unwrap_or(1), scheme, Scheme::Kjv);
+    (kjv_chapter, verse.map(|_| kjv_verse))
+}
+
+/// Parse a compound reference, cited in the given versification scheme, and
+/// load the verses of every segment
+pub fn lookup_passages(db: &Database, input: &str, translation_id: i64, scheme: Scheme) -> Result<Vec<Passage>, AppError> {
+    let index = BookIndex::new(db.get_books()?);
+    let refs = parse_passages(input, &index).map_err(AppError::InvalidReference)?;
+
+    let mut passages = Vec::with_capacity(refs.len());
+    for mut p in refs {
+        (p.start_chapter, p.start_verse) = to_kjv(p.book_id, p.start_chapter, p.start_verse, scheme);
+        (p.end_chapter, p.end_verse) = to_kjv(p.book_id, p.end_chapter, p.end_verse, scheme);
+
+        let start = VerseRef { book_id: p.book_id, chapter: p.start_chapter, verse: p.start_verse };
+        let end = VerseRef { book_id: p.book_id, chapter: p.end_chapter, verse: p.end_verse };
+        validate(db, &index, &start)?;
+        validate(db, &index, &end)?;
+
+        let book = index.get(p.book_id)
+            .ok_or_else(|| AppError::InvalidReference(format!("Unknown book id: {}", p.book_id)))?;
+
+        let verses = db.get_passage_verses(
+            p.book_id,
+            p.start_chapter,
+            p.start_verse.unwrap_or(1),
+            p.end_chapter,
+            p.end_verse.unwrap_or(i64::MAX),
+            translation_id,
+        )?;
+
+        passages.push(Passage {
+            reference: p.label(book),
+            book_id: p.book_id,
+            start_chapter: p.start_chapter,
+            start_verse: p.start_verse,
+            end_chapter: p.end_chapter,
+            end_verse: p.end_verse,
+            verses,
+        });
+    }
+
+    Ok(passages)
+}
+
+/// Look up a reference that must name exactly one passage ("Heb 11:3", "Ps 33:6-9"),
+/// returning KJV verses
+pub fn lookup_single_passage(db: &Database, input: &str) -> Result<Passage, AppError> {
+    let mut passages = lookup_passages(db, input, KJV_TRANSLATION_ID, Scheme::Kjv)?;
+    if passages.len() > 1 {
+        return Err(AppError::InvalidReference(format!("Expected a single passage: '{}'", input.trim())));
+    }
+    Ok(passages.remove(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    fn index() -> BookIndex {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE books (id INTEGER PRIMARY KEY, name TEXT NOT NULL UNIQUE, abbreviation TEXT NOT NULL,
+                                 testament TEXT NOT NULL, chapter_count INTEGER NOT NULL, sort_order INTEGER NOT NULL)"
+        ).unwrap();
+        crate::seed::seed_books(&conn).unwrap();
+
+        let mut stmt = conn.prepare("SELECT id, name, abbreviation, testament, chapter_count, sort_order FROM books").unwrap();
+        let books = stmt.query_map([], |row| {
+            Ok(Book {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                abbreviation: row.get(2)?,
+                testament: row.get(3)?,
+                chapter_count: row.get(4)?,
+                sort_order: row.get(5)?,
+            })
+        }).unwrap().collect::<rusqlite::Result<Vec<_>>>().unwrap();
+        BookIndex::new(books)
+    }
+
+    fn verse(book_id: i64, chapter: i64, verse: Option<i64>) -> VerseRef {
+        VerseRef { book_id, chapter, verse }
+    }
+
+    #[test]
+    fn splits_ordinals() {
+        assert_eq!(split_ordinal("1 John"), (Some(1), "John"));
+        assert_eq!(split_ordinal("I Jn"), (Some(1), "Jn"));
+        assert_eq!(split_ordinal("First John"), (Some(1), "John"));
+        assert_eq!(split_ordinal("II Kings"), (Some(2), "Kings"));
+        assert_eq!(split_ordinal("3rd John"), (Some(3), "John"));
+        assert_eq!(split_ordinal("1Cor"), (Some(1), "Cor"));
+        // A roman numeral must be followed by a separator
+        assert_eq!(split_ordinal("Isaiah"), (None, "Isaiah"));
+        assert_eq!(split_ordinal("Iii"), (None, "Iii"));
+    }
+
+    #[test]
+    fn resolves_books() {
+        let index = index();
+        let id = |name: &str| index.resolve(name).map(|b| b.id);
+
+        for name in ["1 John", "I Jn", "First John", "1Jn", "1 john", "1st John"] {
+            assert_eq!(id(name), Some(62), "{}", name);
+        }
+        assert_eq!(id("II Kings"), Some(12));
+        assert_eq!(id("Ps"), Some(19));
+        assert_eq!(id("Psalm"), Some(19));
+
+        // A unique prefix of the full name resolves
+        assert_eq!(id("Gene"), Some(1));
+        assert_eq!(id("Revel"), Some(66));
+        assert_eq!(id("1 Thess"), Some(52));
+
+        // An ambiguous or too short prefix doesn't
+        assert_eq!(id("Phi"), None);
+        assert_eq!(id("Jo"), None);
+        assert_eq!(id("Hezekiah"), None);
+        assert_eq!(id(""), None);
+    }
+
+    #[test]
+    fn parses_references() {
+        let index = index();
+        let parse = |input: &str| parse_reference(input, &index);
+
+        assert_eq!(parse("John 3:16"), Ok(verse(43, 3, Some(16))));
+        assert_eq!(parse("Jn3.16"), Ok(verse(43, 3, Some(16))));
+        assert_eq!(parse("john 3 16"), Ok(verse(43, 3, Some(16))));
+        assert_eq!(parse("1 Jn 4:8"), Ok(verse(62, 4, Some(8))));
+        assert_eq!(parse("II Kings 2:11"), Ok(verse(12, 2, Some(11))));
+
+        // Chapter only
+        assert_eq!(parse("Ps 23"), Ok(verse(19, 23, None)));
+
+        // Single-chapter books are cited by verse
+        assert_eq!(parse("Jude 3"), Ok(verse(65, 1, Some(3))));
+        assert_eq!(parse("Philemon 1:6"), Ok(verse(57, 1, Some(6))));
+    }
+
+    #[test]
+    fn rejects_invalid_references() {
+        let index = index();
+        let parse = |input: &str| parse_reference(input, &index);
+
+        assert!(parse("John 3:x").unwrap_err().contains("Invalid chapter or verse"));
+        assert!(parse("John 3:16:2").is_err());
+        assert!(parse("John 3:-1").is_err());
+        assert!(parse("John").is_err());
+        assert!(parse("3:16").unwrap_err().contains("Missing book name"));
+        assert!(parse("Hezekiah 1:1").unwrap_err().contains("Unknown book"));
+
+        // A range is a passage, not a single verse
+        assert!(parse("John 3:16-18").is_err());
+    }
+
+    #[test]
+    fn validates_verse_numbers() {
+        let db = Database::new(std::path::Path::new(":memory:")).unwrap();
+        let index = index();
+        let check = |input: &str| validate(&db, &index, &parse_reference(input, &index).unwrap());
+
+        assert!(check("John 3:36").is_ok());
+        assert!(check("John 3:0").is_err());
+        assert!(check("John 3:37").unwrap_err().to_string().contains("has 36 verses"));
+        assert!(check("John 22").unwrap_err().to_string().contains("has 21 chapters"));
+        assert!(check("Jude 26").is_err());
+    }
+
+    #[test]
+    fn parses_verse_ranges() {
+        let index = index();
+        let passages = parse_passages("John 3:16-18", &index).unwrap();
+        assert_eq!(passages, vec![PassageRef { book_id: 43, start_chapter: 3, start_verse: Some(16), end_chapter: 3, end_verse: Some(18) }]);
+        assert_eq!(passages[0].label(index.get(43).unwrap()), "John 3:16-18");
+    }
+
+    fn passage(book_id: i64, start: (i64, Option<i64>), end: (i64, Option<i64>)) -> PassageRef {
+        PassageRef { book_id, start_chapter: start.0, start_verse: start.1, end_chapter: end.0, end_verse: end.1 }
+    }
+
+    #[test]
+    fn parses_passage_lists() {
+        let index = index();
+        let parse = |input: &str| parse_passages(input, &index).unwrap();
+
+        // A comma continues the verse list of the current chapter
+        assert_eq!(parse("John 3:16, 18"), vec![
+            passage(43, (3, Some(16)), (3, Some(16))),
+            passage(43, (3, Some(18)), (3, Some(18))),
+        ]);
+
+        // A semicolon starts a new chapter
+        assert_eq!(parse("Rom 8:28; 12"), vec![
+            passage(45, (8, Some(28)), (8, Some(28))),
+            passage(45, (12, None), (12, None)),
+        ]);
+
+        // After a whole chapter a comma also lists chapters
+        assert_eq!(parse("Ps 23, 91"), vec![
+            passage(19, (23, None), (23, None)),
+            passage(19, (91, None), (91, None)),
+        ]);
+
+        assert_eq!(parse("Matt 5:3-12, 6:9-13; Luke 11"), vec![
+            passage(40, (5, Some(3)), (5, Some(12))),
+            passage(40, (6, Some(9)), (6, Some(13))),
+            passage(42, (11, None), (11, None)),
+        ]);
+    }
+
+    #[test]
+    fn parses_passage_ranges() {
+        let index = index();
+        let parse = |input: &str| parse_passages(input, &index);
+
+        let across_chapters = parse("Gen 1:26-2:3").unwrap();
+        assert_eq!(across_chapters, vec![passage(1, (1, Some(26)), (2, Some(3)))]);
+        assert_eq!(across_chapters[0].label(index.get(1).unwrap()), "Genesis 1:26-2:3");
+
+        assert_eq!(parse("Ps 1-3").unwrap(), vec![passage(19, (1, None), (3, None))]);
+        assert_eq!(parse("Jude").unwrap(), vec![passage(65, (1, None), (1, None))]);
+        assert_eq!(parse("Jude 3-5").unwrap(), vec![passage(65, (1, Some(3)), (1, Some(5)))]);
+        assert_eq!(parse("Rom 8:28–30").unwrap(), vec![passage(45, (8, Some(28)), (8, Some(30)))]);
+
+        assert!(parse("John 3:18-16").unwrap_err().contains("ends before it starts"));
+        assert!(parse("3:16").unwrap_err().contains("Missing book name"));
+        assert!(parse("John 3:x-4").unwrap_err().contains("Invalid passage"));
+        assert!(parse(" ; ").unwrap_err().contains("No passages"));
+    }
+}
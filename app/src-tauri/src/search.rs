@@ -0,0 +1,381 @@
+// Full-text search: KJV word normalization and FTS5 query building
+
+// Archaic KJV words and spellings with their modern equivalents
+const ARCHAIC_WORDS: &[(&str, &str)] = &[
+    ("thee", "you"), ("thou", "you"), ("ye", "you"),
+    ("thy", "your"), ("thine", "your"), ("thyself", "yourself"),
+    ("art", "are"), ("wast", "was"), ("wert", "were"),
+    ("hath", "has"), ("hast", "have"), ("hadst", "had"),
+    ("doth", "does"), ("dost", "do"), ("didst", "did"), ("doeth", "does"), ("doest", "do"),
+    ("shalt", "shall"), ("wilt", "will"), ("canst", "can"),
+    ("couldest", "could"), ("wouldest", "would"), ("shouldest", "should"),
+    ("mayest", "may"), ("mightest", "might"), ("needest", "need"),
+    ("saith", "says"), ("sayest", "say"), ("spake", "spoke"),
+    ("shew", "show"), ("shewed", "showed"), ("shewn", "shown"),
+    ("shewest", "show"), ("sheweth", "shows"), ("shewing", "showing"),
+    ("seeth", "sees"), ("seest", "see"), ("goeth", "goes"), ("goest", "go"),
+    ("fleeth", "flees"), ("fleest", "flee"),
+    ("unto", "to"), ("whither", "where"), ("hither", "here"), ("thither", "there"),
+    ("wherefore", "why"), ("nay", "no"), ("yea", "yes"), ("ere", "before"),
+    ("naught", "nothing"), ("aught", "anything"),
+    ("brake", "broke"), ("sware", "swore"), ("gat", "got"), ("begat", "begot"),
+    ("digged", "dug"), ("builded", "built"), ("holpen", "helped"),
+    ("stablish", "establish"), ("stablished", "established"),
+];
+
+// Words ending in -eth/-est that are not archaic verb forms. Shorter words
+// ("chest", "guest") leave a stem too short to be changed anyway.
+const NOT_VERB_FORMS: &[&str] = &[
+    "teeth", "beneath", "forest", "honest", "dishonest", "harvest", "interest",
+    "manifest", "modest", "tempest", "earnest", "behest", "conquest", "request",
+    "arrest", "contest", "protest", "invest", "infest", "molest", "attest",
+    "detest", "digest", "suggest", "unrest", "inquest", "priest",
+];
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u')
+}
+
+/// Rebuild the base verb from a stem left after removing -eth/-est
+/// ("lov" -> "love", "sitt" -> "sit", "mak" -> "make", "know" -> "know")
+fn restore_stem(stem: &str) -> String {
+    let chars: Vec<char> = stem.chars().collect();
+    let n = chars.len();
+    let last = chars[n - 1];
+
+    if n >= 2 && last == chars[n - 2] && !is_vowel(last) && !matches!(last, 'l' | 's' | 'f' | 'z') {
+        return chars[..n - 1].iter().collect();
+    }
+
+    // A single-vowel consonant-vowel-consonant stem takes a silent e,
+    // as does any stem ending in v (no English word ends in a bare v)
+    let vowel_groups = chars
+        .iter()
+        .zip(std::iter::once(&' ').chain(chars.iter()))
+        .filter(|(c, prev)| is_vowel(**c) && !is_vowel(**prev))
+        .count();
+    let cvc = n >= 3
+        && !is_vowel(last)
+        && !matches!(last, 'w' | 'x' | 'y')
+        && is_vowel(chars[n - 2])
+        && !is_vowel(chars[n - 3]);
+
+    if last == 'v' || (cvc && vowel_groups == 1) {
+        format!("{}e", stem)
+    } else {
+        stem.to_string()
+    }
+}
+
+/// Map one KJV word to its modern lemma ("loveth" -> "love", "shew" -> "show").
+/// Always returns a single lower-case word so token positions are preserved.
+pub fn normalize_word(word: &str) -> String {
+    let lower = word.to_lowercase();
+
+    if let Some((_, modern)) = ARCHAIC_WORDS.iter().find(|(archaic, _)| *archaic == lower) {
+        return modern.to_string();
+    }
+
+    if !lower.is_ascii() || NOT_VERB_FORMS.contains(&lower.as_str()) {
+        return lower;
+    }
+
+    if let Some(stem) = lower.strip_suffix("edst") {
+        return format!("{}ed", stem);
+    }
+
+    for suffix in ["eth", "est"] {
+        if let Some(stem) = lower.strip_suffix(suffix) {
+            if stem.len() >= 3 {
+                return restore_stem(stem);
+            }
+        }
+    }
+
+    lower
+}
+
+/// Normalize every word of a text while keeping all punctuation and spacing,
+/// so the n-th token of the result corresponds to the n-th token of the input
+pub fn normalize_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word = String::new();
+
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            word.push(c);
+        } else {
+            if !word.is_empty() {
+                out.push_str(&normalize_word(&word));
+                word.clear();
+            }
+            out.push(c);
+        }
+    }
+    if !word.is_empty() {
+        out.push_str(&normalize_word(&word));
+    }
+
+    out
+}
+
+/// Copy highlights made on normalized text back onto the original wording.
+/// `marked` is the normalized text with matches wrapped in \u{1}...\u{2};
+/// the result wraps the same tokens of `original` in <mark> tags.
+pub fn transfer_highlights(original: &str, marked: &str) -> String {
+    let mut highlighted_tokens = Vec::new();
+    let mut inside = false;
+    let mut in_token = false;
+
+    for c in marked.chars() {
+        match c {
+            '\u{1}' => inside = true,
+            '\u{2}' => inside = false,
+            c if c.is_alphanumeric() => {
+                if !in_token {
+                    highlighted_tokens.push(inside);
+                    in_token = true;
+                }
+            }
+            _ => in_token = false,
+        }
+    }
+
+    let mut out = String::with_capacity(original.len() + 32);
+    let mut token = 0;
+    let mut open = false;
+    in_token = false;
+
+    for c in original.chars() {
+        if c.is_alphanumeric() {
+            if !open && highlighted_tokens.get(token).copied().unwrap_or(false) {
+                out.push_str("<mark>");
+                open = true;
+            }
+            in_token = true;
+            out.push(c);
+        } else {
+            if open {
+                out.push_str("</mark>");
+                open = false;
+            }
+            if in_token {
+                token += 1;
+                in_token = false;
+            }
+            out.push(c);
+        }
+    }
+    if open {
+        out.push_str("</mark>");
+    }
+
+    out
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Term(String, bool),    // word or phrase, prefix flag
+    Operator(&'static str),
+    Open,
+    Close,
+}
+
+/// Split user input into terms, quoted phrases, AND/OR/NOT and parentheses
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::Open);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::Close);
+            }
+            '"' => {
+                chars.next();
+                let phrase: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                let prefix = chars.next_if_eq(&'*').is_some();
+                tokens.push(Token::Term(phrase, prefix));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, '"' | '(' | ')')) {
+                    word.push(c);
+                }
+
+                match word.as_str() {
+                    "AND" => tokens.push(Token::Operator("AND")),
+                    "OR" => tokens.push(Token::Operator("OR")),
+                    "NOT" => tokens.push(Token::Operator("NOT")),
+                    _ => {
+                        let prefix = word.ends_with('*');
+                        let term = word.trim_end_matches('*').to_string();
+                        tokens.push(Token::Term(term, prefix));
+                    }
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+/// A search query checked and built into an FTS5 MATCH expression for one
+/// of the two verse indexes
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchQuery {
+    pub expr: String,
+    pub exact: bool,  // For verses_fts as written rather than verses_fts_normalized
+}
+
+impl MatchQuery {
+    /// Returns `None` if nothing searchable remains
+    pub fn parse(input: &str, exact: bool) -> Result<Option<Self>, String> {
+        Ok(build_match_query(input, !exact)?.map(|expr| Self { expr, exact }))
+    }
+}
+
+/// Build a safe FTS5 MATCH expression from free-form user input.
+///
+/// Supports `"exact phrases"`, `prefix*`, upper-case `AND`/`OR`/`NOT` and
+/// parentheses; adjacent terms are joined with an explicit AND, which FTS5
+/// requires next to a parenthesised group. Every term is quoted so
+/// punctuation such as "3:16" or "don't" can never be read as FTS5 syntax;
+/// dangling AND/OR and unbalanced parentheses are dropped. A NOT without a
+/// term before it is an error: dropping it would search for exactly what
+/// was excluded. Returns `None` if nothing searchable remains.
+///
+/// With `normalize`, terms go through the same KJV normalization as the
+/// verses_fts_normalized index (prefix terms are left as typed).
+fn build_match_query(input: &str, normalize: bool) -> Result<Option<String>, String> {
+    let mut parts: Vec<String> = Vec::new();
+    let mut depth = 0;
+    // Whether the previous part can be followed by an operator
+    let mut after_operand = false;
+
+    for token in tokenize(input) {
+        match token {
+            Token::Term(term, prefix) => {
+                if !term.chars().any(|c| c.is_alphanumeric()) {
+                    continue;
+                }
+                let term = if normalize && !prefix { normalize_text(&term) } else { term };
+                let mut part = format!("\"{}\"", term.replace('"', "\"\""));
+                if prefix {
+                    part.push('*');
+                }
+                if after_operand {
+                    parts.push("AND".to_string());
+                }
+                parts.push(part);
+                after_operand = true;
+            }
+            Token::Operator("NOT") if !after_operand => {
+                return Err("NOT needs a term before it, as in \"faith NOT works\"".to_string());
+            }
+            Token::Operator(op) => {
+                if after_operand {
+                    parts.push(op.to_string());
+                    after_operand = false;
+                }
+            }
+            Token::Open => {
+                if after_operand {
+                    parts.push("AND".to_string());
+                }
+                parts.push("(".to_string());
+                depth += 1;
+                after_operand = false;
+            }
+            Token::Close => {
+                if depth > 0 && after_operand {
+                    parts.push(")".to_string());
+                    depth -= 1;
+                }
+            }
+        }
+    }
+
+    // Trim trailing operators and empty groups, then close open groups
+    loop {
+        match parts.last().map(String::as_str) {
+            Some("AND") | Some("OR") | Some("NOT") => {
+                parts.pop();
+            }
+            Some("(") => {
+                parts.pop();
+                depth -= 1;
+            }
+            _ => break,
+        }
+    }
+    for _ in 0..depth {
+        parts.push(")".to_string());
+    }
+
+    if parts.iter().any(|p| p.starts_with('"')) {
+        Ok(Some(parts.join(" ")))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_match_queries() {
+        let build = |input: &str| build_match_query(input, false).unwrap();
+
+        assert_eq!(build("faith hope"), Some("\"faith\" AND \"hope\"".to_string()));
+        assert_eq!(build("faith NOT works"), Some("\"faith\" NOT \"works\"".to_string()));
+        assert_eq!(build("\"born again\" OR (new creat*)"), Some("\"born again\" OR ( \"new\" AND \"creat\"* )".to_string()));
+        assert_eq!(build("John 3:16"), Some("\"John\" AND \"3:16\"".to_string()));
+        assert_eq!(build("OR faith AND"), Some("\"faith\"".to_string()));
+        assert_eq!(build("((grace"), Some("( ( \"grace\" ) )".to_string()));
+        assert_eq!(build("AND ()"), None);
+    }
+
+    #[test]
+    fn rejects_not_without_a_term_before_it() {
+        for input in ["NOT faith", "(NOT faith)", "hope OR NOT faith"] {
+            assert!(build_match_query(input, false).unwrap_err().contains("NOT needs a term"), "{}", input);
+        }
+        assert!(MatchQuery::parse("NOT faith", true).is_err());
+    }
+
+    #[test]
+    fn normalizes_terms() {
+        let query = MatchQuery::parse("loveth shew*", false).unwrap().unwrap();
+        assert_eq!(query.expr, "\"love\" AND \"shew\"*");
+        assert!(!query.exact);
+        assert_eq!(MatchQuery::parse("loveth", true).unwrap().unwrap().expr, "\"loveth\"");
+    }
+
+    #[test]
+    fn normalizes_archaic_verb_forms() {
+        assert_eq!(normalize_word("Loveth"), "love");
+        assert_eq!(normalize_word("sitteth"), "sit");
+        assert_eq!(normalize_word("knowest"), "know");
+        assert_eq!(normalize_word("makest"), "make");
+        assert_eq!(normalize_word("lovedst"), "loved");
+        assert_eq!(normalize_word("hath"), "has");
+    }
+
+    #[test]
+    fn leaves_words_that_only_look_like_verb_forms() {
+        for word in ["forest", "honest", "interest", "harvest", "priest", "teeth", "beneath", "chest", "guest", "beast", "feast"] {
+            assert_eq!(normalize_word(word), word);
+        }
+        assert_eq!(normalize_text("The priest's harvest, beneath"), "the priest's harvest, beneath");
+    }
+}
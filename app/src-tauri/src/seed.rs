@@ -1,5 +1,14 @@
 use rusqlite::{Connection, Result, params};
 use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use crate::db::KJV_TRANSLATION_ID;
+
+/// Parse a bundled dataset, reporting a bad file as an error rather than a panic
+fn parse_dataset<T: DeserializeOwned>(name: &str, json: &str) -> Result<T> {
+    serde_json::from_str(json).map_err(|e| {
+        rusqlite::Error::ToSqlConversionFailure(format!("Failed to parse {}: {}", name, e).into())
+    })
+}
 
 #[derive(Deserialize)]
 struct BibleVerse {
@@ -7,30 +16,62 @@ struct BibleVerse {
     c: i64,  // chapter
     v: i64,  // verse
     t: String, // text
+    #[serde(default)]
+    m: Option<String>, // markup with italics and red letters, when the source has them
 }
 
 /// Seeds all 31,100 verses from the KJV Bible
 pub fn seed_full_bible(conn: &Connection) -> Result<()> {
     let json_data = include_str!("../data/kjv_verses.json");
-    let verses: Vec<BibleVerse> = serde_json::from_str(json_data)
-        .expect("Failed to parse KJV Bible JSON");
+    let verses: Vec<BibleVerse> = parse_dataset("data/kjv_verses.json", json_data)?;
+    insert_verses(conn, &verses)?;
+
+    println!("Seeded {} verses from the KJV Bible", verses.len());
+    Ok(())
+}
 
+fn insert_verses(conn: &Connection, verses: &[BibleVerse]) -> Result<()> {
     // Use a transaction for much faster inserts
     conn.execute("BEGIN TRANSACTION", [])?;
 
-    for verse in &verses {
+    for verse in verses {
         conn.execute(
-            "INSERT OR IGNORE INTO verses (book_id, chapter, verse, text) VALUES (?1, ?2, ?3, ?4)",
-            params![verse.b, verse.c, verse.v, verse.t],
+            "INSERT OR IGNORE INTO verses (book_id, chapter, verse, text, markup) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![verse.b, verse.c, verse.v, verse.t, verse.m],
         )?;
     }
 
     conn.execute("COMMIT", [])?;
+    Ok(())
+}
 
-    println!("Seeded {} verses from the KJV Bible", verses.len());
+/// Fills in the markup of KJV verses seeded before verses had markup, from
+/// the bundled text. Markup already there (from an imported KJV) is kept.
+pub fn seed_kjv_markup(conn: &Connection) -> Result<()> {
+    let json_data = include_str!("../data/kjv_verses.json");
+    let verses: Vec<BibleVerse> = parse_dataset("data/kjv_verses.json", json_data)?;
+    let updated = update_markup(conn, &verses)?;
+
+    println!("Added markup to {} KJV verses", updated);
     Ok(())
 }
 
+fn update_markup(conn: &Connection, verses: &[BibleVerse]) -> Result<usize> {
+    conn.execute("BEGIN TRANSACTION", [])?;
+
+    let mut updated = 0;
+    for verse in verses.iter().filter(|v| v.m.is_some()) {
+        updated += conn.execute(
+            "UPDATE verses SET markup = ?1
+             WHERE translation_id = ?2 AND book_id = ?3 AND chapter = ?4 AND verse = ?5 AND markup IS NULL",
+            params![verse.m, KJV_TRANSLATION_ID, verse.b, verse.c, verse.v],
+        )?;
+    }
+
+    conn.execute("COMMIT", [])?;
+    Ok(updated)
+}
+
 pub fn seed_books(conn: &Connection) -> Result<()> {
     let books = vec![
         // Old Testament
@@ -846,3 +887,73 @@ pub fn seed_reflection_questions(conn: &Connection) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verses_table() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE verses (
+                id INTEGER PRIMARY KEY,
+                translation_id INTEGER NOT NULL DEFAULT 1,
+                book_id INTEGER NOT NULL,
+                chapter INTEGER NOT NULL,
+                verse INTEGER NOT NULL,
+                text TEXT NOT NULL,
+                markup TEXT,
+                UNIQUE(translation_id, book_id, chapter, verse)
+            );"
+        ).unwrap();
+        conn
+    }
+
+    fn markup(conn: &Connection, chapter: i64, verse: i64) -> Option<String> {
+        conn.query_row(
+            "SELECT markup FROM verses WHERE book_id = 43 AND chapter = ?1 AND verse = ?2",
+            params![chapter, verse],
+            |row| row.get(0),
+        ).unwrap()
+    }
+
+    const VERSES: &str = r#"[
+        {"b": 43, "c": 11, "v": 35, "t": "Jesus wept."},
+        {"b": 43, "c": 14, "v": 6, "t": "Jesus saith unto him, I am the way",
+         "m": "Jesus saith unto him, <j>I am the way</j>"}
+    ]"#;
+
+    #[test]
+    fn seeds_markup_when_present() {
+        let conn = verses_table();
+        let verses: Vec<BibleVerse> = parse_dataset("verses", VERSES).unwrap();
+        insert_verses(&conn, &verses).unwrap();
+
+        assert_eq!(markup(&conn, 11, 35), None);
+        assert_eq!(markup(&conn, 14, 6).as_deref(), Some("Jesus saith unto him, <j>I am the way</j>"));
+    }
+
+    #[test]
+    fn backfills_markup_without_overwriting() {
+        let conn = verses_table();
+        conn.execute_batch(
+            "INSERT INTO verses (book_id, chapter, verse, text) VALUES (43, 11, 35, 'Jesus wept.');
+             INSERT INTO verses (book_id, chapter, verse, text, markup)
+             VALUES (43, 14, 6, 'Jesus saith unto him, I am the way', 'imported');"
+        ).unwrap();
+
+        let verses: Vec<BibleVerse> = parse_dataset("verses", VERSES).unwrap();
+        assert_eq!(update_markup(&conn, &verses).unwrap(), 0);
+        assert_eq!(markup(&conn, 14, 6).as_deref(), Some("imported"));
+
+        conn.execute("UPDATE verses SET markup = NULL", []).unwrap();
+        assert_eq!(update_markup(&conn, &verses).unwrap(), 1);
+        assert_eq!(markup(&conn, 14, 6).as_deref(), Some("Jesus saith unto him, <j>I am the way</j>"));
+    }
+
+    #[test]
+    fn reports_a_bad_dataset() {
+        let err = parse_dataset::<Vec<BibleVerse>>("data/kjv_verses.json", r#"[{"b": 1}]"#).err().unwrap();
+        assert!(err.to_string().contains("Failed to parse data/kjv_verses.json"));
+    }
+}
@@ -0,0 +1,222 @@
+// AI search: a need in the user's words is expanded by the LLM into themes,
+// which are matched against topic names and slugs (and so the topics'
+// verses) and the keyword index
+
+use std::collections::{BTreeSet, HashMap};
+use crate::db::Database;
+use crate::models::SemanticMatch;
+use crate::search::MatchQuery;
+
+/// Themes used from a reply; the prompt asks for 5-10
+pub const MAX_THEMES: usize = 10;
+
+/// Keyword matches taken per theme
+const KEYWORD_RESULTS: i64 = 20;
+
+/// A verse in a matched topic scores this times its relevance to the topic;
+/// the best keyword match of a theme scores 1
+const TOPIC_WEIGHT: f64 = 2.0;
+
+// Words that say nothing about what a theme is about
+const STOP_WORDS: &[&str] = &[
+    "and", "the", "for", "with", "from", "about", "into", "our", "your", "their",
+    "his", "her", "its", "one", "who", "what", "when", "how", "that", "this",
+];
+
+/// The themes in a reply to the semantic search prompt: separated by commas
+/// or lines, without list markers or quotes, lower-cased and de-duplicated
+pub fn parse_themes(reply: &str) -> Vec<String> {
+    let mut themes: Vec<String> = Vec::new();
+    for part in reply.split([',', ';', '\n']) {
+        let theme = part
+            .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '-' | '*' | '•' | '.' | ')') || c.is_whitespace())
+            .trim_matches(|c: char| matches!(c, '"' | '\'' | '.' | '*') || c.is_whitespace())
+            .to_lowercase();
+        if !theme.is_empty() && theme.len() <= 60 && !themes.contains(&theme) {
+            themes.push(theme);
+        }
+        if themes.len() == MAX_THEMES {
+            break;
+        }
+    }
+    themes
+}
+
+/// The words of a text that say what it is about: lower-case, three
+/// letters or more, and not stop words
+fn significant_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| word.len() >= 3 && !STOP_WORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// Significant words with a plural s dropped, so "finances" meets "finance"
+fn stems(text: &str) -> Vec<String> {
+    significant_words(text).into_iter()
+        .map(|word| match word.strip_suffix('s') {
+            Some(stem) if stem.len() >= 3 && !stem.ends_with('s') => stem.to_string(),
+            _ => word,
+        })
+        .collect()
+}
+
+/// Verses matching `themes`, best first. A theme matches a topic when they
+/// share a word (of its name or slug), bringing in the topic's verses by
+/// their relevance; each theme's words are also looked up in the keyword
+/// index. A verse's score adds up everything it matched.
+pub fn search(db: &Database, themes: &[String], limit: usize, translation_id: i64) -> rusqlite::Result<Vec<SemanticMatch>> {
+    let mut found: HashMap<i64, SemanticMatch> = HashMap::new();
+    let mut reasons: HashMap<i64, Vec<String>> = HashMap::new();
+
+    for topic in db.get_topics()? {
+        let topic_stems = stems(&format!("{} {}", topic.name, topic.slug));
+        let matched: Vec<String> = themes.iter()
+            .filter(|theme| stems(theme).iter().any(|stem| topic_stems.contains(stem)))
+            .cloned()
+            .collect();
+        if matched.is_empty() {
+            continue;
+        }
+
+        let reason = format!("In the topic {}, which matches {}", topic.name, quoted(&matched));
+        for verse in db.get_verses_by_topic(topic.id, None, translation_id)? {
+            let entry = found.entry(verse.id).or_insert_with(|| SemanticMatch {
+                id: verse.id,
+                book_id: verse.book_id,
+                chapter: verse.chapter,
+                verse: verse.verse,
+                snippet: verse.text.clone(),
+                text: verse.text,
+                book_name: verse.book_name,
+                book_abbreviation: verse.book_abbreviation,
+                score: 0.0,
+                themes: Vec::new(),
+                topics: Vec::new(),
+                explanation: String::new(),
+            });
+            entry.score += TOPIC_WEIGHT * verse.relevance_score;
+            entry.themes.extend(matched.iter().cloned());
+            entry.topics.push(topic.name.clone());
+            reasons.entry(verse.id).or_default().push(reason.clone());
+        }
+    }
+
+    for theme in themes {
+        // Significant words are lower-case, so they never read as operators
+        let Ok(Some(query)) = MatchQuery::parse(&significant_words(theme).join(" "), false) else {
+            continue;
+        };
+        let results = db.search_verses(&query, Some(KEYWORD_RESULTS), translation_id)?;
+        let best = results.first().map_or(1.0, |result| result.score.max(f64::EPSILON));
+        for result in results {
+            let entry = found.entry(result.id).or_insert_with(|| SemanticMatch {
+                id: result.id,
+                book_id: result.book_id,
+                chapter: result.chapter,
+                verse: result.verse,
+                text: result.text,
+                book_name: result.book_name,
+                book_abbreviation: result.book_abbreviation,
+                snippet: String::new(),
+                score: 0.0,
+                themes: Vec::new(),
+                topics: Vec::new(),
+                explanation: String::new(),
+            });
+            entry.score += result.score / best;
+            entry.themes.push(theme.clone());
+            // The first keyword match marks words a topic match leaves plain
+            if !entry.snippet.contains("<mark>") {
+                entry.snippet = result.snippet;
+            }
+            reasons.entry(result.id).or_default().push(format!("Mentions {}", quoted(std::slice::from_ref(theme))));
+        }
+    }
+
+    let mut matches: Vec<SemanticMatch> = found.into_values()
+        .map(|mut verse| {
+            let themes: BTreeSet<String> = verse.themes.drain(..).collect();
+            verse.themes = themes.into_iter().collect();
+            verse.explanation = reasons.remove(&verse.id).unwrap_or_default().join("; ");
+            verse
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    matches.truncate(limit);
+
+    Ok(matches)
+}
+
+fn quoted(themes: &[String]) -> String {
+    themes.iter().map(|theme| format!("\"{}\"", theme)).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::KJV_TRANSLATION_ID;
+
+    #[test]
+    fn parses_themes_from_lists() {
+        let reply = "1. Trust in God\n2) \"Provision\"\n- fear, *anxiety*\n\u{2022} provision.\n\n";
+        assert_eq!(parse_themes(reply), vec!["trust in god", "provision", "fear", "anxiety"]);
+
+        assert_eq!(parse_themes("'Hope'; \"PEACE.\", hope"), vec!["hope", "peace"]);
+        assert!(parse_themes("").is_empty());
+        assert!(parse_themes(" - \n* , \"\"").is_empty());
+
+        // A sentence rather than a theme is left out
+        let long = "a very long explanation of what the user might be looking for in scripture";
+        assert_eq!(parse_themes(&format!("{}, grace", long)), vec!["grace"]);
+    }
+
+    #[test]
+    fn keeps_at_most_max_themes() {
+        let reply = (1..=15).map(|n| format!("theme {}", n)).collect::<Vec<_>>().join(", ");
+        let themes = parse_themes(&reply);
+        assert_eq!(themes.len(), MAX_THEMES);
+        assert_eq!(themes.last().map(String::as_str), Some("theme 10"));
+    }
+
+    #[test]
+    fn stems_significant_words() {
+        assert_eq!(stems("Finances & Wealth"), vec!["finance", "wealth"]);
+        // Short words, stop words and -ss endings are kept out or left alone
+        assert_eq!(stems("the fear of his loss is great"), vec!["fear", "loss", "great"]);
+        assert_eq!(stems("gas bus kindness"), vec!["gas", "bus", "kindness"]);
+    }
+
+    #[test]
+    fn ranks_verses_by_everything_they_match() {
+        let db = Database::new(std::path::Path::new(":memory:")).unwrap();
+        let search = |themes: &[&str], limit: usize| {
+            let themes: Vec<String> = themes.iter().map(|t| t.to_string()).collect();
+            search(&db, &themes, limit, KJV_TRANSLATION_ID).unwrap()
+        };
+
+        assert!(search(&["xylophone"], 25).is_empty());
+
+        // "finances" only matches the topic: its verses score by relevance
+        let topic = search(&["finances"], 100);
+        assert!(!topic.is_empty());
+        assert_eq!(topic[0].score, TOPIC_WEIGHT);
+        assert!(topic.iter().all(|m| m.topics == ["Finances & Wealth"] && m.themes == ["finances"]));
+        assert!(topic[0].explanation.starts_with("In the topic Finances & Wealth, which matches \"finances\""));
+
+        let keyword = search(&["treasure"], 100);
+        assert!(!keyword.is_empty());
+        assert_eq!(keyword[0].score, 1.0);
+        assert!(keyword.iter().all(|m| m.explanation == "Mentions \"treasure\""));
+
+        // Scores add up across themes, best first
+        let both = search(&["finances", "treasure"], 100);
+        assert!(both.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        for m in &both {
+            let part = |results: &[SemanticMatch]| results.iter().find(|r| r.id == m.id).map_or(0.0, |r| r.score);
+            assert!((m.score - part(&topic) - part(&keyword)).abs() < 1e-9, "{}", m.id);
+        }
+
+        assert_eq!(search(&["finances", "treasure"], 3).len(), 3);
+    }
+}
@@ -0,0 +1,223 @@
+// Editable prompt templates. Each has a built-in default from llm::prompts;
+// a saved body in prompt_templates replaces it until it is reset. Bodies
+// name their inputs as {{variable}} placeholders.
+
+use std::collections::HashMap;
+use crate::db::Database;
+use crate::llm::prompts;
+use crate::models::PromptTemplate;
+
+pub struct TemplateSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub variables: &'static [&'static str],
+    pub required: &'static [&'static str],  // Variables a body must use for the prompt to make sense
+    pub default_body: &'static str,
+}
+
+pub const TEMPLATES: &[TemplateSpec] = &[
+    TemplateSpec {
+        name: "system",
+        description: "Sent with every request: the assistant's role, tone and emphasis",
+        variables: &[],
+        required: &[],
+        default_body: prompts::SYSTEM_PROMPT,
+    },
+    TemplateSpec {
+        name: "verse_insight",
+        description: "AI insight for a verse",
+        variables: &["reference", "verse_text"],
+        required: &["verse_text"],
+        default_body: prompts::VERSE_INSIGHT,
+    },
+    TemplateSpec {
+        name: "action_steps",
+        description: "Action steps for applying a verse; the reply must be JSON",
+        variables: &["reference", "verse_text", "topic"],
+        required: &["verse_text"],
+        default_body: prompts::ACTION_STEPS,
+    },
+    TemplateSpec {
+        name: "reflection_questions",
+        description: "Reflection questions for a verse; the reply must be JSON",
+        variables: &["reference", "verse_text"],
+        required: &["verse_text"],
+        default_body: prompts::REFLECTION_QUESTIONS,
+    },
+    TemplateSpec {
+        name: "semantic_search",
+        description: "Expands a search into Bible topics and themes",
+        variables: &["query"],
+        required: &["query"],
+        default_body: prompts::SEMANTIC_SEARCH,
+    },
+];
+
+/// Values used by `preview_prompt_template` for variables it isn't given
+pub fn sample_value(variable: &str) -> &'static str {
+    match variable {
+        "reference" => "John 3:16",
+        "verse_text" => "For God so loved the world, that he gave his only begotten Son, that whosoever believeth in him should not perish, but have everlasting life.",
+        "topic" => "faith",
+        "query" => "I'm worried about losing my job",
+        _ => "",
+    }
+}
+
+pub fn spec(name: &str) -> Result<&'static TemplateSpec, String> {
+    TEMPLATES.iter()
+        .find(|spec| spec.name == name)
+        .ok_or_else(|| format!("Unknown prompt template: {}", name))
+}
+
+/// The placeholders in a body, trimmed, in order of appearance
+fn placeholders(body: &str) -> Result<Vec<&str>, String> {
+    let mut found = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after.find("}}")
+            .ok_or_else(|| "A {{ placeholder is not closed with }}".to_string())?;
+        found.push(after[..end].trim());
+        rest = &after[end + 2..];
+    }
+    Ok(found)
+}
+
+/// Check a body for a template: not blank, only the template's variables,
+/// and every variable it requires
+pub fn validate(spec: &TemplateSpec, body: &str) -> Result<(), String> {
+    if body.trim().is_empty() {
+        return Err(format!("The {} template can't be empty", spec.name));
+    }
+
+    let used = placeholders(body)?;
+    let unknown: Vec<&str> = used.iter()
+        .copied()
+        .filter(|variable| !spec.variables.contains(variable))
+        .collect();
+    if !unknown.is_empty() {
+        let allowed = match spec.variables {
+            [] => "it takes no variables".to_string(),
+            variables => format!("it takes {}", placeholder_list(variables)),
+        };
+        return Err(format!("Unknown variable {} in the {} template; {}", placeholder_list(&unknown), spec.name, allowed));
+    }
+
+    let missing: Vec<&str> = spec.required.iter()
+        .copied()
+        .filter(|variable| !used.contains(variable))
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("The {} template must use {}", spec.name, placeholder_list(&missing)));
+    }
+
+    Ok(())
+}
+
+fn placeholder_list(variables: &[&str]) -> String {
+    variables.iter().map(|variable| format!("{{{{{}}}}}", variable)).collect::<Vec<_>>().join(", ")
+}
+
+/// Fill in a body's placeholders; a variable without a value becomes empty
+pub fn render(body: &str, values: &HashMap<&str, &str>) -> String {
+    let mut rendered = String::with_capacity(body.len());
+    let mut rest = body;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}").map(|end| start + 2 + end) else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(values.get(rest[start + 2..end].trim()).copied().unwrap_or_default());
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// A template with the body in use: the saved one, or the default
+pub fn load(db: &Database, spec: &TemplateSpec) -> rusqlite::Result<PromptTemplate> {
+    let saved = db.get_prompt_template(spec.name)?;
+    Ok(PromptTemplate {
+        name: spec.name.to_string(),
+        description: spec.description.to_string(),
+        variables: spec.variables.iter().map(|v| v.to_string()).collect(),
+        required_variables: spec.required.iter().map(|v| v.to_string()).collect(),
+        customized: saved.is_some(),
+        body: saved.unwrap_or_else(|| spec.default_body.to_string()),
+        default_body: spec.default_body.to_string(),
+    })
+}
+
+/// Render the template called `name` as it is in use
+pub fn render_template(db: &Database, name: &str, values: &[(&str, &str)]) -> rusqlite::Result<String> {
+    let spec = spec(name).expect("built-in template name");
+    let template = load(db, spec)?;
+    Ok(render(&template.body, &values.iter().copied().collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values<'a>(pairs: &[(&'a str, &'a str)]) -> HashMap<&'a str, &'a str> {
+        pairs.iter().copied().collect()
+    }
+
+    #[test]
+    fn finds_placeholders() {
+        assert_eq!(placeholders("{{reference}} - \"{{ verse_text }}\"").unwrap(), vec!["reference", "verse_text"]);
+        assert_eq!(placeholders("No placeholders, just } and { braces").unwrap(), Vec::<&str>::new());
+        assert_eq!(placeholders("About {{topic").unwrap_err(), "A {{ placeholder is not closed with }}");
+        assert!(placeholders("{{reference}} and {{verse_text").is_err());
+    }
+
+    #[test]
+    fn validates_bodies() {
+        let insight = spec("verse_insight").unwrap();
+        assert!(validate(insight, "Explain {{reference}}: {{verse_text}}").is_ok());
+        assert!(validate(insight, "Explain {{verse_text}}").is_ok());
+
+        assert_eq!(validate(insight, "  \n").unwrap_err(), "The verse_insight template can't be empty");
+        assert_eq!(validate(insight, "Explain {{verse_text}} for {{verse_txt").unwrap_err(), "A {{ placeholder is not closed with }}");
+        assert_eq!(
+            validate(insight, "Explain {{verse_text}} about {{topic}}").unwrap_err(),
+            "Unknown variable {{topic}} in the verse_insight template; it takes {{reference}}, {{verse_text}}",
+        );
+        assert_eq!(
+            validate(spec("system").unwrap(), "You answer {{query}}").unwrap_err(),
+            "Unknown variable {{query}} in the system template; it takes no variables",
+        );
+
+        // Without the verse, the prompt would be sent with nothing to explain
+        assert_eq!(
+            validate(insight, "Explain {{reference}}").unwrap_err(),
+            "The verse_insight template must use {{verse_text}}",
+        );
+        assert!(validate(spec("semantic_search").unwrap(), "List Bible themes").unwrap_err().contains("must use {{query}}"));
+    }
+
+    #[test]
+    fn built_in_bodies_are_valid() {
+        for spec in TEMPLATES {
+            assert!(validate(spec, spec.default_body).is_ok(), "{}", spec.name);
+            assert!(spec.required.iter().all(|v| spec.variables.contains(v)), "{}", spec.name);
+        }
+        assert!(spec("daily_verse").is_err());
+    }
+
+    #[test]
+    fn renders_values() {
+        let body = "{{reference}} - \"{{ verse_text }}\" ({{topic}})";
+        assert_eq!(
+            render(body, &values(&[("reference", "John 11:35"), ("verse_text", "Jesus wept."), ("topic", "grief")])),
+            "John 11:35 - \"Jesus wept.\" (grief)",
+        );
+
+        // A variable without a value becomes empty
+        assert_eq!(render(body, &values(&[("reference", "John 11:35")])), "John 11:35 - \"\" ()");
+        // An unclosed placeholder is left as it is
+        assert_eq!(render("Hello {{name", &values(&[("name", "x")])), "Hello {{name");
+        assert_eq!(render("", &values(&[])), "");
+    }
+}
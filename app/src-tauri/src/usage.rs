@@ -0,0 +1,289 @@
+// Token usage: how logged LLM calls are grouped in reports, their estimated
+// cost from the model_prices table, and quotas on them.
+//
+// Each call is logged with a request type: "insight", "action_steps" or
+// "reflection" for the verse generations, and "search" for the themes AI
+// search asks for.
+
+use serde::Serialize;
+use std::fmt;
+use crate::db::Database;
+use crate::llm::MAX_OUTPUT_TOKENS;
+use crate::models::Settings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageGrouping {
+    Day,
+    Month,
+    Provider,
+    Model,
+}
+
+impl UsageGrouping {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "day" | "daily" => Ok(Self::Day),
+            "month" | "monthly" => Ok(Self::Month),
+            "provider" => Ok(Self::Provider),
+            "model" => Ok(Self::Model),
+            _ => Err(format!("Unknown usage grouping '{}'; expected day, month, provider or model", name.trim())),
+        }
+    }
+
+    /// SQL expression over token_usage `u` giving each row's group. Days and
+    /// months are local time; timestamps are stored in UTC.
+    pub(crate) fn sql_key(self) -> &'static str {
+        match self {
+            Self::Day => "date(u.timestamp, 'localtime')",
+            Self::Month => "strftime('%Y-%m', u.timestamp, 'localtime')",
+            Self::Provider => "u.provider",
+            Self::Model => "u.model",
+        }
+    }
+}
+
+/// SQL expression for the estimated cost in USD of token_usage row `u`,
+/// priced by the longest model_prices prefix of its model name
+pub(crate) const COST_SQL: &str = "
+    COALESCE((
+        SELECT (u.input_tokens * p.input_per_million + u.output_tokens * p.output_per_million) / 1000000.0
+        FROM model_prices p
+        WHERE p.provider = u.provider AND substr(u.model, 1, length(p.model)) = p.model
+        ORDER BY length(p.model) DESC
+        LIMIT 1
+    ), 0)";
+
+// Quotas
+// Caps on daily and monthly AI usage, checked before each generation. Local
+// providers cost nothing, so they are exempt unless
+// `Settings::quota_local_providers` is set.
+
+pub const LOCAL_PROVIDERS: &[&str] = &["lmstudio", "ollama"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaPeriod {
+    Daily,
+    Monthly,
+}
+
+impl QuotaPeriod {
+    /// SQL expression for the local date the current period started on
+    pub(crate) fn sql_start(self) -> &'static str {
+        match self {
+            Self::Daily => "date('now', 'localtime')",
+            Self::Monthly => "strftime('%Y-%m-01', 'now', 'localtime')",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaUnit {
+    Tokens,
+    Usd,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuotaExceeded {
+    pub period: QuotaPeriod,
+    pub unit: QuotaUnit,
+    pub limit: f64,
+    pub used: f64,
+    pub requested: f64,  // Estimate for the refused request
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let period = match self.period {
+            QuotaPeriod::Daily => "Daily",
+            QuotaPeriod::Monthly => "Monthly",
+        };
+        match self.unit {
+            QuotaUnit::Tokens => write!(
+                f,
+                "{} AI limit of {} tokens reached: {} used, this request needs up to {}",
+                period, self.limit, self.used, self.requested,
+            ),
+            QuotaUnit::Usd => write!(
+                f,
+                "{} AI limit of ${:.2} reached: ${:.2} used, this request may cost up to ${:.4}",
+                period, self.limit, self.used, self.requested,
+            ),
+        }
+    }
+}
+
+/// Rough token count of a text, about four characters per token
+pub fn estimate_tokens(text: &str) -> u32 {
+    (text.chars().count() as u32).div_ceil(4)
+}
+
+/// Check a request against the configured caps. `prompt_tokens` is the
+/// estimated input; the output is assumed to run to `MAX_OUTPUT_TOKENS`.
+pub fn check_quota(
+    db: &Database,
+    settings: &Settings,
+    provider: &str,
+    model: &str,
+    prompt_tokens: u32,
+) -> rusqlite::Result<Option<QuotaExceeded>> {
+    if !settings.quota_local_providers && LOCAL_PROVIDERS.contains(&provider) {
+        return Ok(None);
+    }
+
+    let tokens = prompt_tokens + MAX_OUTPUT_TOKENS;
+    let caps = [
+        (QuotaPeriod::Daily, settings.quota_daily_tokens, settings.quota_daily_cost),
+        (QuotaPeriod::Monthly, settings.quota_monthly_tokens, settings.quota_monthly_cost),
+    ];
+
+    for (period, token_cap, cost_cap) in caps {
+        if token_cap.is_none() && cost_cap.is_none() {
+            continue;
+        }
+        let (used_tokens, used_cost) = db.get_usage_since(period, settings.quota_local_providers)?;
+
+        if let Some(limit) = token_cap {
+            if used_tokens + tokens as i64 > limit {
+                return Ok(Some(QuotaExceeded {
+                    period,
+                    unit: QuotaUnit::Tokens,
+                    limit: limit as f64,
+                    used: used_tokens as f64,
+                    requested: tokens as f64,
+                }));
+            }
+        }
+
+        if let Some(limit) = cost_cap {
+            let cost = db.estimate_cost(provider, model, prompt_tokens, MAX_OUTPUT_TOKENS)?;
+            if used_cost + cost > limit {
+                return Ok(Some(QuotaExceeded {
+                    period,
+                    unit: QuotaUnit::Usd,
+                    limit,
+                    used: used_cost,
+                    requested: cost,
+                }));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ModelPrice;
+
+    // $1 per thousand tokens, in and out, so costs are easy to follow
+    const MODEL: &str = "test-model";
+
+    fn database() -> Database {
+        let db = Database::new(std::path::Path::new(":memory:")).unwrap();
+        db.set_model_price(&ModelPrice {
+            provider: "openai".to_string(),
+            model: MODEL.to_string(),
+            input_per_million: 1000.0,
+            output_per_million: 1000.0,
+        }).unwrap();
+        // 1,000 tokens ($1) today, and a cached call that spent nothing
+        db.log_token_usage("openai", MODEL, 600, 400, "insight", false).unwrap();
+        db.log_token_usage("openai", MODEL, 0, 0, "insight", true).unwrap();
+        db
+    }
+
+    fn check(db: &Database, settings: &Settings, provider: &str) -> Option<QuotaExceeded> {
+        // A 100-token prompt asks for up to 100 + MAX_OUTPUT_TOKENS
+        check_quota(db, settings, provider, MODEL, 100).unwrap()
+    }
+
+    #[test]
+    fn estimates_four_characters_per_token() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
+        // Characters, not bytes
+        assert_eq!(estimate_tokens("\u{e9}\u{e9}\u{e9}\u{e9}"), 1);
+    }
+
+    #[test]
+    fn caps_tokens() {
+        let db = database();
+        let requested = 100 + MAX_OUTPUT_TOKENS as i64;
+
+        // Reaching the cap exactly is allowed
+        let settings = Settings { quota_daily_tokens: Some(1000 + requested), ..Settings::default() };
+        assert!(check(&db, &settings, "openai").is_none());
+
+        let settings = Settings { quota_daily_tokens: Some(1000 + requested - 1), ..Settings::default() };
+        let exceeded = check(&db, &settings, "openai").unwrap();
+        assert_eq!((exceeded.period, exceeded.unit), (QuotaPeriod::Daily, QuotaUnit::Tokens));
+        assert_eq!((exceeded.used, exceeded.requested), (1000.0, requested as f64));
+        assert_eq!(exceeded.limit, (1000 + requested - 1) as f64);
+    }
+
+    #[test]
+    fn caps_cost() {
+        let db = database();
+        // The request may cost (100 + 1024) tokens at $1 per thousand
+        let settings = Settings { quota_daily_cost: Some(2.2), ..Settings::default() };
+        assert!(check(&db, &settings, "openai").is_none());
+
+        let settings = Settings { quota_daily_cost: Some(2.0), ..Settings::default() };
+        let exceeded = check(&db, &settings, "openai").unwrap();
+        assert_eq!(exceeded.unit, QuotaUnit::Usd);
+        assert!((exceeded.used - 1.0).abs() < 1e-9, "{}", exceeded.used);
+        assert!((exceeded.requested - 1.124).abs() < 1e-9, "{}", exceeded.requested);
+
+        // A model without a price costs nothing
+        let free = check_quota(&db, &settings, "openai", "unpriced-model", 100).unwrap();
+        assert!(free.is_none());
+    }
+
+    #[test]
+    fn exempts_local_providers() {
+        let db = database();
+        db.log_token_usage("ollama", "mistral", 50_000, 50_000, "insight", false).unwrap();
+
+        let settings = Settings { quota_daily_tokens: Some(10), ..Settings::default() };
+        assert!(check(&db, &settings, "ollama").is_none());
+        assert!(check(&db, &settings, "lmstudio").is_none());
+
+        // Local usage doesn't count against the cap of a paid provider
+        let settings = Settings { quota_daily_tokens: Some(3000), ..Settings::default() };
+        assert!(check(&db, &settings, "openai").is_none());
+
+        // Unless local providers are capped too
+        let settings = Settings { quota_local_providers: true, ..settings };
+        let exceeded = check(&db, &settings, "ollama").unwrap();
+        assert_eq!(exceeded.used, 101_000.0);
+        assert!(check(&db, &settings, "openai").is_some());
+    }
+
+    #[test]
+    fn checks_daily_before_monthly() {
+        let db = database();
+
+        let settings = Settings {
+            quota_daily_tokens: Some(10),
+            quota_monthly_tokens: Some(10),
+            ..Settings::default()
+        };
+        assert_eq!(check(&db, &settings, "openai").unwrap().period, QuotaPeriod::Daily);
+
+        // A daily cap with room left still leaves the monthly cap to check
+        let settings = Settings {
+            quota_daily_tokens: Some(100_000),
+            quota_monthly_cost: Some(1.5),
+            ..Settings::default()
+        };
+        let exceeded = check(&db, &settings, "openai").unwrap();
+        assert_eq!((exceeded.period, exceeded.unit), (QuotaPeriod::Monthly, QuotaUnit::Usd));
+
+        assert!(check(&db, &Settings::default(), "openai").is_none());
+    }
+}
@@ -0,0 +1,293 @@
+// Versification: converting verse numbers between KJV, Hebrew (Masoretic),
+// LXX and Vulgate numbering. KJV numbering is what the database stores.
+
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Kjv,
+    Hebrew,
+    Lxx,
+    Vulgate,
+}
+
+impl Scheme {
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "kjv" | "english" => Ok(Self::Kjv),
+            "hebrew" | "mt" | "masoretic" => Ok(Self::Hebrew),
+            "lxx" | "septuagint" | "greek" => Ok(Self::Lxx),
+            "vulgate" | "vulg" | "latin" => Ok(Self::Vulgate),
+            _ => Err(format!(
+                "Unknown versification '{}'; expected kjv, hebrew, lxx or vulgate",
+                name.trim()
+            )),
+        }
+    }
+}
+
+/// Through the end of the chapter
+const END: i64 = 999;
+
+// A rule maps KJV verses chapter:first..=last in a book onto the other
+// scheme's other_chapter:other_first onward, verse for verse:
+// (book, chapter, first, last, other_chapter, other_first).
+// Verses not covered by any rule are numbered the same in both schemes.
+type Rule = (i64, i64, i64, i64, i64, i64);
+
+// Hebrew chapter boundaries that differ from the KJV outside the Psalms
+const HEBREW_CHAPTERS: &[Rule] = &[
+    (1, 31, 55, 55, 32, 1), (1, 32, 1, END, 32, 2),
+    (2, 8, 1, 4, 7, 26), (2, 8, 5, END, 8, 1),
+    (2, 22, 1, 1, 21, 37), (2, 22, 2, END, 22, 1),
+    (3, 6, 1, 7, 5, 20), (3, 6, 8, END, 6, 1),
+    (4, 16, 36, 50, 17, 1), (4, 17, 1, END, 17, 16),
+    (4, 29, 40, 40, 30, 1), (4, 30, 1, END, 30, 2),
+    (5, 12, 32, 32, 13, 1), (5, 13, 1, END, 13, 2),
+    (5, 22, 30, 30, 23, 1), (5, 23, 1, END, 23, 2),
+    (5, 29, 1, 1, 28, 69), (5, 29, 2, END, 29, 1),
+    (9, 23, 29, 29, 24, 1), (9, 24, 1, END, 24, 2),
+    (10, 18, 33, 33, 19, 1), (10, 19, 1, END, 19, 2),
+    (11, 4, 21, 34, 5, 1), (11, 5, 1, END, 5, 15),
+    (12, 11, 21, 21, 12, 1), (12, 12, 1, END, 12, 2),
+    (13, 6, 1, 15, 5, 27), (13, 6, 16, END, 6, 1),
+    (14, 2, 1, 1, 1, 18), (14, 2, 2, END, 2, 1),
+    (14, 14, 1, 1, 13, 23), (14, 14, 2, END, 14, 1),
+    (16, 4, 1, 6, 3, 33), (16, 4, 7, END, 4, 1),
+    (16, 9, 38, 38, 10, 1), (16, 10, 1, END, 10, 2),
+    (18, 41, 1, 8, 40, 25), (18, 41, 9, END, 41, 1),
+    (21, 5, 1, 1, 4, 17), (21, 5, 2, END, 5, 1),
+    (22, 6, 13, 13, 7, 1), (22, 7, 1, END, 7, 2),
+    (23, 9, 1, 1, 8, 23), (23, 9, 2, END, 9, 1),
+    (23, 64, 1, 1, 63, 19), (23, 64, 2, END, 64, 1),
+    (24, 9, 1, 1, 8, 23), (24, 9, 2, END, 9, 1),
+    (26, 20, 45, 49, 21, 1), (26, 21, 1, END, 21, 6),
+    (27, 4, 1, 3, 3, 31), (27, 4, 4, END, 4, 1),
+    (27, 5, 31, 31, 6, 1), (27, 6, 1, END, 6, 2),
+    (28, 1, 10, 11, 2, 1), (28, 2, 1, END, 2, 3),
+    (28, 11, 12, 12, 12, 1), (28, 12, 1, END, 12, 2),
+    (28, 13, 16, 16, 14, 1), (28, 14, 1, END, 14, 2),
+    (29, 2, 28, 32, 3, 1), (29, 3, 1, END, 4, 1),
+    (32, 1, 17, 17, 2, 1), (32, 2, 1, END, 2, 2),
+    (33, 5, 1, 1, 4, 14), (33, 5, 2, END, 5, 1),
+    (34, 1, 15, 15, 2, 1), (34, 2, 1, END, 2, 2),
+    (38, 1, 18, 21, 2, 1), (38, 2, 1, END, 2, 5),
+    (39, 4, 1, END, 3, 19),
+];
+
+// The LXX follows the Hebrew chapters of Joel and Malachi
+const LXX_CHAPTERS: &[Rule] = &[
+    (29, 2, 28, 32, 3, 1), (29, 3, 1, END, 4, 1),
+    (39, 4, 1, END, 3, 19),
+];
+
+// Psalms whose title is verse 1 in Hebrew, LXX and Vulgate numbering
+const TITLED_PSALMS: &[i64] = &[
+    3, 4, 5, 6, 7, 8, 9, 12, 18, 19, 20, 21, 22, 30, 31, 34, 36, 38, 39, 40, 41, 42, 44, 45,
+    46, 47, 48, 49, 53, 55, 56, 57, 58, 59, 61, 62, 63, 64, 65, 67, 68, 69, 70, 75, 76, 77,
+    80, 81, 83, 84, 85, 88, 89, 92, 102, 108, 140, 142,
+];
+
+// Psalms whose title takes verses 1-2
+const LONG_TITLED_PSALMS: &[i64] = &[51, 52, 54, 60];
+
+/// Where the verses of a KJV psalm start in LXX/Vulgate numbering, as
+/// (first KJV verse, LXX chapter, LXX verse for that first verse) segments
+fn greek_psalm(psalm: i64) -> Vec<(i64, i64, i64)> {
+    match psalm {
+        10 => vec![(1, 9, 22)],
+        11..=113 | 117..=146 => vec![(1, psalm - 1, 1)],
+        114 => vec![(1, 113, 1)],
+        115 => vec![(1, 113, 9)],
+        116 => vec![(1, 114, 1), (10, 115, 1)],
+        147 => vec![(1, 146, 1), (12, 147, 1)],
+        _ => vec![(1, psalm, 1)],
+    }
+}
+
+/// Psalm rules for a scheme: titles become verse 1 (or 1-2), and the
+/// LXX/Vulgate chapters are renumbered where Psalms 9-10, 114-115 and
+/// 116, 147 are joined or split
+fn psalm_rules(greek: bool) -> Vec<Rule> {
+    let mut rules = Vec::new();
+
+    for psalm in 1..=150 {
+        let segments = if greek { greek_psalm(psalm) } else { vec![(1, psalm, 1)] };
+        let (_, first_chapter, first_verse) = segments[0];
+
+        // Psalm 13: KJV verses 5-6 are both Hebrew verse 6
+        if psalm == 13 {
+            rules.push((19, 13, 0, 5, first_chapter, 1));
+            rules.push((19, 13, 6, 6, first_chapter, 6));
+            continue;
+        }
+
+        let offset = if LONG_TITLED_PSALMS.contains(&psalm) {
+            2
+        } else if TITLED_PSALMS.contains(&psalm) {
+            1
+        } else {
+            0
+        };
+
+        if offset > 0 {
+            rules.push((19, psalm, 0, 0, first_chapter, first_verse));
+        }
+        for (i, &(start, chapter, verse)) in segments.iter().enumerate() {
+            let last = segments.get(i + 1).map(|(next, _, _)| next - 1).unwrap_or(END);
+            if offset > 0 || chapter != psalm || verse != start {
+                rules.push((19, psalm, start, last, chapter, verse + offset));
+            }
+        }
+        // Hebrew verse 2 of a two-verse title still maps back to the title
+        if offset == 2 {
+            rules.push((19, psalm, 0, 0, first_chapter, first_verse + 1));
+        }
+    }
+
+    rules
+}
+
+fn rules(scheme: Scheme) -> &'static [Rule] {
+    static TABLES: OnceLock<[Vec<Rule>; 3]> = OnceLock::new();
+    let tables = TABLES.get_or_init(|| {
+        [
+            [HEBREW_CHAPTERS.to_vec(), psalm_rules(false)].concat(),
+            [LXX_CHAPTERS.to_vec(), psalm_rules(true)].concat(),
+            psalm_rules(true),
+        ]
+    });
+
+    match scheme {
+        Scheme::Kjv => &[],
+        Scheme::Hebrew => &tables[0],
+        Scheme::Lxx => &tables[1],
+        Scheme::Vulgate => &tables[2],
+    }
+}
+
+fn from_kjv(book_id: i64, chapter: i64, verse: i64, scheme: Scheme) -> (i64, i64) {
+    rules(scheme).iter()
+        .find(|r| r.0 == book_id && r.1 == chapter && (r.2..=r.3).contains(&verse))
+        .map(|r| (r.4, r.5 + (verse - r.2)))
+        .unwrap_or((chapter, verse))
+}
+
+fn to_kjv(book_id: i64, chapter: i64, verse: i64, scheme: Scheme) -> (i64, i64) {
+    // Open-ended rules can overlap where one chapter holds two KJV chapters
+    // (LXX Psalm 9 is KJV Psalms 9-10); the rule starting latest wins
+    rules(scheme).iter()
+        .filter(|r| r.0 == book_id && r.4 == chapter && (r.5..=r.5 + (r.3 - r.2)).contains(&verse))
+        .max_by_key(|r| r.5)
+        .map(|r| (r.1, r.2 + (verse - r.5)))
+        .unwrap_or((chapter, verse))
+}
+
+/// Convert a verse from one numbering scheme to another, returning the
+/// (chapter, verse) it has there. Psalm titles are verse 0 in KJV numbering.
+pub fn convert(book_id: i64, chapter: i64, verse: i64, from: Scheme, to: Scheme) -> (i64, i64) {
+    if from == to {
+        return (chapter, verse);
+    }
+    let (chapter, verse) = to_kjv(book_id, chapter, verse, from);
+    from_kjv(book_id, chapter, verse, to)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Scheme::*;
+
+    const PSALMS: i64 = 19;
+    const JOEL: i64 = 29;
+    const MALACHI: i64 = 39;
+
+    #[test]
+    fn converts_joel_and_malachi() {
+        assert_eq!(from_kjv(JOEL, 2, 27, Hebrew), (2, 27));
+        assert_eq!(from_kjv(JOEL, 2, 28, Hebrew), (3, 1));
+        assert_eq!(from_kjv(JOEL, 2, 32, Hebrew), (3, 5));
+        assert_eq!(from_kjv(JOEL, 3, 1, Hebrew), (4, 1));
+        assert_eq!(to_kjv(JOEL, 4, 21, Hebrew), (3, 21));
+        assert_eq!(to_kjv(JOEL, 3, 1, Lxx), (2, 28));
+        // The Vulgate keeps the KJV chapters of Joel
+        assert_eq!(from_kjv(JOEL, 3, 1, Vulgate), (3, 1));
+
+        assert_eq!(from_kjv(MALACHI, 4, 1, Hebrew), (3, 19));
+        assert_eq!(from_kjv(MALACHI, 4, 6, Hebrew), (3, 24));
+        assert_eq!(to_kjv(MALACHI, 3, 19, Hebrew), (4, 1));
+        assert_eq!(to_kjv(MALACHI, 3, 18, Hebrew), (3, 18));
+        assert_eq!(convert(MALACHI, 3, 19, Hebrew, Lxx), (3, 19));
+    }
+
+    #[test]
+    fn converts_psalm_titles() {
+        // A title is verse 0 in the KJV and verse 1 elsewhere
+        assert_eq!(from_kjv(PSALMS, 3, 0, Hebrew), (3, 1));
+        assert_eq!(from_kjv(PSALMS, 3, 1, Hebrew), (3, 2));
+        assert_eq!(from_kjv(PSALMS, 23, 1, Hebrew), (23, 1));
+
+        // ...or verses 1-2
+        assert_eq!(from_kjv(PSALMS, 51, 0, Hebrew), (51, 1));
+        assert_eq!(from_kjv(PSALMS, 51, 1, Hebrew), (51, 3));
+        assert_eq!(to_kjv(PSALMS, 51, 2, Hebrew), (51, 0));
+
+        // KJV Psalm 13:5-6 is Hebrew 13:6
+        assert_eq!(from_kjv(PSALMS, 13, 5, Hebrew), (13, 6));
+        assert_eq!(to_kjv(PSALMS, 13, 6, Hebrew), (13, 6));
+    }
+
+    #[test]
+    fn converts_greek_psalm_chapters() {
+        // LXX Psalm 9 holds KJV Psalms 9 and 10
+        assert_eq!(from_kjv(PSALMS, 9, 20, Lxx), (9, 21));
+        assert_eq!(from_kjv(PSALMS, 10, 1, Lxx), (9, 22));
+        assert_eq!(to_kjv(PSALMS, 9, 21, Lxx), (9, 20));
+        assert_eq!(to_kjv(PSALMS, 9, 22, Lxx), (10, 1));
+
+        assert_eq!(from_kjv(PSALMS, 23, 1, Vulgate), (22, 1));
+        assert_eq!(from_kjv(PSALMS, 115, 1, Lxx), (113, 9));
+        assert_eq!(from_kjv(PSALMS, 116, 10, Lxx), (115, 1));
+        assert_eq!(from_kjv(PSALMS, 147, 12, Vulgate), (147, 1));
+        assert_eq!(convert(PSALMS, 50, 3, Vulgate, Hebrew), (51, 3));
+    }
+
+    #[test]
+    fn round_trips_through_every_scheme() {
+        for scheme in [Hebrew, Lxx, Vulgate] {
+            for psalm in 1..=150 {
+                // Psalm 13 joins two KJV verses into one
+                if psalm == 13 {
+                    continue;
+                }
+                let first = if TITLED_PSALMS.contains(&psalm) || LONG_TITLED_PSALMS.contains(&psalm) { 0 } else { 1 };
+                // Verses past the end of a psalm joined to the next would land in it
+                let last = if psalm == 114 { 8 } else { 20 };
+                for verse in first..=last {
+                    let (chapter, other) = from_kjv(PSALMS, psalm, verse, scheme);
+                    assert_eq!(to_kjv(PSALMS, chapter, other, scheme), (psalm, verse), "Ps {}:{} {:?}", psalm, verse, scheme);
+                }
+            }
+            for (book, chapter) in [(JOEL, 2), (JOEL, 3), (MALACHI, 3), (MALACHI, 4)] {
+                for verse in 1..=18 {
+                    let (other_chapter, other) = from_kjv(book, chapter, verse, scheme);
+                    assert_eq!(to_kjv(book, other_chapter, other, scheme), (chapter, verse));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn leaves_kjv_numbering_and_other_books_alone() {
+        assert_eq!(convert(43, 3, 16, Kjv, Hebrew), (3, 16));
+        assert_eq!(convert(PSALMS, 10, 1, Kjv, Kjv), (10, 1));
+        assert_eq!(convert(PSALMS, 9, 22, Lxx, Lxx), (9, 22));
+    }
+
+    #[test]
+    fn reads_scheme_names() {
+        assert_eq!(Scheme::from_name(" MT "), Ok(Hebrew));
+        assert_eq!(Scheme::from_name("septuagint"), Ok(Lxx));
+        assert!(Scheme::from_name("nrsv").unwrap_err().contains("Unknown versification 'nrsv'"));
+    }
+}
@@ -1,14 +1,157 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import type { ActionStep, AppError, ReflectionQuestion, SemanticMatch } from '../lib/types';
+import { parseAppError } from '../lib/utils';
 
 export interface AiInsight {
   content: string;
   tokensUsed: number;
+  cached: boolean;  // Reused from the response cache; no tokens were spent
+  provider: string;  // The provider that answered, which may be a fallback
+  model: string;
+  stopReason: string | null;  // As the provider reports it, e.g. "max_tokens"; null when cached
+}
+
+// Action steps or reflection questions validated from the model's JSON reply,
+// which is the content. Generated items aren't saved and have negative ids.
+export interface AiItems<T> extends AiInsight {
+  items: T[];
+}
+
+// Verses found by AI search for the themes the query was expanded into;
+// the content is the model's list of themes
+export interface SemanticSearch extends AiInsight {
+  query: string;
+  themes: string[];
+  results: SemanticMatch[];
+}
+
+// Streaming events for a generation started with a request id
+export interface AiToken {
+  requestId: string;
+  delta: string;
+}
+
+export interface AiDone {
+  requestId: string;
+  content: string;
+  inputTokens: number;
+  outputTokens: number;
+  stopReason: string | null;
+}
+
+export type LlmErrorKind =
+  | 'unreachable'
+  | 'timeout'
+  | 'auth'
+  | 'rateLimited'
+  | 'server'
+  | 'api'
+  | 'quotaExceeded'
+  | 'other';
+
+// Sent before a rate-limited or failing request is retried,
+// e.g. "rate limited, retrying in 8s"
+export interface AiRetry {
+  requestId: string;
+  provider: string;
+  kind: LlmErrorKind;
+  message: string;
+  attempt: number;  // The attempt that failed, from 1
+  maxAttempts: number;
+  delayMs: number;
+}
+
+// Token usage totals for one day, month, provider or model
+export type UsageGrouping = 'day' | 'month' | 'provider' | 'model';
+
+export interface TokenUsageTotal {
+  group: string;
+  requests: number;
+  cachedRequests: number;
+  inputTokens: number;
+  outputTokens: number;
+  estimatedCost: number;  // USD
+}
+
+// USD per million tokens for models whose name starts with `model`
+export interface ModelPrice {
+  provider: string;
+  model: string;
+  inputPerMillion: number;
+  outputPerMillion: number;
+}
+
+// A prompt sent to the LLM; `body` is the edited one when `customized`,
+// else `defaultBody`. It may use its `variables` as {{name}} placeholders.
+export interface PromptTemplate {
+  name: string;
+  description: string;
+  variables: string[];
+  requiredVariables: string[];  // Must appear in the body
+  body: string;
+  defaultBody: string;
+  customized: boolean;
+}
+
+// Error from an AI command refused because it would go over a usage quota
+export interface QuotaExceeded extends AppError {
+  code: 'quotaExceeded';
+  period: 'daily' | 'monthly';
+  unit: 'tokens' | 'usd';
+  limit: number;
+  used: number;
+  requested: number;
+}
+
+// The quota details of a rejected AI command, or null for any other error
+export const parseQuotaExceeded = (error: unknown): QuotaExceeded | null => {
+  const appError = parseAppError(error);
+  return appError?.code === 'quotaExceeded' ? (appError as QuotaExceeded) : null;
+};
+
+// Error from an AI command whose provider was still rate limiting it after retries
+export interface RateLimited extends AppError {
+  code: 'rateLimited';
+  retryAfterSecs: number | null;  // When the provider said to try again, if it did
+}
+
+export const parseRateLimited = (error: unknown): RateLimited | null => {
+  const appError = parseAppError(error);
+  return appError?.code === 'rateLimited' ? (appError as RateLimited) : null;
+};
+
+// Whether generation stopped at the output token limit rather than finishing
+// ("max_tokens" from Claude, "length" from OpenAI-compatible servers and Ollama)
+export const isTruncated = (stopReason: string | null): boolean =>
+  stopReason === 'max_tokens' || stopReason === 'length';
+
+export interface LlmModel {
+  name: string;
+  size: number | null;  // Bytes on disk, for local models
+}
+
+// Whether one provider can be reached
+export interface BackendStatus {
+  provider: string;
+  model: string | null;  // As configured; null for the provider's default
+  connected: boolean;
 }
 
 export interface LlmStatus {
   connected: boolean;
   provider: string;
   model: string | null;
+  models: LlmModel[];  // What the provider offers; empty when not connected
+  fallbacks: BackendStatus[];  // In the order they are tried
+}
+
+// Progress of an Ollama model download
+export interface OllamaPull {
+  model: string;
+  status: string;
+  total?: number;
+  completed?: number;
 }
 
 // AI API for Tauri
@@ -18,19 +161,112 @@ export const aiApi = {
     return invoke<LlmStatus>('check_llm_connection');
   },
 
-  // Get AI-generated insight for a verse
-  getInsight: async (verseText: string, reference: string): Promise<AiInsight> => {
-    return invoke<AiInsight>('get_ai_insight', { verseText, reference });
+  // Download a model into Ollama; progress arrives as "ollama-pull" events
+  pullOllamaModel: async (model: string, onProgress?: (progress: OllamaPull) => void): Promise<void> => {
+    const unlisten = await listen<OllamaPull>('ollama-pull', (event) => {
+      if (event.payload.model === model) onProgress?.(event.payload);
+    });
+    try {
+      await invoke('pull_ollama_model', { model });
+    } finally {
+      unlisten();
+    }
+  },
+
+  // Get AI-generated insight for a verse. Pass a requestId to receive the
+  // text as it is generated (see onGeneration), and regenerate to skip the
+  // response cache and replace what it holds.
+  getInsight: async (verseText: string, reference: string, requestId?: string, regenerate?: boolean): Promise<AiInsight> => {
+    return invoke<AiInsight>('get_ai_insight', { verseText, reference, requestId, regenerate });
   },
 
   // Generate action steps for applying a verse
-  generateActionSteps: async (verseText: string, reference: string, topic: string): Promise<AiInsight> => {
-    return invoke<AiInsight>('generate_action_steps', { verseText, reference, topic });
+  generateActionSteps: async (verseId: number, verseText: string, reference: string, topic: string, requestId?: string, regenerate?: boolean): Promise<AiItems<ActionStep>> => {
+    return invoke<AiItems<ActionStep>>('generate_action_steps', { verseId, verseText, reference, topic, requestId, regenerate });
   },
 
   // Generate reflection questions for a verse
-  generateReflectionQuestions: async (verseText: string, reference: string): Promise<AiInsight> => {
-    return invoke<AiInsight>('generate_reflection_questions', { verseText, reference });
+  generateReflectionQuestions: async (verseId: number, verseText: string, reference: string, requestId?: string, regenerate?: boolean): Promise<AiItems<ReflectionQuestion>> => {
+    return invoke<AiItems<ReflectionQuestion>>('generate_reflection_questions', { verseId, verseText, reference, requestId, regenerate });
+  },
+
+  // Search by need ("I'm worried about losing my job"); recorded in the search history
+  aiSearch: async (query: string, limit?: number, translation?: string, requestId?: string, regenerate?: boolean): Promise<SemanticSearch> => {
+    return invoke<SemanticSearch>('ai_search', { query, limit, translation, requestId, regenerate });
+  },
+
+  // Stop a generation started with this requestId; resolves false if it already finished.
+  // The cancelled command rejects with "Generation cancelled".
+  cancelGeneration: async (requestId: string): Promise<boolean> => {
+    return invoke<boolean>('cancel_generation', { requestId });
+  },
+
+  // Drop all cached responses; resolves to how many were removed
+  clearCache: async (): Promise<number> => {
+    return invoke<number>('clear_ai_cache');
+  },
+
+  // Token usage and estimated cost, optionally only from `since` (YYYY-MM-DD) on
+  getTokenUsage: async (groupBy: UsageGrouping, since?: string): Promise<TokenUsageTotal[]> => {
+    return invoke<TokenUsageTotal[]>('get_token_usage', { groupBy, since });
+  },
+
+  // Price table used for cost estimates
+  getModelPrices: async (): Promise<ModelPrice[]> => {
+    return invoke<ModelPrice[]>('get_model_prices');
+  },
+
+  setModelPrice: async (price: ModelPrice): Promise<void> => {
+    return invoke('set_model_price', { price });
+  },
+
+  deleteModelPrice: async (provider: string, model: string): Promise<boolean> => {
+    return invoke<boolean>('delete_model_price', { provider, model });
+  },
+
+  // Editable prompts; updating rejects a body with unknown variables
+  getPromptTemplates: async (): Promise<PromptTemplate[]> => {
+    return invoke<PromptTemplate[]>('get_prompt_templates');
+  },
+
+  updatePromptTemplate: async (name: string, body: string): Promise<PromptTemplate> => {
+    return invoke<PromptTemplate>('update_prompt_template', { name, body });
+  },
+
+  // `body` (or the one in use) rendered with `values`, and sample values for the rest
+  previewPromptTemplate: async (name: string, body?: string, values?: Record<string, string>): Promise<string> => {
+    return invoke<string>('preview_prompt_template', { name, body, values });
+  },
+
+  // Restore the built-in body of one template, or of all without a name
+  resetPromptTemplate: async (name?: string): Promise<PromptTemplate[]> => {
+    return invoke<PromptTemplate[]>('reset_prompt_template', { name });
+  },
+
+  // Listen for the streamed tokens, retries and final usage of one generation.
+  // Subscribe before invoking the command; call the returned function to stop.
+  // When action steps or reflection questions come back as invalid JSON, the
+  // model's correction streams under `${requestId}:repair`.
+  onGeneration: async (
+    requestId: string,
+    onToken: (delta: string) => void,
+    onDone?: (done: AiDone) => void,
+    onRetry?: (retry: AiRetry) => void,
+  ): Promise<UnlistenFn> => {
+    const unlistenToken = await listen<AiToken>('ai-token', (event) => {
+      if (event.payload.requestId === requestId) onToken(event.payload.delta);
+    });
+    const unlistenDone = await listen<AiDone>('ai-done', (event) => {
+      if (event.payload.requestId === requestId) onDone?.(event.payload);
+    });
+    const unlistenRetry = await listen<AiRetry>('ai-retry', (event) => {
+      if (event.payload.requestId === requestId) onRetry?.(event.payload);
+    });
+    return () => {
+      unlistenToken();
+      unlistenDone();
+      unlistenRetry();
+    };
   },
 };
 
@@ -42,6 +278,8 @@ export const mockAiApi = {
       connected: true,
       provider: 'lmstudio',
       model: 'local-model',
+      models: [{ name: 'local-model', size: null }],
+      fallbacks: [],
     };
   },
 
@@ -61,40 +299,87 @@ export const mockAiApi = {
     return {
       content: insights.default,
       tokensUsed: 150,
+      cached: false,
+      provider: 'mock',
+      model: 'mock',
+      stopReason: 'stop',
     };
   },
 
-  generateActionSteps: async (_verseText: string, _reference: string, topic: string): Promise<AiInsight> => {
+  generateActionSteps: async (verseId: number, _verseText: string, _reference: string, topic: string): Promise<AiItems<ActionStep>> => {
     void _verseText; void _reference; // Suppress unused warnings
     await new Promise(resolve => setTimeout(resolve, 1200));
 
-    const steps = `1. **[Easy]** Set aside 5 minutes each morning this week to meditate on this verse and how it relates to ${topic.toLowerCase()} in your life.
-
-2. **[Medium]** Identify one specific area where you struggle with ${topic.toLowerCase()}. Write down how this verse speaks to that struggle and share it with a trusted friend.
-
-3. **[Challenging]** Choose one concrete action based on this verse's teaching about ${topic.toLowerCase()}. Commit to it for the next 30 days and journal your experience.`;
+    const steps: Pick<ActionStep, 'content' | 'difficulty'>[] = [
+      { content: `Set aside 5 minutes each morning this week to meditate on this verse and how it relates to ${topic.toLowerCase()} in your life.`, difficulty: 'easy' },
+      { content: `Identify one specific area where you struggle with ${topic.toLowerCase()}. Write down how this verse speaks to that struggle and share it with a trusted friend.`, difficulty: 'medium' },
+      { content: `Choose one concrete action based on this verse's teaching about ${topic.toLowerCase()}. Commit to it for the next 30 days and journal your experience.`, difficulty: 'challenging' },
+    ];
 
     return {
-      content: steps,
+      items: steps.map((step, i) => ({ ...step, id: -(i + 1), verseId, stepNumber: i + 1, source: 'ai', model: 'mock' })),
+      content: JSON.stringify({ steps }),
       tokensUsed: 120,
+      cached: false,
+      provider: 'mock',
+      model: 'mock',
+      stopReason: 'stop',
     };
   },
 
-  generateReflectionQuestions: async (_verseText: string, _reference: string): Promise<AiInsight> => {
+  generateReflectionQuestions: async (verseId: number, _verseText: string, _reference: string): Promise<AiItems<ReflectionQuestion>> => {
     void _verseText; void _reference; // Suppress unused warnings
     await new Promise(resolve => setTimeout(resolve, 1000));
 
-    const questions = `**Personal:** What emotions or memories does this verse stir up in you? How might God be speaking to your current situation through these words?
+    const questions: Pick<ReflectionQuestion, 'question' | 'category'>[] = [
+      { question: 'What emotions or memories does this verse stir up in you? How might God be speaking to your current situation through these words?', category: 'personal' },
+      { question: 'How could applying this verse improve your relationships with family, friends, or coworkers this week?', category: 'relational' },
+      { question: "What does this verse reveal about God's character? How does it deepen your understanding of His love for you?", category: 'spiritual' },
+      { question: 'What is one specific, measurable action you can take today to live out the truth of this verse?', category: 'practical' },
+    ];
 
-**Relational:** How could applying this verse improve your relationships with family, friends, or coworkers this week?
+    return {
+      items: questions.map((question, i) => ({ ...question, id: -(i + 1), verseId, source: 'ai', model: 'mock' })),
+      content: JSON.stringify({ questions }),
+      tokensUsed: 100,
+      cached: false,
+      provider: 'mock',
+      model: 'mock',
+      stopReason: 'stop',
+    };
+  },
 
-**Spiritual:** What does this verse reveal about God's character? How does it deepen your understanding of His love for you?
+  aiSearch: async (query: string, limit?: number): Promise<SemanticSearch> => {
+    await new Promise(resolve => setTimeout(resolve, 900));
 
-**Practical:** What is one specific, measurable action you can take today to live out the truth of this verse?`;
+    const themes = ['anxiety', 'trust', 'provision'];
+    const results: SemanticMatch[] = [
+      {
+        id: 23444, bookId: 50, chapter: 4, verse: 6, bookName: 'Philippians', bookAbbreviation: 'Phil',
+        text: 'Be careful for nothing; but in every thing by prayer and supplication with thanksgiving let your requests be made known unto God.',
+        snippet: 'Be careful for nothing; but in every thing by prayer and supplication with thanksgiving let your requests be made known unto God.',
+        score: 1.9, themes: ['anxiety'], topics: ['Anxiety & Fear'],
+        explanation: 'In the topic Anxiety & Fear, which matches "anxiety"',
+      },
+      {
+        id: 16478, bookId: 20, chapter: 3, verse: 5, bookName: 'Proverbs', bookAbbreviation: 'Prov',
+        text: 'Trust in the LORD with all thine heart; and lean not unto thine own understanding.',
+        snippet: '<mark>Trust</mark> in the LORD with all thine heart; and lean not unto thine own understanding.',
+        score: 1, themes: ['trust'], topics: [],
+        explanation: 'Mentions "trust"',
+      },
+    ];
 
     return {
-      content: questions,
-      tokensUsed: 100,
+      query,
+      themes,
+      results: results.slice(0, limit),
+      content: themes.join(', '),
+      tokensUsed: 60,
+      cached: false,
+      provider: 'mock',
+      model: 'mock',
+      stopReason: 'stop',
     };
   },
 };
@@ -110,13 +395,50 @@ export const ai = {
   checkConnection: async (): Promise<LlmStatus> => {
     return isTauriAvailable() ? aiApi.checkConnection() : mockAiApi.checkConnection();
   },
-  getInsight: async (verseText: string, reference: string): Promise<AiInsight> => {
-    return isTauriAvailable() ? aiApi.getInsight(verseText, reference) : mockAiApi.getInsight(verseText, reference);
+  getInsight: async (verseText: string, reference: string, regenerate?: boolean): Promise<AiInsight> => {
+    return isTauriAvailable() ? aiApi.getInsight(verseText, reference, undefined, regenerate) : mockAiApi.getInsight(verseText, reference);
+  },
+  generateActionSteps: async (verseId: number, verseText: string, reference: string, topic: string, regenerate?: boolean): Promise<AiItems<ActionStep>> => {
+    return isTauriAvailable() ? aiApi.generateActionSteps(verseId, verseText, reference, topic, undefined, regenerate) : mockAiApi.generateActionSteps(verseId, verseText, reference, topic);
+  },
+  generateReflectionQuestions: async (verseId: number, verseText: string, reference: string, regenerate?: boolean): Promise<AiItems<ReflectionQuestion>> => {
+    return isTauriAvailable() ? aiApi.generateReflectionQuestions(verseId, verseText, reference, undefined, regenerate) : mockAiApi.generateReflectionQuestions(verseId, verseText, reference);
+  },
+  aiSearch: async (query: string, limit?: number, translation?: string, regenerate?: boolean): Promise<SemanticSearch> => {
+    return isTauriAvailable() ? aiApi.aiSearch(query, limit, translation, undefined, regenerate) : mockAiApi.aiSearch(query, limit);
+  },
+  cancelGeneration: async (requestId: string): Promise<boolean> => {
+    return isTauriAvailable() ? aiApi.cancelGeneration(requestId) : false;
+  },
+  pullOllamaModel: async (model: string, onProgress?: (progress: OllamaPull) => void): Promise<void> => {
+    if (isTauriAvailable()) await aiApi.pullOllamaModel(model, onProgress);
+  },
+  clearCache: async (): Promise<number> => {
+    return isTauriAvailable() ? aiApi.clearCache() : 0;
+  },
+  getTokenUsage: async (groupBy: UsageGrouping, since?: string): Promise<TokenUsageTotal[]> => {
+    return isTauriAvailable() ? aiApi.getTokenUsage(groupBy, since) : [];
+  },
+  getModelPrices: async (): Promise<ModelPrice[]> => {
+    return isTauriAvailable() ? aiApi.getModelPrices() : [];
+  },
+  setModelPrice: async (price: ModelPrice): Promise<void> => {
+    if (isTauriAvailable()) await aiApi.setModelPrice(price);
+  },
+  deleteModelPrice: async (provider: string, model: string): Promise<boolean> => {
+    return isTauriAvailable() ? aiApi.deleteModelPrice(provider, model) : false;
+  },
+  getPromptTemplates: async (): Promise<PromptTemplate[]> => {
+    return isTauriAvailable() ? aiApi.getPromptTemplates() : [];
+  },
+  updatePromptTemplate: async (name: string, body: string): Promise<PromptTemplate> => {
+    if (!isTauriAvailable()) throw new Error('Prompt templates can only be edited in the desktop app');
+    return aiApi.updatePromptTemplate(name, body);
   },
-  generateActionSteps: async (verseText: string, reference: string, topic: string): Promise<AiInsight> => {
-    return isTauriAvailable() ? aiApi.generateActionSteps(verseText, reference, topic) : mockAiApi.generateActionSteps(verseText, reference, topic);
+  previewPromptTemplate: async (name: string, body?: string, values?: Record<string, string>): Promise<string> => {
+    return isTauriAvailable() ? aiApi.previewPromptTemplate(name, body, values) : body ?? '';
   },
-  generateReflectionQuestions: async (verseText: string, reference: string): Promise<AiInsight> => {
-    return isTauriAvailable() ? aiApi.generateReflectionQuestions(verseText, reference) : mockAiApi.generateReflectionQuestions(verseText, reference);
+  resetPromptTemplate: async (name?: string): Promise<PromptTemplate[]> => {
+    return isTauriAvailable() ? aiApi.resetPromptTemplate(name) : [];
   },
 };
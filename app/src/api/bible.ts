@@ -1,5 +1,5 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { Book, Verse, VerseWithBook, Topic, VerseWithTopic, VerseApplication } from '../lib/types';
+import type { Book, Translation, ImportReport, ParallelPassage, Versification, VerseLocation, Verse, VerseWithBook, SearchResult, Passage, CrossReference, CrossReferenceImportReport, Topic, VerseWithTopic, VerseApplication, ActionStep, ReflectionQuestion } from '../lib/types';
 
 // Bible API
 export const bibleApi = {
@@ -8,29 +8,86 @@ export const bibleApi = {
     return invoke<Book[]>('get_books');
   },
 
-  // Get verses for a chapter
-  getVerses: async (bookId: number, chapter: number): Promise<Verse[]> => {
-    return invoke<Verse[]>('get_verses', { bookId, chapter });
+  // Get available translations (KJV first)
+  getTranslations: async (): Promise<Translation[]> => {
+    return invoke<Translation[]>('get_translations');
+  },
+
+  // Import a translation from an OSIS, USFM or Zefania file (or a folder of USFM files).
+  // The format is detected from the content when omitted; verses are renumbered
+  // from the given versification (KJV by default).
+  importBible: async (path: string, code: string, name?: string, format?: 'osis' | 'usfm' | 'zefania', versification?: Versification): Promise<ImportReport> => {
+    return invoke<ImportReport>('import_bible', { path, code, name, format, versification });
+  },
+
+  // Get verses for a chapter (translation code defaults to KJV)
+  getVerses: async (bookId: number, chapter: number, translation?: string): Promise<Verse[]> => {
+    return invoke<Verse[]>('get_verses', { bookId, chapter, translation });
   },
 
   // Get a single verse by ID
-  getVerse: async (verseId: number): Promise<VerseWithBook> => {
-    return invoke<VerseWithBook>('get_verse', { verseId });
+  getVerse: async (verseId: number, translation?: string): Promise<VerseWithBook> => {
+    return invoke<VerseWithBook>('get_verse', { verseId, translation });
   },
 
   // Get verse by reference string (e.g., "John 3:16")
-  getVerseByReference: async (reference: string): Promise<VerseWithBook> => {
-    return invoke<VerseWithBook>('get_verse_by_reference', { reference });
+  getVerseByReference: async (reference: string, translation?: string, versification?: Versification): Promise<VerseWithBook> => {
+    return invoke<VerseWithBook>('get_verse_by_reference', { reference, translation, versification });
+  },
+
+  // Get passages for a compound reference (e.g., "Rom 8:28-39; 12:1-2")
+  // References may be cited in Hebrew, LXX or Vulgate numbering (e.g., "Mal 3:19" in 'hebrew')
+  getPassages: async (reference: string, translation?: string, versification?: Versification): Promise<Passage[]> => {
+    return invoke<Passage[]>('get_passages', { reference, translation, versification });
+  },
+
+  // Compare a chapter or passage verse by verse across translations (e.g., "Ps 23", ["KJV", "WEB"])
+  comparePassages: async (reference: string, translations: string[]): Promise<ParallelPassage[]> => {
+    return invoke<ParallelPassage[]>('compare_passages', { reference, translations });
+  },
+
+  // Convert a verse number between versification schemes (e.g., Joel 3:1 KJV -> 4:1 Hebrew)
+  convertVersification: async (bookId: number, chapter: number, verse: number, from: Versification, to: Versification): Promise<VerseLocation> => {
+    return invoke<VerseLocation>('convert_versification', { bookId, chapter, verse, from, to });
   },
 
   // Get random verse (for daily verse)
-  getRandomVerse: async (topicId?: number): Promise<VerseWithBook> => {
-    return invoke<VerseWithBook>('get_random_verse', { topicId });
+  getRandomVerse: async (topicId?: number, translation?: string): Promise<VerseWithBook> => {
+    return invoke<VerseWithBook>('get_random_verse', { topicId, translation });
   },
 
-  // Search verses by text
-  searchVerses: async (query: string, limit?: number): Promise<VerseWithBook[]> => {
-    return invoke<VerseWithBook[]>('search_verses', { query, limit });
+  // Full-text search, ranked by relevance with highlighted snippets.
+  // Archaic KJV forms match modern words unless `exact` is set.
+  searchVerses: async (query: string, limit?: number, exact?: boolean, translation?: string): Promise<SearchResult[]> => {
+    return invoke<SearchResult[]>('search_verses', { query, limit, exact, translation });
+  },
+};
+
+// Cross References API
+export const crossReferencesApi = {
+  // Passages a verse points to
+  getCrossReferences: async (verseId: number, limit?: number): Promise<CrossReference[]> => {
+    return invoke<CrossReference[]>('get_cross_references', { verseId, limit });
+  },
+
+  // Verses that point to this verse
+  getCrossReferencesTo: async (verseId: number, limit?: number): Promise<CrossReference[]> => {
+    return invoke<CrossReference[]>('get_cross_references_to', { verseId, limit });
+  },
+
+  // Add a user cross reference (e.g., reference = "Heb 11:3")
+  addCrossReference: async (verseId: number, reference: string, note?: string): Promise<CrossReference> => {
+    return invoke<CrossReference>('add_cross_reference', { verseId, reference, note });
+  },
+
+  // Delete a user cross reference
+  deleteCrossReference: async (id: number): Promise<boolean> => {
+    return invoke<boolean>('delete_cross_reference', { id });
+  },
+
+  // Import OpenBible.info's cross_references.txt (CC BY), replacing earlier imported references
+  importCrossReferences: async (path: string): Promise<CrossReferenceImportReport> => {
+    return invoke<CrossReferenceImportReport>('import_cross_references', { path });
   },
 };
 
@@ -42,8 +99,8 @@ export const topicsApi = {
   },
 
   // Get verses for a topic
-  getVersesByTopic: async (topicId: number, limit?: number): Promise<VerseWithTopic[]> => {
-    return invoke<VerseWithTopic[]>('get_verses_by_topic', { topicId, limit });
+  getVersesByTopic: async (topicId: number, limit?: number, translation?: string): Promise<VerseWithTopic[]> => {
+    return invoke<VerseWithTopic[]>('get_verses_by_topic', { topicId, limit, translation });
   },
 
   // Get topic by slug
@@ -58,6 +115,25 @@ export const applicationApi = {
   getVerseApplication: async (verseId: number): Promise<VerseApplication> => {
     return invoke<VerseApplication>('get_verse_application', { verseId });
   },
+
+  // Save generated ('ai') or user-written ('user') steps after the verse's
+  // existing ones; resolves to all of the verse's steps
+  saveActionSteps: async (verseId: number, steps: ActionStep[]): Promise<ActionStep[]> => {
+    return invoke<ActionStep[]>('save_action_steps', { verseId, steps });
+  },
+
+  saveReflectionQuestions: async (verseId: number, questions: ReflectionQuestion[]): Promise<ReflectionQuestion[]> => {
+    return invoke<ReflectionQuestion[]>('save_reflection_questions', { verseId, questions });
+  },
+
+  // Delete a saved step or question; resolves false for curated ones
+  deleteActionStep: async (id: number): Promise<boolean> => {
+    return invoke<boolean>('delete_action_step', { id });
+  },
+
+  deleteReflectionQuestion: async (id: number): Promise<boolean> => {
+    return invoke<boolean>('delete_reflection_question', { id });
+  },
 };
 
 // Mock data for development (used when Tauri is not available)
@@ -261,15 +337,15 @@ export const mockApplicationApi = {
   getVerseApplication: async (_verseId: number): Promise<VerseApplication> => {
     return {
       actionSteps: [
-        { id: 1, verseId: 1, stepNumber: 1, content: 'Start each day by reading this verse and meditating on its meaning', difficulty: 'easy' },
-        { id: 2, verseId: 1, stepNumber: 2, content: 'Identify one area of your life where you tend to rely on your own understanding', difficulty: 'medium' },
-        { id: 3, verseId: 1, stepNumber: 3, content: 'Practice surrendering a difficult decision to God through prayer', difficulty: 'challenging' },
+        { id: 1, verseId: 1, stepNumber: 1, content: 'Start each day by reading this verse and meditating on its meaning', difficulty: 'easy', source: 'curated', model: null },
+        { id: 2, verseId: 1, stepNumber: 2, content: 'Identify one area of your life where you tend to rely on your own understanding', difficulty: 'medium', source: 'curated', model: null },
+        { id: 3, verseId: 1, stepNumber: 3, content: 'Practice surrendering a difficult decision to God through prayer', difficulty: 'challenging', source: 'curated', model: null },
       ],
       reflectionQuestions: [
-        { id: 1, verseId: 1, question: 'What areas of your life are you trying to control without trusting God?', category: 'personal' },
-        { id: 2, verseId: 1, question: 'How does trusting God impact your relationships with others?', category: 'relational' },
-        { id: 3, verseId: 1, question: 'What would it look like to fully trust God in your current situation?', category: 'spiritual' },
-        { id: 4, verseId: 1, question: 'What is one practical step you can take today to lean on God instead of your own understanding?', category: 'practical' },
+        { id: 1, verseId: 1, question: 'What areas of your life are you trying to control without trusting God?', category: 'personal', source: 'curated', model: null },
+        { id: 2, verseId: 1, question: 'How does trusting God impact your relationships with others?', category: 'relational', source: 'curated', model: null },
+        { id: 3, verseId: 1, question: 'What would it look like to fully trust God in your current situation?', category: 'spiritual', source: 'curated', model: null },
+        { id: 4, verseId: 1, question: 'What is one practical step you can take today to lean on God instead of your own understanding?', category: 'practical', source: 'curated', model: null },
       ],
     };
   },
@@ -318,5 +394,18 @@ export const api = {
     getVerseApplication: async (verseId: number): Promise<VerseApplication> => {
       return isTauriAvailable() ? applicationApi.getVerseApplication(verseId) : mockApplicationApi.getVerseApplication(verseId);
     },
+    // Without Tauri nothing is stored; the steps and questions come back as given
+    saveActionSteps: async (verseId: number, steps: ActionStep[]): Promise<ActionStep[]> => {
+      return isTauriAvailable() ? applicationApi.saveActionSteps(verseId, steps) : steps;
+    },
+    saveReflectionQuestions: async (verseId: number, questions: ReflectionQuestion[]): Promise<ReflectionQuestion[]> => {
+      return isTauriAvailable() ? applicationApi.saveReflectionQuestions(verseId, questions) : questions;
+    },
+    deleteActionStep: async (id: number): Promise<boolean> => {
+      return isTauriAvailable() ? applicationApi.deleteActionStep(id) : false;
+    },
+    deleteReflectionQuestion: async (id: number): Promise<boolean> => {
+      return isTauriAvailable() ? applicationApi.deleteReflectionQuestion(id) : false;
+    },
   },
 };
@@ -16,8 +16,19 @@ export const settingsApi = {
       llmBaseUrl: backend.llmBaseUrl || undefined,
       llmModel: backend.llmModel || undefined,
       llmApiKey: backend.llmApiKey || undefined,
+      llmFallbacks: backend.llmFallbacks ?? [],
+      llmTemperature: backend.llmTemperature ?? undefined,
+      llmStopSequences: backend.llmStopSequences ?? [],
+      ollamaKeepAlive: backend.ollamaKeepAlive || undefined,
+      ollamaNumCtx: backend.ollamaNumCtx ?? undefined,
       dailyVerseEnabled: backend.dailyVerseEnabled,
       dailyVerseTime: backend.dailyVerseTime,
+      aiCacheHours: backend.aiCacheHours,
+      quotaDailyTokens: backend.quotaDailyTokens ?? undefined,
+      quotaMonthlyTokens: backend.quotaMonthlyTokens ?? undefined,
+      quotaDailyCost: backend.quotaDailyCost ?? undefined,
+      quotaMonthlyCost: backend.quotaMonthlyCost ?? undefined,
+      quotaLocalProviders: backend.quotaLocalProviders,
     };
   },
 
@@ -32,8 +43,20 @@ export const settingsApi = {
     if (settings.llmBaseUrl !== undefined) updates.llmBaseUrl = settings.llmBaseUrl || undefined;
     if (settings.llmModel !== undefined) updates.llmModel = settings.llmModel || undefined;
     if (settings.llmApiKey !== undefined) updates.llmApiKey = settings.llmApiKey || undefined;
+    if (settings.llmFallbacks !== undefined) updates.llmFallbacks = settings.llmFallbacks;
+    if ('llmTemperature' in settings) updates.llmTemperature = settings.llmTemperature;
+    if (settings.llmStopSequences !== undefined) updates.llmStopSequences = settings.llmStopSequences;
+    if (settings.ollamaKeepAlive !== undefined) updates.ollamaKeepAlive = settings.ollamaKeepAlive || undefined;
+    if ('ollamaNumCtx' in settings) updates.ollamaNumCtx = settings.ollamaNumCtx;
     if (settings.dailyVerseEnabled !== undefined) updates.dailyVerseEnabled = settings.dailyVerseEnabled;
     if (settings.dailyVerseTime !== undefined) updates.dailyVerseTime = settings.dailyVerseTime;
+    if (settings.aiCacheHours !== undefined) updates.aiCacheHours = settings.aiCacheHours;
+    // Quotas are cleared by passing the key with an undefined value
+    if ('quotaDailyTokens' in settings) updates.quotaDailyTokens = settings.quotaDailyTokens;
+    if ('quotaMonthlyTokens' in settings) updates.quotaMonthlyTokens = settings.quotaMonthlyTokens;
+    if ('quotaDailyCost' in settings) updates.quotaDailyCost = settings.quotaDailyCost;
+    if ('quotaMonthlyCost' in settings) updates.quotaMonthlyCost = settings.quotaMonthlyCost;
+    if (settings.quotaLocalProviders !== undefined) updates.quotaLocalProviders = settings.quotaLocalProviders;
 
     const merged = { ...current, ...updates };
     console.log('[Settings API] Saving settings:', merged);
@@ -51,8 +74,12 @@ export const mockSettingsApi = {
       llmBaseUrl: 'http://localhost:1234/v1',
       llmModel: '',
       llmApiKey: '',
+      llmFallbacks: [],
+      llmStopSequences: [],
       dailyVerseEnabled: true,
       dailyVerseTime: '07:00',
+      aiCacheHours: 168,
+      quotaLocalProviders: false,
     };
   },
 
@@ -8,12 +8,68 @@ export interface Book {
   sortOrder: number;
 }
 
-export interface Verse {
+export interface Translation {
   id: number;
+  code: string;
+  name: string;
+  language: string;
+  year?: number;
+  verseCount: number;
+}
+
+export type Versification = 'kjv' | 'hebrew' | 'lxx' | 'vulgate';
+
+export interface VerseLocation {
   bookId: number;
   chapter: number;
   verse: number;
+}
+
+export interface UnmappedVerse {
+  reference: string;
+  reason: string;
+}
+
+export interface ImportReport {
+  translationId: number;
+  code: string;
+  name: string;
+  format: 'osis' | 'usfm' | 'zefania';
+  imported: number;
+  unmapped: UnmappedVerse[];
+}
+
+export interface Verse {
+  id: number;
+  bookId: number;
+  chapter: number;
+  verse: number;        // 0 for a Psalm title
+  endVerse?: number;    // Last verse covered when merged with the following verses
   text: string;
+  markup?: string;      // Text with <i> (supplied words) and <j> (words of Christ) tags, &amp;-escaped
+}
+
+export type ParallelStatus = 'present' | 'merged' | 'versification' | 'missing';
+
+export interface ParallelCell {
+  status: ParallelStatus;
+  verseId?: number;     // For merged cells, the verse holding the text
+  text?: string;
+  endVerse?: number;
+  mergedInto?: number;
+}
+
+export interface ParallelRow {
+  chapter: number;
+  verse: number;
+  cells: ParallelCell[];
+}
+
+export interface ParallelPassage {
+  reference: string;
+  bookId: number;
+  translations: string[];
+  rows: ParallelRow[];
 }
 
 export interface VerseWithBook extends Verse {
@@ -21,6 +77,45 @@ export interface VerseWithBook extends Verse {
   bookAbbreviation: string;
 }
 
+export interface SearchResult extends VerseWithBook {
+  snippet: string;
+  score: number;
+}
+
+// A verse found by AI search; `score` adds up its topic and keyword matches
+export interface SemanticMatch extends SearchResult {
+  themes: string[];
+  topics: string[];
+  explanation: string;
+}
+
+export interface Passage {
+  reference: string;
+  bookId: number;
+  startChapter: number;
+  startVerse: number | null;
+  endChapter: number;
+  endVerse: number | null;
+  verses: VerseWithBook[];
+}
+
+export interface CrossReference {
+  id: number;
+  fromVerseId: number;
+  toVerseId: number;
+  toVerseEndId: number | null;
+  votes: number;
+  source: 'openbible' | 'user';
+  note: string | null;
+  reference: string;
+  text: string;
+}
+
+export interface CrossReferenceImportReport {
+  imported: number;
+  skipped: number;      // Links to verses the KJV doesn't have
+}
+
 export interface VerseReference {
   bookId: number;
   chapter: number;
@@ -53,12 +148,18 @@ export interface VerseWithTopic extends VerseWithBook {
 }
 
 // Application Content Types
+// Curated items come with the app; 'ai' ones were generated and saved, and
+// 'user' ones were written by the user
+export type ApplicationSource = 'curated' | 'ai' | 'user';
+
 export interface ActionStep {
   id: number;
   verseId: number;
   stepNumber: number;
   content: string;
   difficulty: 'easy' | 'medium' | 'challenging';
+  source: ApplicationSource;
+  model: string | null;  // The model that generated an 'ai' step
 }
 
 export interface ReflectionQuestion {
@@ -66,6 +167,8 @@ export interface ReflectionQuestion {
   verseId: number;
   question: string;
   category: 'personal' | 'relational' | 'spiritual' | 'practical';
+  source: ApplicationSource;
+  model: string | null;  // The model that generated an 'ai' question
 }
 
 export interface VerseApplication {
@@ -150,12 +253,31 @@ export interface Settings {
   llmBaseUrl?: string;
   llmModel?: string;
   llmApiKey?: string;  // API key for cloud providers (OpenAI, Claude)
+  llmFallbacks: LLMFallback[];  // Tried in order when the provider above is unavailable
+  llmTemperature?: number;  // Unset: the default, 0.7
+  llmStopSequences: string[];  // Text that ends a completion early
+  ollamaKeepAlive?: string;  // How long Ollama keeps the model loaded, e.g. "10m"
+  ollamaNumCtx?: number;  // Ollama context window size
   dailyVerseEnabled: boolean;
   dailyVerseTime: string;
+  aiCacheHours: number;  // How long AI responses are reused; 0 disables the cache
+  // Token and dollar (USD) caps on AI usage; unset means no cap
+  quotaDailyTokens?: number;
+  quotaMonthlyTokens?: number;
+  quotaDailyCost?: number;
+  quotaMonthlyCost?: number;
+  quotaLocalProviders: boolean;  // Also cap LM Studio/Ollama, which are exempt by default
 }
 
 export type LLMProvider = 'lmstudio' | 'claude' | 'openai' | 'ollama';
 
+export interface LLMFallback {
+  provider: LLMProvider;
+  baseUrl?: string;
+  model?: string;
+  apiKey?: string;
+}
+
 export interface LLMConfig {
   provider: LLMProvider;
   baseUrl?: string;
@@ -172,6 +294,26 @@ export interface LLMStatus {
   error?: string;
 }
 
+// Error Types
+// What every backend command rejects with
+export type AppErrorCode =
+  | 'notFound'
+  | 'invalidReference'
+  | 'invalidInput'
+  | 'llmUnreachable'
+  | 'llmAuth'
+  | 'rateLimited'
+  | 'quotaExceeded'
+  | 'llmFailed'
+  | 'cancelled'
+  | 'database'
+  | 'internal';
+
+export interface AppError {
+  code: AppErrorCode;
+  message: string;
+}
+
 // Quota Types
 export interface QuotaStatus {
   tier: 'free' | 'premium';
@@ -1,5 +1,6 @@
 import { clsx, type ClassValue } from 'clsx';
 import { twMerge } from 'tailwind-merge';
+import type { AppError } from './types';
 
 // Merge Tailwind classes intelligently
 export function cn(...inputs: ClassValue[]) {
@@ -136,6 +137,21 @@ export function debounce<T extends (...args: unknown[]) => unknown>(
   };
 }
 
+// The error a backend command rejected with, or null for anything else
+export function parseAppError(error: unknown): AppError | null {
+  if (typeof error !== 'object' || error === null) return null;
+  const { code, message } = error as Partial<AppError>;
+  return typeof code === 'string' && typeof message === 'string' ? (error as AppError) : null;
+}
+
+// A readable message for any error
+export function errorMessage(error: unknown, fallback = 'Something went wrong'): string {
+  const appError = parseAppError(error);
+  if (appError) return appError.message;
+  if (error instanceof Error) return error.message;
+  return typeof error === 'string' ? error : fallback;
+}
+
 // Generate unique ID
 export function generateId(): string {
   return Math.random().toString(36).substring(2, 9);
@@ -6,6 +6,7 @@ import { Card } from '../components/ui/Card';
 import { VerseCard } from '../components/bible/VerseCard';
 import { api } from '../api/bible';
 import type { VerseWithBook } from '../lib/types';
+import { errorMessage } from '../lib/utils';
 
 export const Search: React.FC = () => {
   const [searchParams, setSearchParams] = useSearchParams();
@@ -14,6 +15,7 @@ export const Search: React.FC = () => {
   const [results, setResults] = useState<VerseWithBook[]>([]);
   const [loading, setLoading] = useState(false);
   const [searched, setSearched] = useState(false);
+  const [searchError, setSearchError] = useState<string | null>(null);
 
   // Perform search when params change
   useEffect(() => {
@@ -30,6 +32,7 @@ export const Search: React.FC = () => {
   const performSearch = async (searchQuery: string, _useAI: boolean) => {
     setLoading(true);
     setSearched(true);
+    setSearchError(null);
 
     try {
       const searchResults = await api.bible.searchVerses(searchQuery, 50);
@@ -37,6 +40,7 @@ export const Search: React.FC = () => {
     } catch (error) {
       console.error('Search failed:', error);
       setResults([]);
+      setSearchError(errorMessage(error, 'Search failed'));
     }
 
     setLoading(false);
@@ -134,10 +138,10 @@ export const Search: React.FC = () => {
         ) : (
           <Card className="text-center py-12">
             <p className="text-gray-500 dark:text-gray-400 mb-2">
-              No results found for "{query}"
+              {searchError ?? `No results found for "${query}"`}
             </p>
             <p className="text-sm text-gray-400 dark:text-gray-500">
-              Try different keywords or enable AI search for better results
+              {searchError ? 'Check the search and try again' : 'Try different keywords or enable AI search for better results'}
             </p>
           </Card>
         )
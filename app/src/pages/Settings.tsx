@@ -4,9 +4,9 @@ import { Card, CardHeader, CardTitle, CardDescription, CardContent } from '../co
 import { Button } from '../components/ui/Button';
 import { Input } from '../components/ui/Input';
 import { useSettingsStore } from '../stores/settingsStore';
-import { ai } from '../api/ai';
+import { ai, type BackendStatus } from '../api/ai';
 import { settings as settingsApi } from '../api/settings';
-import { cn } from '../lib/utils';
+import { cn, errorMessage } from '../lib/utils';
 import type { LLMProvider, Settings as SettingsType } from '../lib/types';
 
 export const Settings: React.FC = () => {
@@ -27,10 +27,12 @@ export const Settings: React.FC = () => {
 
   const [connectionStatus, setConnectionStatus] = useState<'idle' | 'testing' | 'success' | 'error'>('idle');
   const [connectionError, setConnectionError] = useState<string>('');
+  const [fallbackStatuses, setFallbackStatuses] = useState<BackendStatus[]>([]);
 
   const testConnection = async () => {
     setConnectionStatus('testing');
     setConnectionError('');
+    setFallbackStatuses([]);
 
     try {
       // First sync settings to backend so the connection test uses current values
@@ -43,6 +45,7 @@ export const Settings: React.FC = () => {
 
       // Now test the connection
       const status = await ai.checkConnection();
+      setFallbackStatuses(status.fallbacks);
       if (status.connected) {
         setConnectionStatus('success');
       } else {
@@ -51,7 +54,7 @@ export const Settings: React.FC = () => {
       }
     } catch (error) {
       setConnectionStatus('error');
-      setConnectionError(error instanceof Error ? error.message : 'Connection failed');
+      setConnectionError(errorMessage(error, 'Connection failed'));
     }
   };
 
@@ -249,6 +252,21 @@ export const Settings: React.FC = () => {
                   </span>
                 )}
               </div>
+              {fallbackStatuses.length > 0 && (
+                <ul className="space-y-1 text-sm text-gray-600 dark:text-gray-400">
+                  {fallbackStatuses.map((fallback, i) => (
+                    <li key={i} className="flex items-center gap-1">
+                      {fallback.connected ? (
+                        <CheckCircle2 className="w-4 h-4 text-green-600 dark:text-green-400" />
+                      ) : (
+                        <XCircle className="w-4 h-4 text-red-600 dark:text-red-400" />
+                      )}
+                      Fallback {i + 1}: {fallback.provider}{fallback.model ? ` (${fallback.model})` : ''}
+                      {fallback.connected ? ' connected' : ' not reachable'}
+                    </li>
+                  ))}
+                </ul>
+              )}
             </div>
           ) : (
             <div className="space-y-4 pt-4 border-t border-gray-200 dark:border-slate-700">
@@ -331,6 +349,27 @@ export const Settings: React.FC = () => {
             <p className="pt-2">
               Built with Tauri, React, and love for Scripture.
             </p>
+            <p className="pt-2">
+              Cross references from{' '}
+              <a
+                href="https://www.openbible.info/labs/cross-references/"
+                target="_blank"
+                rel="noreferrer"
+                className="underline hover:text-gray-900 dark:hover:text-white"
+              >
+                OpenBible.info
+              </a>
+              , licensed{' '}
+              <a
+                href="https://creativecommons.org/licenses/by/4.0/"
+                target="_blank"
+                rel="noreferrer"
+                className="underline hover:text-gray-900 dark:hover:text-white"
+              >
+                CC BY
+              </a>
+              .
+            </p>
           </div>
         </CardContent>
       </Card>
@@ -1,6 +1,6 @@
 import { useState } from 'react';
 import { useParams, useNavigate } from 'react-router-dom';
-import { useQuery, useMutation } from '@tanstack/react-query';
+import { useQuery, useMutation, useQueryClient } from '@tanstack/react-query';
 import {
   ArrowLeft,
   Bookmark,
@@ -12,18 +12,20 @@ import {
   HelpCircle,
   Loader2,
   ChevronDown,
-  ChevronUp
+  ChevronUp,
+  Save
 } from 'lucide-react';
 import { api } from '../api/bible';
-import { ai } from '../api/ai';
+import { ai, isTruncated } from '../api/ai';
 import { Button } from '../components/ui/Button';
 import { Card } from '../components/ui/Card';
 import { cn, formatReference } from '../lib/utils';
-import type { ActionStep, ReflectionQuestion } from '../lib/types';
+import type { ActionStep, ApplicationSource, ReflectionQuestion } from '../lib/types';
 
 export function VerseDetail() {
   const { id } = useParams<{ id: string }>();
   const navigate = useNavigate();
+  const queryClient = useQueryClient();
   const [showAiInsight, setShowAiInsight] = useState(false);
   const [aiInsightExpanded, setAiInsightExpanded] = useState(true);
   const [activeTab, setActiveTab] = useState<'actions' | 'questions' | 'ai'>('actions');
@@ -46,10 +48,10 @@ export function VerseDetail() {
 
   // AI insight mutation
   const aiInsightMutation = useMutation({
-    mutationFn: async () => {
+    mutationFn: async (regenerate: boolean) => {
       if (!verse) throw new Error('Verse not loaded');
       const reference = formatReference(verse.bookName, verse.chapter, verse.verse);
-      return ai.getInsight(verse.text, reference);
+      return ai.getInsight(verse.text, reference, regenerate);
     },
   });
 
@@ -60,7 +62,7 @@ export function VerseDetail() {
       const reference = formatReference(verse.bookName, verse.chapter, verse.verse);
       // Use "life" as default topic
       const topic = 'life';
-      return ai.generateActionSteps(verse.text, reference, topic);
+      return ai.generateActionSteps(verseId, verse.text, reference, topic);
     },
   });
 
@@ -69,13 +71,30 @@ export function VerseDetail() {
     mutationFn: async () => {
       if (!verse) throw new Error('Verse not loaded');
       const reference = formatReference(verse.bookName, verse.chapter, verse.verse);
-      return ai.generateReflectionQuestions(verse.text, reference);
+      return ai.generateReflectionQuestions(verseId, verse.text, reference);
+    },
+  });
+
+  // Saving generated items adds them to the verse's application content
+  const saveStepsMutation = useMutation({
+    mutationFn: async (steps: ActionStep[]) => api.application.saveActionSteps(verseId, steps),
+    onSuccess: () => {
+      actionStepsMutation.reset();
+      queryClient.invalidateQueries({ queryKey: ['application', verseId] });
+    },
+  });
+
+  const saveQuestionsMutation = useMutation({
+    mutationFn: async (questions: ReflectionQuestion[]) => api.application.saveReflectionQuestions(verseId, questions),
+    onSuccess: () => {
+      reflectionMutation.reset();
+      queryClient.invalidateQueries({ queryKey: ['application', verseId] });
     },
   });
 
   const handleGetAiInsight = () => {
     setShowAiInsight(true);
-    aiInsightMutation.mutate();
+    aiInsightMutation.mutate(false);
   };
 
   const handleGenerateActionSteps = () => {
@@ -186,7 +205,22 @@ export function VerseDetail() {
                 <div className="prose prose-sm max-w-none text-primary">
                   <div className="whitespace-pre-wrap">{aiInsightMutation.data.content}</div>
                   <p className="text-xs text-secondary mt-3">
-                    Tokens used: {aiInsightMutation.data.tokensUsed}
+                    {aiInsightMutation.data.cached ? (
+                      <>
+                        Saved response ·{' '}
+                        <button
+                          className="underline hover:text-primary"
+                          onClick={() => aiInsightMutation.mutate(true)}
+                        >
+                          Regenerate
+                        </button>
+                      </>
+                    ) : (
+                      <>
+                        Tokens used: {aiInsightMutation.data.tokensUsed} · {aiInsightMutation.data.provider}
+                        {isTruncated(aiInsightMutation.data.stopReason) && ' · cut short at the length limit'}
+                      </>
+                    )}
                   </p>
                 </div>
               ) : null}
@@ -238,8 +272,10 @@ export function VerseDetail() {
                 steps={application?.actionSteps || []}
                 onGenerate={handleGenerateActionSteps}
                 isGenerating={actionStepsMutation.isPending}
-                generatedContent={actionStepsMutation.data?.content}
-                error={actionStepsMutation.isError ? 'Failed to generate action steps' : undefined}
+                generatedSteps={actionStepsMutation.data?.items}
+                onSave={(steps) => saveStepsMutation.mutate(steps)}
+                isSaving={saveStepsMutation.isPending}
+                error={actionStepsMutation.isError ? 'Failed to generate action steps' : saveStepsMutation.isError ? 'Failed to save action steps' : undefined}
               />
             )}
             {activeTab === 'questions' && (
@@ -247,8 +283,10 @@ export function VerseDetail() {
                 questions={application?.reflectionQuestions || []}
                 onGenerate={handleGenerateReflections}
                 isGenerating={reflectionMutation.isPending}
-                generatedContent={reflectionMutation.data?.content}
-                error={reflectionMutation.isError ? 'Failed to generate reflection questions' : undefined}
+                generatedQuestions={reflectionMutation.data?.items}
+                onSave={(questions) => saveQuestionsMutation.mutate(questions)}
+                isSaving={saveQuestionsMutation.isPending}
+                error={reflectionMutation.isError ? 'Failed to generate reflection questions' : saveQuestionsMutation.isError ? 'Failed to save reflection questions' : undefined}
               />
             )}
           </>
@@ -258,15 +296,42 @@ export function VerseDetail() {
   );
 }
 
+// Marks steps and questions that aren't curated
+function SourceBadge({ source, model }: { source: ApplicationSource; model: string | null }) {
+  if (source === 'curated') return null;
+  return (
+    <span className="text-xs text-secondary" title={model ? `Generated by ${model}` : undefined}>
+      {source === 'ai' ? 'AI' : 'Yours'}
+    </span>
+  );
+}
+
+// Header over generated items that aren't saved yet
+function GeneratedHeader({ title, onSave, isSaving }: { title: string; onSave: () => void; isSaving: boolean }) {
+  return (
+    <div className="flex items-center gap-2">
+      <Sparkles className="h-5 w-5 text-brand-primary" />
+      <span className="font-semibold text-primary">{title}</span>
+      <div className="flex-1" />
+      <Button variant="secondary" size="sm" onClick={onSave} disabled={isSaving}>
+        {isSaving ? <Loader2 className="h-4 w-4 mr-2 animate-spin" /> : <Save className="h-4 w-4 mr-2" />}
+        Save to this verse
+      </Button>
+    </div>
+  );
+}
+
 interface ActionStepsProps {
   steps: ActionStep[];
   onGenerate: () => void;
   isGenerating: boolean;
-  generatedContent?: string;
+  generatedSteps?: ActionStep[];
+  onSave: (steps: ActionStep[]) => void;
+  isSaving: boolean;
   error?: string;
 }
 
-function ActionSteps({ steps, onGenerate, isGenerating, generatedContent, error }: ActionStepsProps) {
+function ActionSteps({ steps: savedSteps, onGenerate, isGenerating, generatedSteps, onSave, isSaving, error }: ActionStepsProps) {
   const [completedSteps, setCompletedSteps] = useState<Set<number>>(new Set());
 
   const toggleStep = (id: number) => {
@@ -279,20 +344,8 @@ function ActionSteps({ steps, onGenerate, isGenerating, generatedContent, error
     setCompletedSteps(newCompleted);
   };
 
-  // Show AI-generated content if available
-  if (generatedContent) {
-    return (
-      <Card className="p-6 bg-gradient-to-br from-brand-primary/5 to-brand-accent/5 border-brand-primary/20">
-        <div className="flex items-center gap-2 mb-4">
-          <Sparkles className="h-5 w-5 text-brand-primary" />
-          <span className="font-semibold text-primary">AI-Generated Action Steps</span>
-        </div>
-        <div className="prose prose-sm max-w-none text-primary whitespace-pre-wrap">
-          {generatedContent}
-        </div>
-      </Card>
-    );
-  }
+  // Show AI-generated steps if available
+  const steps = generatedSteps ?? savedSteps;
 
   if (steps.length === 0) {
     return (
@@ -326,6 +379,10 @@ function ActionSteps({ steps, onGenerate, isGenerating, generatedContent, error
 
   return (
     <div className="space-y-3">
+      {generatedSteps && (
+        <GeneratedHeader title="AI-Generated Action Steps" onSave={() => onSave(generatedSteps)} isSaving={isSaving} />
+      )}
+      {error && <p className="text-red-500 text-sm">{error}</p>}
       {steps.map((step) => (
         <Card
           key={step.id}
@@ -355,6 +412,7 @@ function ActionSteps({ steps, onGenerate, isGenerating, generatedContent, error
                 )}>
                   {step.difficulty}
                 </span>
+                {!generatedSteps && <SourceBadge source={step.source} model={step.model} />}
               </div>
               <p className={cn(
                 'text-primary',
@@ -374,27 +432,17 @@ interface ReflectionQuestionsProps {
   questions: ReflectionQuestion[];
   onGenerate: () => void;
   isGenerating: boolean;
-  generatedContent?: string;
+  generatedQuestions?: ReflectionQuestion[];
+  onSave: (questions: ReflectionQuestion[]) => void;
+  isSaving: boolean;
   error?: string;
 }
 
-function ReflectionQuestions({ questions, onGenerate, isGenerating, generatedContent, error }: ReflectionQuestionsProps) {
+function ReflectionQuestions({ questions: savedQuestions, onGenerate, isGenerating, generatedQuestions, onSave, isSaving, error }: ReflectionQuestionsProps) {
   const [expandedQuestion, setExpandedQuestion] = useState<number | null>(null);
 
-  // Show AI-generated content if available
-  if (generatedContent) {
-    return (
-      <Card className="p-6 bg-gradient-to-br from-brand-primary/5 to-brand-accent/5 border-brand-primary/20">
-        <div className="flex items-center gap-2 mb-4">
-          <Sparkles className="h-5 w-5 text-brand-primary" />
-          <span className="font-semibold text-primary">AI-Generated Reflection Questions</span>
-        </div>
-        <div className="prose prose-sm max-w-none text-primary whitespace-pre-wrap">
-          {generatedContent}
-        </div>
-      </Card>
-    );
-  }
+  // Show AI-generated questions if available
+  const questions = generatedQuestions ?? savedQuestions;
 
   if (questions.length === 0) {
     return (
@@ -436,6 +484,10 @@ function ReflectionQuestions({ questions, onGenerate, isGenerating, generatedCon
 
   return (
     <div className="space-y-3">
+      {generatedQuestions && (
+        <GeneratedHeader title="AI-Generated Reflection Questions" onSave={() => onSave(generatedQuestions)} isSaving={isSaving} />
+      )}
+      {error && <p className="text-red-500 text-sm">{error}</p>}
       {questions.map((question) => (
         <Card
           key={question.id}
@@ -454,6 +506,7 @@ function ReflectionQuestions({ questions, onGenerate, isGenerating, generatedCon
                 )}>
                   {question.category}
                 </span>
+                {!generatedQuestions && <SourceBadge source={question.source} model={question.model} />}
               </div>
               <p className="text-primary font-medium">{question.question}</p>
 
@@ -38,8 +38,12 @@ export const useSettingsStore = create<SettingsState>()(
       llmBaseUrl: 'http://localhost:1234/v1',
       llmModel: '',
       llmApiKey: '',
+      llmFallbacks: [],
+      llmStopSequences: [],
       dailyVerseEnabled: true,
       dailyVerseTime: '07:00',
+      aiCacheHours: 168,
+      quotaLocalProviders: false,
 
       // Computed
       effectiveTheme: getEffectiveTheme('system'),
@@ -60,7 +64,7 @@ export const useSettingsStore = create<SettingsState>()(
         if (llmProvider === 'lmstudio') {
           llmBaseUrl = 'http://localhost:1234/v1';
         } else if (llmProvider === 'ollama') {
-          llmBaseUrl = 'http://localhost:11434/v1';
+          llmBaseUrl = 'http://localhost:11434';
         } else if (llmProvider === 'openai') {
           llmBaseUrl = 'https://api.openai.com/v1';
         } else if (llmProvider === 'claude') {